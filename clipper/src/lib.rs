@@ -11,7 +11,7 @@ use vst::{
     util::AtomicFloat,
 };
 
-use common::{ease_in_expo, make_strings};
+use common::{ease_in_expo, make_strings, mapping::Mapping, smoothing::Smoothing};
 struct Clipper {
     params: Arc<RawParameters>,
 }
@@ -53,7 +53,7 @@ impl Plugin for Clipper {
 
     // Output audio given the current state of the VST
     fn process(&mut self, buffer: &mut AudioBuffer<f32>) {
-        let params = Parameters::from(self.params.as_ref());
+        let params = self.params.snapshot();
         let wet_dry = params.wet_dry;
         let num_samples = buffer.samples();
 
@@ -77,6 +77,8 @@ impl Plugin for Clipper {
             let out = out * params.post_amplify;
             right_output[i] = right_input[i] * (1.0 - wet_dry) + out * wet_dry;
         }
+
+        self.params.flush_edits();
     }
 
     // The raw parameters exposed to the host
@@ -85,7 +87,7 @@ impl Plugin for Clipper {
     }
 }
 
-struct Parameters {
+pub struct Parameters {
     clip_level: f32,
     pre_amplify: f32,
     post_amplify: f32,
@@ -108,11 +110,11 @@ macro_rules! table {
         $macro! {
         //  RawParameter identifier, ParameterType identifier
             RawParameters,          ParameterType;
-        //  variant      field_name     name             idx  default    strings
-            WetDry,      wet_dry,       "Wet/Dry",       0,   1.0,       |x: f32| make_strings(x * 100.0, "% Wet");
-            PreAmp,      pre_amplify,   "Pre-Amplify",   1,   0.125,     |x: f32| make_strings(x * 100.0, "%");
-            ClipLevel,   clip_level,    "Clip Level",    2,   0.5,       |x: f32| make_strings(x, "");
-            PostAmp,     post_amplify,  "Post-Amplify",  3,   0.25,      |x: f32| make_strings(x * 100.0, "%");
+        //  variant    field_name    name            idx  default  smoothing        mapping          usable_min  usable_max  strings
+            WetDry,    wet_dry,      "Wet/Dry",      0,   1.0,     Smoothing::None, Mapping::Linear, 0.0,        1.0,        |x: f32| make_strings(x * 100.0, "% Wet");
+            PreAmp,    pre_amplify,  "Pre-Amplify",  1,   0.125,   Smoothing::None, Mapping::Linear, 0.0,        1.0,        |x: f32| make_strings(x * 100.0, "%");
+            ClipLevel, clip_level,   "Clip Level",   2,   0.5,     Smoothing::None, Mapping::Linear, 0.0,        1.0,        |x: f32| make_strings(x, "");
+            PostAmp,   post_amplify, "Post-Amplify", 3,   0.25,    Smoothing::None, Mapping::Linear, 0.0,        1.0,        |x: f32| make_strings(x * 100.0, "%");
         }
     };
 }
@@ -123,5 +125,12 @@ impl ParameterType {
 
 impl_all! {RawParameters, ParameterType, table}
 
+impl RawParameters {
+    // None of the parameters can be typed in.
+    fn parse_text(&self, _parameter: ParameterType, _text: &str) -> Option<f32> {
+        None
+    }
+}
+
 // Export symbols for main
 vst::plugin_main!(Clipper);
@@ -0,0 +1,105 @@
+use std::marker::PhantomData;
+
+use vst::event::MidiEvent;
+
+/// Sends parameter changes out as MIDI CC messages, so that motorized faders
+/// and other controllers with feedback follow the plugin. Each parameter can
+/// be mapped to one CC number. To avoid flooding the host, changes are sent at
+/// most once every `min_interval` samples, and only when a parameter's 7-bit
+/// value actually changes.
+///
+/// `P` is the plugin's parameter type, and `N` is its number of parameters.
+pub struct CcFeedback<P, const N: usize> {
+    // The CC number each parameter is sent as, if any.
+    mapping: [Option<u8>; N],
+    // The value last sent for each parameter, or None if it hasn't been sent
+    // since it was mapped.
+    sent: [Option<u8>; N],
+    // The MIDI channel, from 0 to 15.
+    channel: u8,
+    min_interval: usize,
+    // Samples since changes were last sent.
+    elapsed: usize,
+    events: Vec<MidiEvent>,
+    parameter: PhantomData<P>,
+}
+
+impl<P: Into<i32>, const N: usize> CcFeedback<P, N> {
+    /// Feedback with nothing mapped, sent on the first MIDI channel.
+    pub fn new(min_interval: usize) -> CcFeedback<P, N> {
+        CcFeedback {
+            mapping: [None; N],
+            sent: [None; N],
+            channel: 0,
+            min_interval,
+            elapsed: min_interval,
+            events: Vec::with_capacity(N),
+            parameter: PhantomData,
+        }
+    }
+
+    /// Send `parameter` as the CC number `cc`, or stop sending it if `cc` is
+    /// None. A newly mapped parameter is sent with the next update, so that
+    /// the controller jumps to its current value. CC numbers above 127 are
+    /// ignored.
+    pub fn set_mapping(&mut self, parameter: P, cc: Option<u8>) {
+        let index = Self::index(parameter);
+        self.mapping[index] = cc.filter(|&cc| cc < 128);
+        self.sent[index] = None;
+    }
+
+    pub fn mapping(&self, parameter: P) -> Option<u8> {
+        self.mapping[Self::index(parameter)]
+    }
+
+    /// Set the MIDI channel, from 0 to 15, that messages are sent on.
+    pub fn set_channel(&mut self, channel: u8) {
+        self.channel = channel.min(15);
+    }
+
+    /// Set the fewest samples between two sends.
+    pub fn set_min_interval(&mut self, min_interval: usize) {
+        self.min_interval = min_interval;
+    }
+
+    /// Note that `samples` more samples have passed, and, if it has been long
+    /// enough since the last send, queue a message for every mapped parameter
+    /// whose value differs from what was last sent. `values` are the normalized
+    /// parameter values, usually from `RawParameters::all_normalized`. Returns
+    /// the queued messages, which replace any queued by an earlier update.
+    pub fn update(&mut self, values: &[f32; N], samples: usize) -> &[MidiEvent] {
+        self.events.clear();
+        self.elapsed = self.elapsed.saturating_add(samples);
+        if self.elapsed < self.min_interval {
+            return &self.events;
+        }
+        for ((&cc, sent), &value) in self.mapping.iter().zip(self.sent.iter_mut()).zip(values) {
+            let cc = match cc {
+                Some(cc) => cc,
+                None => continue,
+            };
+            let value = (value.clamp(0.0, 1.0) * 127.0).round() as u8;
+            if *sent != Some(value) {
+                *sent = Some(value);
+                self.events.push(MidiEvent {
+                    data: [0xB0 | self.channel, cc, value],
+                    delta_frames: 0,
+                    live: true,
+                    note_length: None,
+                    note_offset: None,
+                    detune: 0,
+                    note_off_velocity: 0,
+                });
+            }
+        }
+        if !self.events.is_empty() {
+            self.elapsed = 0;
+        }
+        &self.events
+    }
+
+    fn index(parameter: P) -> usize {
+        let index: i32 = parameter.into();
+        index as usize
+    }
+}
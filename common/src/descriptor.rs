@@ -0,0 +1,25 @@
+use crate::mapping::Mapping;
+
+/// A description of one parameter which doesn't depend on the plugin or on
+/// the VST2 API, built from a plugin's parameter table. Tools, and adapters
+/// for other plugin formats, can read every plugin's parameters through
+/// these. See `descriptors`, which the parameter macros generate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamDescriptor {
+    /// The name shown to the user.
+    pub name: String,
+    /// The parameter's index, as used by the host.
+    pub index: usize,
+    /// The default normalized value.
+    pub default: f32,
+    /// The normalized values which have an effect, from `min` to `max`. See
+    /// `get_usable_range`.
+    pub min: f32,
+    pub max: f32,
+    /// The units shown after the parameter's default value, which may be
+    /// empty.
+    pub unit: String,
+    /// Whether the parameter is continuous or stepped, and how its
+    /// normalized value maps to the value it controls.
+    pub kind: Mapping,
+}
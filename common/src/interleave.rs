@@ -0,0 +1,48 @@
+/// Split interleaved audio, where each frame holds one sample of every
+/// channel, into one buffer per channel. A partial frame at the end is
+/// dropped.
+pub fn deinterleave(samples: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    if channels == 0 {
+        return Vec::new();
+    }
+    let mut buffers = vec![Vec::with_capacity(samples.len() / channels); channels];
+    for frame in samples.chunks_exact(channels) {
+        for (buffer, &x) in buffers.iter_mut().zip(frame.iter()) {
+            buffer.push(x);
+        }
+    }
+    buffers
+}
+
+/// Join one buffer per channel into interleaved audio, the reverse of
+/// `deinterleave`. If the buffers differ in length, the output is as long as
+/// the shortest one.
+pub fn interleave(buffers: &[Vec<f32>]) -> Vec<f32> {
+    let frames = buffers.iter().map(Vec::len).min().unwrap_or(0);
+    let mut samples = Vec::with_capacity(frames * buffers.len());
+    for i in 0..frames {
+        samples.extend(buffers.iter().map(|buffer| buffer[i]));
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let channels = vec![vec![1.0, 2.0, 3.0], vec![-1.0, -2.0, -3.0]];
+        let interleaved = interleave(&channels);
+        assert_eq!(interleaved, [1.0, -1.0, 2.0, -2.0, 3.0, -3.0]);
+        assert_eq!(deinterleave(&interleaved, 2), channels);
+    }
+
+    /// Uneven lengths and partial frames are cut off.
+    #[test]
+    fn ragged_input() {
+        assert_eq!(interleave(&[vec![1.0, 2.0], vec![-1.0]]), [1.0, -1.0]);
+        assert_eq!(deinterleave(&[1.0, -1.0, 2.0], 2), [vec![1.0], vec![-1.0]]);
+        assert!(deinterleave(&[1.0], 0).is_empty());
+    }
+}
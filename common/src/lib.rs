@@ -1,9 +1,79 @@
+#[macro_use]
 pub mod macros;
+pub mod cc_feedback;
+pub mod denormal;
+pub mod descriptor;
+pub mod interleave;
+pub mod mapping;
+pub mod morph;
+pub mod plugin;
+pub mod processor;
+pub mod smoothing;
+pub mod test_util;
 
 pub fn make_strings(value: f32, label: &str) -> (String, String) {
     (format!("{:.2}", value), label.to_string())
 }
 
+/// Like `make_strings`, but rounds `value` to the nearest multiple of `step`
+/// and shows only as many decimal places as `step` needs. This keeps the
+/// readout steady while automation wiggles a parameter by tiny amounts. Only
+/// the display is rounded; the parameter keeps its exact value.
+pub fn make_rounded_strings(value: f32, step: f32, label: &str) -> (String, String) {
+    let decimals = if step >= 1.0 {
+        0
+    } else {
+        (-step.log10()).ceil() as usize
+    };
+    // Adding 0.0 turns -0.0 into 0.0, so that tiny negative values don't
+    // show a minus sign.
+    let rounded = (value / step).round() * step + 0.0;
+    (format!("{:.*}", decimals, rounded), label.to_string())
+}
+
+pub fn make_bool_strings(value: bool) -> (String, String) {
+    let value = if value { "ON" } else { "OFF" };
+    (value.to_string(), "".to_string())
+}
+
+/// Snap a normalized value to the nearest of `steps` evenly spaced points in
+/// the [0.0, 1.0] range, and return the index of that point. Useful for stepped
+/// parameters, such as booleans (`steps` = 2) or enums.
+pub fn quantize_index(norm: f32, steps: usize) -> usize {
+    if steps < 2 {
+        return 0;
+    }
+    let last = (steps - 1) as f32;
+    (norm.clamp(0.0, 1.0) * last).round() as usize
+}
+
+/// Snap a normalized value to the nearest of `steps` evenly spaced points in
+/// the [0.0, 1.0] range. The first point is always 0.0 and the last is 1.0.
+pub fn quantize(norm: f32, steps: usize) -> f32 {
+    if steps < 2 {
+        return 0.0;
+    }
+    quantize_index(norm, steps) as f32 / (steps - 1) as f32
+}
+
+/// Convert a value in decibels to a linear gain.
+pub fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+/// Derive a plugin's `unique_id` from its name, using the 32-bit FNV-1a hash.
+/// Hosts tell plugins apart by their id, so each plugin needs its own, and
+/// the id must not change once the plugin is released, or hosts won't find
+/// it in saved projects. The same name always gives the same id.
+pub fn unique_id(name: &str) -> i32 {
+    const OFFSET_BASIS: u32 = 0x811c_9dc5;
+    const PRIME: u32 = 0x0100_0193;
+    let hash = name.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(PRIME)
+    });
+    hash as i32
+}
+
 pub fn ease_in_expo(x: f32) -> f32 {
     if x <= 0.0 {
         0.0
@@ -11,3 +81,54 @@ pub fn ease_in_expo(x: f32) -> f32 {
         (2.0f32.powf(10.0 * x) - 1.0) / (2.0f32.powf(10.0) - 1.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantize_booleans() {
+        assert_eq!(quantize(0.0, 2), 0.0);
+        assert_eq!(quantize(0.49, 2), 0.0);
+        assert_eq!(quantize(0.51, 2), 1.0);
+        assert_eq!(quantize(1.0, 2), 1.0);
+    }
+
+    #[test]
+    fn quantize_window_sizes() {
+        // The grid points are at multiples of 1/99.
+        assert_eq!(quantize(0.0, 100), 0.0);
+        assert_eq!(quantize(12.3 / 99.0, 100), 12.0 / 99.0);
+        assert_eq!(quantize(12.7 / 99.0, 100), 13.0 / 99.0);
+        assert_eq!(quantize(1.0, 100), 1.0);
+    }
+
+    /// The ends of the range are always grid points, and values past them
+    /// are clamped.
+    #[test]
+    fn quantize_edges() {
+        for steps in [2, 3, 100].iter().copied() {
+            assert_eq!(quantize(0.0, steps), 0.0);
+            assert_eq!(quantize(1.0, steps), 1.0);
+            assert_eq!(quantize(-0.5, steps), 0.0);
+            assert_eq!(quantize(1.5, steps), 1.0);
+        }
+    }
+
+    #[test]
+    fn rounded_strings() {
+        assert_eq!(make_rounded_strings(49.994, 0.1, "%").0, "50.0");
+        assert_eq!(make_rounded_strings(12.34, 1.0, "ms").0, "12");
+        assert_eq!(make_rounded_strings(0.1234, 0.01, "").0, "0.12");
+        // Tiny negative values don't show a minus sign.
+        assert_eq!(make_rounded_strings(-0.01, 0.1, "%").0, "0.0");
+    }
+
+    /// Ids derived from names are distinct and never change. A changed id
+    /// would hide the plugins from projects saved with them.
+    #[test]
+    fn unique_ids() {
+        assert_eq!(unique_id("Median Filter"), 928570183);
+        assert_ne!(unique_id("Median Filter"), unique_id("Clipper"));
+    }
+}
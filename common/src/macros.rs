@@ -9,6 +9,11 @@
 ///     returns a tuple where the first String is the parameter's name
 ///     (ex: "Master Volume") and the second tuple is the parameter's value
 ///     (ex: "12 db")
+/// parse_text(&self, $parameter_type, &str) -> Option<f32>
+///     parses a value typed in by the user (ex: "12 db") into a normalized
+///     value, or returns None if the parameter can't be typed in
+/// Presets are saved as each parameter's normalized value, in index order, as
+/// little endian f32s.
 #[macro_export]
 macro_rules! impl_plugin_parameters {
     ($raw_parameters: ident, $parameter_type: ident) => {
@@ -52,16 +57,6 @@ macro_rules! impl_plugin_parameters {
             fn set_parameter(&self, index: i32, value: f32) {
                 use std::convert::TryFrom;
                 if let Ok(parameter) = $parameter_type::try_from(index) {
-                    // This is needed because some VST hosts, such as Ableton, echo a
-                    // parameter change back to the plugin. This causes issues such as
-                    // weird knob behavior where the knob "flickers" because the user tries
-                    // to change the knob value, but ableton keeps sending back old, echoed
-                    // values.
-                    #[allow(clippy::float_cmp)]
-                    if self.get(parameter) == value {
-                        return;
-                    }
-
                     self.set(value, parameter);
                 }
             }
@@ -71,9 +66,49 @@ macro_rules! impl_plugin_parameters {
                 $parameter_type::try_from(index).is_ok()
             }
 
-            fn string_to_parameter(&self, _index: i32, _text: String) -> bool {
+            fn string_to_parameter(&self, index: i32, text: String) -> bool {
+                use std::convert::TryFrom;
+                if let Ok(parameter) = $parameter_type::try_from(index) {
+                    if let Some(value) = self.parse_text(parameter, &text) {
+                        self.set(value, parameter);
+                        return true;
+                    }
+                }
                 false
             }
+
+            fn get_preset_data(&self) -> Vec<u8> {
+                self.all_normalized()
+                    .iter()
+                    .flat_map(|value| value.to_le_bytes())
+                    .collect()
+            }
+
+            fn get_bank_data(&self) -> Vec<u8> {
+                self.get_preset_data()
+            }
+
+            fn load_preset_data(&self, data: &[u8]) {
+                // Parameters missing from the preset, such as those added since
+                // it was saved, are set to their defaults. Values which aren't
+                // in range are ignored.
+                let mut values = [0.0; $parameter_type::COUNT];
+                for &parameter in $parameter_type::all() {
+                    let index: i32 = parameter.into();
+                    values[index as usize] = Self::get_default(parameter);
+                }
+                for (value, bytes) in values.iter_mut().zip(data.chunks_exact(4)) {
+                    let loaded = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    if (0.0..=1.0).contains(&loaded) {
+                        *value = loaded;
+                    }
+                }
+                self.load_normalized(&values);
+            }
+
+            fn load_bank_data(&self, data: &[u8]) {
+                self.load_preset_data(data);
+            }
         }
     };
 }
@@ -82,18 +117,155 @@ macro_rules! impl_plugin_parameters {
 macro_rules! impl_get_set {
     ($raw_parameters: ident, $parameter_type: ident) => {
         impl $raw_parameters {
+            /// Set the normalized value of a parameter, unless it already has that
+            /// value. This is the path used by the host and by automation.
             pub fn set(&self, value: f32, parameter: $parameter_type) {
-                // These are needed so Ableton will notice parameter changes in the
-                // "Configure" window.
-                // TODO: investigate if I should send this only on mouseup/mousedown
-                self.host.begin_edit(parameter.into());
+                // This is needed because some VST hosts, such as Ableton, echo a
+                // parameter change back to the plugin. This causes issues such as
+                // weird knob behavior where the knob "flickers" because the user tries
+                // to change the knob value, but ableton keeps sending back old, echoed
+                // values.
+                #[allow(clippy::float_cmp)]
+                if self.get(parameter) == value {
+                    return;
+                }
+
+                self.set_forced(value, parameter);
+            }
+
+            /// Set the normalized value of a parameter, even if it already has that
+            /// value. Use this when the host must hear about every parameter, such
+            /// as when loading a preset or resetting. The new value takes effect
+            /// immediately, but the host is only told about it on the next call to
+            /// `flush_edits`, so that many changes within one block don't flood the
+            /// host with notifications.
+            pub fn set_forced(&self, value: f32, parameter: $parameter_type) {
                 self.get_ref(parameter).set(value);
-                self.host.end_edit(parameter.into());
+                let index: i32 = parameter.into();
+                self.pending_edits[index as usize / 64]
+                    .fetch_or(1 << (index % 64), std::sync::atomic::Ordering::Relaxed);
+            }
+
+            /// Notify the host of every parameter changed by `set` since the last
+            /// flush. Each parameter is reported at most once. Plugins should call
+            /// this once at the end of every `process` call.
+            pub fn flush_edits(&self) {
+                let pending = self
+                    .pending_edits
+                    .each_ref()
+                    .map(|word| word.swap(0, std::sync::atomic::Ordering::Relaxed));
+                for &parameter in $parameter_type::all() {
+                    let index: i32 = parameter.into();
+                    // A parameter in a gesture is already inside a begin_edit,
+                    // which `end_gesture` closes.
+                    if pending[index as usize / 64] & (1 << (index % 64)) != 0
+                        && !self.in_gesture(parameter)
+                    {
+                        // These are needed so Ableton will notice parameter changes in the
+                        // "Configure" window.
+                        // TODO: investigate if I should send this only on mouseup/mousedown
+                        self.host.begin_edit(index);
+                        self.host.end_edit(index);
+                    }
+                }
             }
 
             pub fn get(&self, parameter: $parameter_type) -> f32 {
                 self.get_ref(parameter).get()
             }
+
+            /// Note that the user started a gesture on `parameter`, such as
+            /// dragging its knob in an editor. Until `end_gesture`, the value
+            /// may pass through many intermediate values on its way to where
+            /// the user leaves it. The host is sent a begin_edit, so that it
+            /// records the gesture as one edit.
+            pub fn begin_gesture(&self, parameter: $parameter_type) {
+                let index: i32 = parameter.into();
+                let bit = 1 << (index % 64);
+                let previous = self.gestures[index as usize / 64]
+                    .fetch_or(bit, std::sync::atomic::Ordering::AcqRel);
+                if previous & bit == 0 {
+                    self.host.begin_edit(index);
+                }
+            }
+
+            /// Note that the user finished a gesture started by `begin_gesture`,
+            /// sending the host the matching end_edit.
+            pub fn end_gesture(&self, parameter: $parameter_type) {
+                let index: i32 = parameter.into();
+                let bit = 1 << (index % 64);
+                let previous = self.gestures[index as usize / 64]
+                    .fetch_and(!bit, std::sync::atomic::Ordering::AcqRel);
+                if previous & bit != 0 {
+                    // The gesture's edit already covers any changes made in it.
+                    self.pending_edits[index as usize / 64]
+                        .fetch_and(!bit, std::sync::atomic::Ordering::Relaxed);
+                    self.host.end_edit(index);
+                }
+            }
+
+            /// End every gesture which is still open, and notify the host of any
+            /// parameters changed since the last flush. Call this before the
+            /// plugin goes away, since a host left with a begin_edit and no
+            /// end_edit may think the user is still holding the knob.
+            pub fn shutdown(&self) {
+                for &parameter in $parameter_type::all() {
+                    self.end_gesture(parameter);
+                }
+                self.flush_edits();
+            }
+
+            /// Returns true between `begin_gesture` and `end_gesture`.
+            pub fn in_gesture(&self, parameter: $parameter_type) -> bool {
+                let index: i32 = parameter.into();
+                self.gestures[index as usize / 64].load(std::sync::atomic::Ordering::Acquire)
+                    & (1 << (index % 64))
+                    != 0
+            }
+
+            /// Set every parameter back to its default value, notifying the host of
+            /// each one.
+            pub fn reset_to_defaults(&self) {
+                for &parameter in $parameter_type::all() {
+                    self.set_forced(Self::get_default(parameter), parameter);
+                }
+            }
+
+            /// Returns true if the parameter is currently set to its default
+            /// value. Useful for GUIs which highlight modified parameters.
+            pub fn is_default(&self, parameter: $parameter_type) -> bool {
+                (self.get(parameter) - Self::get_default(parameter)).abs() < 1.0e-6
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! impl_effect_parameters {
+    ($raw_parameters: ident, $parameter_type: ident) => {
+        impl $crate::plugin::EffectParameters for $raw_parameters {
+            type Parameters = Parameters;
+
+            fn new(host: vst::plugin::HostCallback) -> Self {
+                $raw_parameters::default(host)
+            }
+
+            fn snapshot(&self) -> Parameters {
+                $raw_parameters::snapshot(self)
+            }
+
+            fn set_sample_rate(&self, sample_rate: f32) {
+                self.sample_rate.set(sample_rate);
+            }
+
+            fn flush_edits(&self) {
+                $raw_parameters::flush_edits(self)
+            }
+
+            fn record_fault(&self) {
+                self.faulted
+                    .store(true, std::sync::atomic::Ordering::Relaxed);
+            }
         }
     };
 }
@@ -101,7 +273,7 @@ macro_rules! impl_get_set {
 #[macro_export]
 macro_rules! impl_display {
     ($raw_parameters: ident, $parameter_type: ident;
-     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $string:expr;)*) => {
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
         impl std::fmt::Display for $parameter_type {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 match self {
@@ -112,10 +284,28 @@ macro_rules! impl_display {
     };
 }
 
+#[macro_export]
+macro_rules! impl_from_str {
+    ($raw_parameters: ident, $parameter_type: ident;
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
+        impl std::str::FromStr for $parameter_type {
+            type Err = ();
+            /// Parses either the parameter's display name (ex: "Wet/Dry") or its
+            /// field name (ex: "wet_dry").
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $(if s == $name || s == stringify!($field_name) {
+                    return Ok($parameter_type::$variant);
+                })*
+                Err(())
+            }
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! impl_from_i32 {
     ($raw_parameters: ident, $parameter_type: ident;
-     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $string:expr;)*) => {
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
         impl std::convert::TryFrom<i32> for $parameter_type {
             type Error = ();
             fn try_from(x: i32) -> Result<Self, Self::Error> {
@@ -131,7 +321,7 @@ macro_rules! impl_from_i32 {
 #[macro_export]
 macro_rules! impl_into_i32 {
     ($raw_parameters: ident, $parameter_type: ident;
-     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $string:expr;)*) => {
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
         impl std::convert::From<$parameter_type> for i32 {
             fn from(x: $parameter_type) -> i32 {
                 match x {
@@ -145,7 +335,7 @@ macro_rules! impl_into_i32 {
 #[macro_export]
 macro_rules! impl_get_ref {
     ($raw_parameters: ident, $parameter_type: ident;
-     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $string:expr;)*) => {
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
         impl $raw_parameters {
             fn get_ref(&self, x: $parameter_type) -> &vst::util::AtomicFloat {
                 match x {
@@ -159,9 +349,9 @@ macro_rules! impl_get_ref {
 #[macro_export]
 macro_rules! impl_get_default {
     ($raw_parameters: ident, $parameter_type: ident;
-     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $string:expr;)*) => {
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
         impl $raw_parameters {
-            fn get_default(x: $parameter_type) -> f32 {
+            pub fn get_default(x: $parameter_type) -> f32 {
                 match x {
                     $($parameter_type::$variant => $default,)*
                 }
@@ -173,45 +363,265 @@ macro_rules! impl_get_default {
 #[macro_export]
 macro_rules! impl_default {
     ($raw_parameters: ident, $parameter_type: ident;
-     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $string:expr;)*) => {
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
         impl $raw_parameters {
-            fn default(host: vst::plugin::HostCallback) -> Self {
+            pub fn default(host: vst::plugin::HostCallback) -> Self {
+                // A malformed table would silently send the host's edits to the
+                // wrong parameters, so catch it as early as possible.
+                #[cfg(debug_assertions)]
+                if let Err(err) = $parameter_type::check_indices() {
+                    panic!("Malformed parameter table: {}", err);
+                }
                 $raw_parameters {
                     $($field_name: vst::util::AtomicFloat::new($default),)*
                     host,
+                    sample_rate: vst::util::AtomicFloat::new(44100.0),
+                    offline: std::sync::atomic::AtomicBool::new(false),
+                    faulted: std::sync::atomic::AtomicBool::new(false),
+                    pending_edits: std::array::from_fn(|_| std::sync::atomic::AtomicU64::new(0)),
+                    preset_loads: std::sync::atomic::AtomicUsize::new(0),
+                    gestures: std::array::from_fn(|_| std::sync::atomic::AtomicU64::new(0)),
+                }
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! impl_get_smoothing {
+    ($raw_parameters: ident, $parameter_type: ident;
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
+        impl $raw_parameters {
+            /// How changes to the parameter should be smoothed while processing.
+            pub fn get_smoothing(x: $parameter_type) -> $crate::smoothing::Smoothing {
+                match x {
+                    $($parameter_type::$variant => $smoothing,)*
                 }
             }
         }
     };
 }
 
+#[macro_export]
+macro_rules! impl_get_mapping {
+    ($raw_parameters: ident, $parameter_type: ident;
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
+        impl $raw_parameters {
+            /// How the parameter's normalized value maps to the value it
+            /// controls, for drawing its automation. This isn't sent to the
+            /// host; see `Mapping`.
+            pub fn get_mapping(x: $parameter_type) -> $crate::mapping::Mapping {
+                match x {
+                    $($parameter_type::$variant => $mapping,)*
+                }
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! impl_clamp_range {
+    ($raw_parameters: ident, $parameter_type: ident;
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
+        impl $raw_parameters {
+            /// The part of the parameter's normalized range, as (min, max),
+            /// which has an effect. The host still sees and may set any value
+            /// from 0.0 to 1.0, but the plugin treats values outside this
+            /// range as the nearest end of it.
+            pub fn get_usable_range(x: $parameter_type) -> (f32, f32) {
+                match x {
+                    $($parameter_type::$variant => ($usable_min, $usable_max),)*
+                }
+            }
+
+            /// Clamp each of `values`, which are in the same order as the
+            /// parameter indices, to its parameter's usable range. Plugins
+            /// apply this to the normalized values they process, rather than
+            /// to the values the host sees.
+            pub fn clamp_to_usable(values: &mut [f32; $parameter_type::COUNT]) {
+                $(values[$idx] = values[$idx].clamp($usable_min, $usable_max);)*
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! impl_descriptors {
+    ($raw_parameters: ident, $parameter_type: ident;
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
+        impl $raw_parameters {
+            /// Describe every parameter in the table, in index order. The units
+            /// are the ones shown when every parameter has its default value,
+            /// so that the descriptors never change, even for a parameter whose
+            /// units depend on another.
+            pub fn descriptors() -> Vec<$crate::descriptor::ParamDescriptor> {
+                let defaults = Self::default($crate::test_util::null_host());
+                let mut descriptors = vec![$($crate::descriptor::ParamDescriptor {
+                    name: $name.to_string(),
+                    index: $idx,
+                    default: $default,
+                    min: $usable_min,
+                    max: $usable_max,
+                    unit: defaults.get_strings($parameter_type::$variant).1,
+                    kind: $mapping,
+                },)*];
+                descriptors.sort_by_key(|descriptor| descriptor.index);
+                descriptors
+            }
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! impl_get_strings {
     ($raw_parameters: ident, $parameter_type: ident;
-     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $string:expr;)*) => {
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
         impl $raw_parameters {
             /// Returns a user-facing text output for the given parameter. This is broken
             /// into a tuple consisting of (`value`, `units`)
-            fn get_strings(&self, parameter: $parameter_type) -> (String, String) {
-                let params = Parameters::from(self);
+            pub fn get_strings(&self, parameter: $parameter_type) -> (String, String) {
+                let params = self.snapshot();
                 match parameter {
-                    $($parameter_type::$variant => $string(params.$field_name),)*
+                    $($parameter_type::$variant => $string($crate::strings_value!(self, params, $field_name $(, $strings_of)?)),)*
                 }
             }
         }
     };
 }
 
+/// The value a parameter's strings are made from. This is the parameter's
+/// field in the scaled parameters, unless the table marks the strings with
+/// `@normalized`, in which case it is the raw normalized value. Parameters
+/// which are scaled as a group, rather than one field each, use the latter.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! strings_value {
+    ($raw:ident, $params:ident, $field_name:ident) => {
+        $params.$field_name
+    };
+    ($raw:ident, $params:ident, $field_name:ident, normalized) => {
+        $raw.$field_name.get()
+    };
+}
+
+#[macro_export]
+macro_rules! impl_snapshot {
+    ($raw_parameters: ident, $parameter_type: ident;
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
+        impl $raw_parameters {
+            /// Returns the current value of every parameter, scaled to the
+            /// ranges the plugin actually uses.
+            pub fn snapshot(&self) -> Parameters {
+                Parameters::from(self)
+            }
+
+            /// Returns the current normalized value of every parameter, in the
+            /// same order as the parameter indices.
+            pub fn all_normalized(&self) -> [f32; $parameter_type::COUNT] {
+                [$(self.$field_name.get(),)*]
+            }
+
+            /// Set the normalized value of every parameter at once, in the
+            /// same order as the parameter indices. Unlike `set`, the host is
+            /// not told about the changes, so this suits a private copy of
+            /// the parameters which the host never sees.
+            pub fn set_all_normalized(&self, values: &[f32; $parameter_type::COUNT]) {
+                $(self.$field_name.set(values[$idx]);)*
+            }
+
+            /// Set every parameter at once, as when loading a preset, notifying
+            /// the host of each one. Readers using `consistent_normalized` see
+            /// either all of the old values or all of the new ones, never a
+            /// mix of the two. Loads must not overlap each other, which holds
+            /// as long as they all come from the host.
+            pub fn load_normalized(&self, values: &[f32; $parameter_type::COUNT]) {
+                use std::sync::atomic::{fence, Ordering};
+                self.preset_loads.fetch_add(1, Ordering::Relaxed);
+                fence(Ordering::Release);
+                $(self.set_forced(values[$idx], $parameter_type::$variant);)*
+                self.preset_loads.fetch_add(1, Ordering::Release);
+            }
+
+            /// Like `all_normalized`, but returns None if a preset was being
+            /// loaded by `load_normalized` while the values were read, since
+            /// they may then be a mix of the old and new preset. The caller
+            /// should keep using the last values it read, and try again later.
+            pub fn consistent_normalized(&self) -> Option<[f32; $parameter_type::COUNT]> {
+                use std::sync::atomic::{fence, Ordering};
+                let before = self.preset_loads.load(Ordering::Acquire);
+                if before & 1 != 0 {
+                    return None;
+                }
+                let values = self.all_normalized();
+                fence(Ordering::Acquire);
+                if self.preset_loads.load(Ordering::Relaxed) != before {
+                    return None;
+                }
+                Some(values)
+            }
+
+            /// How many preset loads by `load_normalized` have finished.
+            pub fn preset_loads(&self) -> usize {
+                self.preset_loads.load(std::sync::atomic::Ordering::Acquire) / 2
+            }
+
+            /// Set every parameter to its value `amount` of the way through
+            /// `morph`, from A (0.0) to B (1.0). Locked parameters are left
+            /// alone.
+            pub fn apply_morph(
+                &self,
+                morph: &$crate::morph::Morph<$parameter_type, { $parameter_type::COUNT }>,
+                amount: f32,
+            ) {
+                let values = morph.values(amount, &self.all_normalized());
+                $(self.set(values[$idx], $parameter_type::$variant);)*
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! impl_debug {
+    ($raw_parameters: ident, $parameter_type: ident;
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
+        impl std::fmt::Debug for $raw_parameters {
+            /// Prints each parameter's name and current normalized value. The
+            /// host callback is skipped.
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct(stringify!($raw_parameters))
+                    $(.field($name, &self.$field_name.get()))*
+                    .finish()
+            }
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! generate_raw_params {
     ($raw_parameters: ident, $parameter_type: ident;
-     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $string:expr;)*) => {
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
         /// The raw parameter values that a host DAW will set and modify.
         /// These are unscaled and are always in the [0.0, 1.0] range
         pub struct $raw_parameters {
             $($field_name: AtomicFloat,)*
             /// The host callback, used for communicating with the VST host
             pub host: vst::plugin::HostCallback,
+            /// The sample rate the host is running the plugin at
+            pub sample_rate: AtomicFloat,
+            /// Whether the host is rendering offline (such as during a bounce)
+            pub offline: std::sync::atomic::AtomicBool,
+            /// Set if processing has ever panicked. Each block that panics is
+            /// replaced with silence.
+            pub faulted: std::sync::atomic::AtomicBool,
+            // One bit per parameter, set when the host hasn't been told about a
+            // change yet. Parameter `i` is bit `i % 64` of word `i / 64`.
+            pending_edits: [std::sync::atomic::AtomicU64; $parameter_type::COUNT.div_ceil(64)],
+            // Incremented at the start and end of each preset load, so that it
+            // is odd while a load is in progress.
+            preset_loads: std::sync::atomic::AtomicUsize,
+            // One bit per parameter, set while the user is in a gesture on it.
+            // Laid out like `pending_edits`.
+            gestures: [std::sync::atomic::AtomicU64; $parameter_type::COUNT.div_ceil(64)],
         }
     };
 }
@@ -219,12 +629,45 @@ macro_rules! generate_raw_params {
 #[macro_export]
 macro_rules! generate_parameter_type {
     ($raw_parameters: ident, $parameter_type: ident;
-     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $string:expr;)*) => {
+     $($variant:ident, $field_name:ident, $name:expr, $idx:expr, $default:expr, $smoothing:expr, $mapping:expr, $usable_min:expr, $usable_max:expr, $(@$strings_of:ident)? $string:expr;)*) => {
         /// The list of parameters that exist.
         #[derive(Debug, Clone, Copy, PartialEq, Eq)]
         pub enum $parameter_type {
             $($variant,)*
         }
+
+        impl $parameter_type {
+            /// Every parameter, in the order they appear in the table. Use
+            /// `TryFrom<i32>` and `Into<i32>` to convert to and from indices.
+            pub fn all() -> &'static [$parameter_type] {
+                &[$($parameter_type::$variant,)*]
+            }
+
+            /// Check that the table is consistent: there are `COUNT` parameters,
+            /// their indices are exactly `0..COUNT`, and each parameter converts
+            /// to its index and back to itself.
+            pub fn check_indices() -> Result<(), String> {
+                use std::convert::TryFrom;
+                let all = $parameter_type::all();
+                if all.len() != $parameter_type::COUNT {
+                    return Err(format!(
+                        "{} parameters are in the table, but COUNT is {}",
+                        all.len(),
+                        $parameter_type::COUNT
+                    ));
+                }
+                for &parameter in all {
+                    let idx: i32 = parameter.into();
+                    if idx < 0 || idx as usize >= $parameter_type::COUNT {
+                        return Err(format!("{:?} has out of range index {}", parameter, idx));
+                    }
+                    if $parameter_type::try_from(idx) != Ok(parameter) {
+                        return Err(format!("{:?} has index {}, which doesn't convert back to it", parameter, idx));
+                    }
+                }
+                Ok(())
+            }
+        }
     };
 }
 
@@ -233,14 +676,22 @@ macro_rules! impl_all {
     ($raw_parameters: ident, $parameter_type: ident, $table: ident) => {
         impl_plugin_parameters! {$raw_parameters, $parameter_type}
         impl_get_set! {$raw_parameters, $parameter_type}
+        impl_effect_parameters! {$raw_parameters, $parameter_type}
         $table! {generate_raw_params}
         $table! {generate_parameter_type}
         $table! {impl_from_i32}
         $table! {impl_into_i32}
         $table! {impl_display}
+        $table! {impl_from_str}
         $table! {impl_get_ref}
         $table! {impl_default}
         $table! {impl_get_default}
+        $table! {impl_get_smoothing}
+        $table! {impl_get_mapping}
+        $table! {impl_clamp_range}
+        $table! {impl_descriptors}
         $table! {impl_get_strings}
+        $table! {impl_snapshot}
+        $table! {impl_debug}
     };
 }
@@ -0,0 +1,30 @@
+/// How a parameter's normalized value maps to the value it controls. Each
+/// parameter picks one in its plugin's parameter table, so that GUIs and
+/// tools can draw its automation in the parameter's own units.
+///
+/// VST2 hosts are never told the mapping. The `vst` crate doesn't answer the
+/// host's parameter properties request, and those properties could only
+/// describe steps anyway, as VST2 has no way to mark a parameter as log.
+/// Instead, the mapping is read through `get_mapping` or `descriptors`,
+/// which the parameter macros generate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mapping {
+    /// The value moves in proportion to the normalized value.
+    Linear,
+    /// Equal changes to the normalized value multiply the value by equal
+    /// amounts, as with a frequency.
+    Log,
+    /// The value jumps between the given number of evenly spaced steps, such
+    /// as the two of an on/off switch or the choices of a menu.
+    Stepped(usize),
+}
+
+impl Mapping {
+    /// The number of steps, or None if the parameter is continuous.
+    pub fn steps(self) -> Option<usize> {
+        match self {
+            Mapping::Stepped(steps) => Some(steps),
+            Mapping::Linear | Mapping::Log => None,
+        }
+    }
+}
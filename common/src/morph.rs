@@ -0,0 +1,91 @@
+use std::marker::PhantomData;
+
+/// Two snapshots of every parameter's normalized value, A and B, which can be
+/// morphed between. Parameters can be locked so that the morph skips them,
+/// leaving them at whatever value they have, such as to morph the timbre of a
+/// sound without changing its output level.
+///
+/// `P` is the plugin's parameter type, and `N` is its number of parameters.
+/// Apply a morph with the generated `RawParameters::apply_morph`.
+pub struct Morph<P, const N: usize> {
+    a: [f32; N],
+    b: [f32; N],
+    locked: [bool; N],
+    parameter: PhantomData<P>,
+}
+
+impl<P: Into<i32>, const N: usize> Morph<P, N> {
+    /// Morph between the snapshots `a` and `b`, usually taken with
+    /// `RawParameters::all_normalized`. Nothing is locked.
+    pub fn new(a: [f32; N], b: [f32; N]) -> Morph<P, N> {
+        Morph {
+            a,
+            b,
+            locked: [false; N],
+            parameter: PhantomData,
+        }
+    }
+
+    pub fn set_a(&mut self, a: [f32; N]) {
+        self.a = a;
+    }
+
+    pub fn set_b(&mut self, b: [f32; N]) {
+        self.b = b;
+    }
+
+    /// Lock or unlock a parameter. Locked parameters keep their current value
+    /// while morphing.
+    pub fn set_lock(&mut self, parameter: P, locked: bool) {
+        self.locked[Self::index(parameter)] = locked;
+    }
+
+    pub fn is_locked(&self, parameter: P) -> bool {
+        self.locked[Self::index(parameter)]
+    }
+
+    /// The normalized value of every parameter `amount` of the way from A
+    /// (0.0) to B (1.0). Locked parameters keep their value from `current`.
+    pub fn values(&self, amount: f32, current: &[f32; N]) -> [f32; N] {
+        let amount = amount.clamp(0.0, 1.0);
+        let mut values = *current;
+        for (i, value) in values.iter_mut().enumerate() {
+            if !self.locked[i] {
+                *value = self.a[i] + (self.b[i] - self.a[i]) * amount;
+            }
+        }
+        values
+    }
+
+    fn index(parameter: P) -> usize {
+        let index: i32 = parameter.into();
+        index as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{TestParameterType, TestParameters};
+
+    use super::*;
+
+    /// A locked parameter keeps its value through a whole A to B morph, while
+    /// unlocked parameters move between A and B.
+    #[test]
+    fn locked_parameters_stay_put() {
+        let params = TestParameters::new();
+        let mut morph = Morph::new(
+            [0.0; TestParameterType::COUNT],
+            [1.0; TestParameterType::COUNT],
+        );
+        morph.set_lock(TestParameterType::Enabled, true);
+        assert!(morph.is_locked(TestParameterType::Enabled));
+        params.set(0.0, TestParameterType::Enabled);
+        for step in 0..=10 {
+            let amount = step as f32 / 10.0;
+            params.apply_morph(&morph, amount);
+            assert!((params.get(TestParameterType::Gain) - amount).abs() <= 1e-6);
+            assert_eq!(params.get(TestParameterType::Enabled), 0.0);
+        }
+    }
+}
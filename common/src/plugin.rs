@@ -0,0 +1,427 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+};
+
+use vst::{
+    buffer::AudioBuffer,
+    plugin::{HostCallback, Info, Plugin, PluginParameters},
+};
+
+use crate::{processor::ChannelProcessor, unique_id};
+
+/// The most channels an `EffectPlugin` can process. Both mono and stereo
+/// layouts are supported.
+pub const MAX_CHANNELS: usize = 2;
+/// The default number of samples processed between parameter updates. Large
+/// buffers are split into sub-blocks of this size, so that parameters are read
+/// just as often during an offline bounce as during realtime playback.
+pub const DEFAULT_SUB_BLOCK_SIZE: usize = 512;
+
+/// Raw parameters which can be used by `EffectPlugin`. This is implemented by
+/// `impl_all!`, so any parameter table can be used.
+pub trait EffectParameters: PluginParameters + 'static {
+    /// The scaled parameters passed to each channel's processor.
+    type Parameters;
+
+    /// Create the parameters, with every parameter at its default value.
+    fn new(host: HostCallback) -> Self;
+
+    /// Returns the current value of every parameter, scaled to the ranges the
+    /// plugin actually uses.
+    fn snapshot(&self) -> Self::Parameters;
+
+    fn set_sample_rate(&self, sample_rate: f32);
+
+    /// Notify the host of any parameters changed since the last flush.
+    fn flush_edits(&self);
+
+    /// Note that processing panicked, so that a GUI can show it.
+    fn record_fault(&self);
+}
+
+/// A `ChannelProcessor` which describes a whole effect, so that it can be run
+/// by `EffectPlugin`.
+pub trait EffectProcessor: ChannelProcessor + Sized {
+    /// The plugin's info, as reported to the host. If `unique_id` is left at
+    /// zero, it is derived from the name by `unique_id`.
+    fn info() -> Info;
+
+    /// Create the processor for the given channel (0 for left, 1 for right).
+    fn new(channel: usize, params: &Self::Parameters) -> Self;
+}
+
+/// A stereo effect plugin which runs one `P` per channel, controlled by the
+/// parameters `R`. A new effect only needs to supply a processor and a
+/// parameter table. Effects which need more control over processing (such as
+/// mid/side encoding) can wrap an `EffectPlugin` and use its methods directly.
+pub struct EffectPlugin<P, R> {
+    params: Arc<R>,
+    channels: Vec<P>,
+    // A copy of the current channel's input. Some hosts process in-place,
+    // passing the same buffer for input and output, so the input is copied
+    // here before any output is written.
+    scratch: Vec<f32>,
+    // The most samples processed between parameter updates.
+    sub_block_size: usize,
+}
+
+impl<P, R> EffectPlugin<P, R>
+where
+    P: EffectProcessor<Parameters = R::Parameters>,
+    R: EffectParameters,
+{
+    pub fn params(&self) -> &Arc<R> {
+        &self.params
+    }
+
+    pub fn channels_mut(&mut self) -> &mut [P] {
+        &mut self.channels
+    }
+
+    /// The most samples processed between parameter updates.
+    pub fn sub_block_size(&self) -> usize {
+        self.sub_block_size
+    }
+
+    /// Set the most samples processed between parameter updates. Buffers
+    /// larger than this are split into sub-blocks, and the parameters are
+    /// read again at the start of each one. The default is
+    /// `DEFAULT_SUB_BLOCK_SIZE`.
+    pub fn set_sub_block_size(&mut self, size: usize) {
+        self.sub_block_size = size.max(1);
+    }
+
+    /// Match the channels to the host's channel layout. Some hosts change
+    /// their layout without recreating the plugin, which can only be noticed
+    /// from the buffers passed to `process`. When the number of channels
+    /// changes, every channel is recreated, clearing its state. Layouts with
+    /// differing input and output counts, or more than `MAX_CHANNELS`
+    /// channels, are rejected: false is returned and the channels are left
+    /// as they were.
+    pub fn set_channel_layout(&mut self, inputs: usize, outputs: usize) -> bool {
+        if inputs != outputs || inputs == 0 || inputs > MAX_CHANNELS {
+            return false;
+        }
+        if inputs != self.channels.len() {
+            let snapshot = self.params.snapshot();
+            self.channels = (0..inputs)
+                .map(|channel| P::new(channel, &snapshot))
+                .collect();
+        }
+        true
+    }
+
+    /// Handle a panic caught by `catch_faults`. The fault is recorded, and every
+    /// channel is reset in case the panic left it in a bad state.
+    pub fn recover_from_fault(&mut self) {
+        self.params.record_fault();
+        for channel in self.channels.iter_mut() {
+            channel.reset();
+        }
+    }
+
+    fn process_channels(&mut self, buffer: &mut AudioBuffer<f32>) {
+        let num_samples = buffer.samples();
+        let (inputs, mut outputs) = buffer.split();
+        let num_channels = inputs.len().min(outputs.len()).min(self.channels.len());
+        for start in (0..num_samples).step_by(self.sub_block_size) {
+            let end = (start + self.sub_block_size).min(num_samples);
+            self.update_parameters();
+            for i in 0..num_channels {
+                self.scratch.clear();
+                self.scratch.extend_from_slice(&inputs[i][start..end]);
+                self.channels[i].process_block(&self.scratch, &mut outputs[i][start..end]);
+            }
+        }
+    }
+
+    /// Pass the current parameters to every channel, and return them.
+    pub fn update_parameters(&mut self) -> R::Parameters {
+        let params = self.params.snapshot();
+        self.set_parameters(&params);
+        params
+    }
+
+    /// Pass `params` to every channel, in place of the current parameters.
+    pub fn set_parameters(&mut self, params: &R::Parameters) {
+        for channel in self.channels.iter_mut() {
+            channel.set_parameters(params);
+        }
+    }
+}
+
+impl<P, R> Plugin for EffectPlugin<P, R>
+where
+    P: EffectProcessor<Parameters = R::Parameters>,
+    R: EffectParameters,
+{
+    fn new(host: HostCallback) -> Self {
+        let params = R::new(host);
+        let snapshot = params.snapshot();
+        EffectPlugin {
+            params: Arc::new(params),
+            channels: (0..2).map(|channel| P::new(channel, &snapshot)).collect(),
+            scratch: Vec::new(),
+            sub_block_size: DEFAULT_SUB_BLOCK_SIZE,
+        }
+    }
+
+    fn init(&mut self) {
+        self.update_parameters();
+    }
+
+    fn get_info(&self) -> Info {
+        let info = P::info();
+        if info.unique_id == 0 {
+            Info {
+                unique_id: unique_id(&info.name),
+                ..info
+            }
+        } else {
+            info
+        }
+    }
+
+    fn set_sample_rate(&mut self, rate: f32) {
+        self.params.set_sample_rate(rate);
+        for channel in self.channels.iter_mut() {
+            channel.set_sample_rate(rate);
+            // Anything left in the processor was recorded at the old sample
+            // rate, so throw it away to avoid artifacts.
+            channel.reset();
+        }
+        self.update_parameters();
+    }
+
+    fn set_block_size(&mut self, size: i64) {
+        // Reserve space now so that `process` doesn't need to allocate.
+        self.scratch
+            .reserve((size.max(0) as usize).min(self.sub_block_size));
+    }
+
+    fn process(&mut self, buffer: &mut AudioBuffer<f32>) {
+        // Some hosts call `process` with empty buffers, which may not describe
+        // the real channel layout.
+        if buffer.samples() == 0 {
+            self.update_parameters();
+        } else if !self.set_channel_layout(buffer.input_count(), buffer.output_count()) {
+            silence(buffer);
+        } else if catch_faults(buffer, |buffer| self.process_channels(buffer)) {
+            self.recover_from_fault();
+        }
+        self.params.flush_edits();
+    }
+
+    // The raw parameters exposed to the host
+    fn get_parameter_object(&mut self) -> Arc<dyn PluginParameters> {
+        Arc::clone(&self.params) as Arc<dyn PluginParameters>
+    }
+}
+
+/// Run `process` on `buffer`, catching any panic. Unwinding out of a plugin
+/// and into the host is undefined behavior, and will usually crash the host.
+/// If `process` panics, the output is silenced and true is returned.
+pub fn catch_faults(
+    buffer: &mut AudioBuffer<f32>,
+    process: impl FnOnce(&mut AudioBuffer<f32>),
+) -> bool {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| process(buffer)));
+    if result.is_ok() {
+        return false;
+    }
+    silence(buffer);
+    true
+}
+
+/// Set every output of `buffer` to zero.
+pub fn silence(buffer: &mut AudioBuffer<f32>) {
+    let (_, mut outputs) = buffer.split();
+    for i in 0..outputs.len() {
+        for x in outputs[i].iter_mut() {
+            *x = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::test_util::{null_host, Parameters, TestParameters};
+
+    /// Run `plugin` on `inputs`, one buffer per channel, and return its
+    /// output.
+    fn process(plugin: &mut impl Plugin, inputs: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        let samples = inputs[0].len();
+        let mut outputs = vec![vec![0.0; samples]; inputs.len()];
+        let input_ptrs: Vec<*const f32> = inputs.iter().map(|x| x.as_ptr()).collect();
+        let mut output_ptrs: Vec<*mut f32> = outputs.iter_mut().map(|x| x.as_mut_ptr()).collect();
+        let mut buffer = unsafe {
+            AudioBuffer::from_raw(
+                inputs.len(),
+                outputs.len(),
+                input_ptrs.as_ptr(),
+                output_ptrs.as_mut_ptr(),
+                samples,
+            )
+        };
+        plugin.process(&mut buffer);
+        outputs
+    }
+
+    /// Passes its input straight through.
+    struct Passthrough;
+
+    impl ChannelProcessor for Passthrough {
+        type Parameters = Parameters;
+
+        fn process_sample(&mut self, x: f32) -> f32 {
+            x
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    impl EffectProcessor for Passthrough {
+        fn info() -> Info {
+            Info {
+                name: "Passthrough".to_string(),
+                ..Info::default()
+            }
+        }
+
+        fn new(_channel: usize, _params: &Parameters) -> Self {
+            Passthrough
+        }
+    }
+
+    /// A processor which only says how to process one sample runs on every
+    /// channel of the plugin, in stereo and in mono, across several
+    /// sub-blocks.
+    #[test]
+    fn passthrough_processor() {
+        let mut plugin: EffectPlugin<Passthrough, TestParameters> = EffectPlugin::new(null_host());
+        let sine: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.37).sin()).collect();
+        let stereo = vec![sine.clone(), vec![0.5; sine.len()]];
+        assert_eq!(process(&mut plugin, &stereo), stereo);
+        let mono = vec![sine];
+        assert_eq!(process(&mut plugin, &mono), mono);
+    }
+
+    /// Multiplies its input by the `Gain` parameter.
+    struct Amplifier {
+        gain: f32,
+    }
+
+    impl ChannelProcessor for Amplifier {
+        type Parameters = Parameters;
+
+        fn set_parameters(&mut self, params: &Parameters) {
+            self.gain = params.gain;
+        }
+
+        fn process_sample(&mut self, x: f32) -> f32 {
+            x * self.gain
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    impl EffectProcessor for Amplifier {
+        fn info() -> Info {
+            Info {
+                name: "Amplifier".to_string(),
+                ..Info::default()
+            }
+        }
+
+        fn new(_channel: usize, params: &Parameters) -> Self {
+            Amplifier { gain: params.gain }
+        }
+    }
+
+    /// A minimal effect built from a processor and a parameter table reads
+    /// its parameters from the host, and gets an id from its name.
+    #[test]
+    fn minimal_effect() {
+        let mut plugin: EffectPlugin<Amplifier, TestParameters> = EffectPlugin::new(null_host());
+        plugin.init();
+        assert_eq!(plugin.get_info().unique_id, unique_id("Amplifier"));
+
+        let inputs = vec![vec![0.5; 100], vec![-0.25; 100]];
+        // The gain starts at 1.0.
+        assert_eq!(process(&mut plugin, &inputs), inputs);
+        plugin.get_parameter_object().set_parameter(0, 0.25);
+        assert_eq!(
+            process(&mut plugin, &inputs),
+            vec![vec![0.25; 100], vec![-0.125; 100]]
+        );
+    }
+
+    /// Panics on every sample.
+    struct Faulty;
+
+    impl ChannelProcessor for Faulty {
+        type Parameters = Parameters;
+
+        fn process_sample(&mut self, _x: f32) -> f32 {
+            panic!("deliberate fault");
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    impl EffectProcessor for Faulty {
+        fn info() -> Info {
+            Info {
+                name: "Faulty".to_string(),
+                ..Info::default()
+            }
+        }
+
+        fn new(_channel: usize, _params: &Parameters) -> Self {
+            Faulty
+        }
+    }
+
+    /// A processor which panics leaves the block silent and records the
+    /// fault, rather than unwinding into the host.
+    #[test]
+    fn fault_silences_block() {
+        let mut plugin: EffectPlugin<Faulty, TestParameters> = EffectPlugin::new(null_host());
+        assert!(!plugin.params().faulted.load(Ordering::Relaxed));
+        let inputs = vec![vec![0.5; 100], vec![0.5; 100]];
+        assert_eq!(process(&mut plugin, &inputs), vec![vec![0.0; 100]; 2]);
+        assert!(plugin.params().faulted.load(Ordering::Relaxed));
+    }
+
+    /// The channels follow a change from stereo to mono and back, and
+    /// unsupported layouts are rejected, leaving the channels as they were.
+    #[test]
+    fn channel_layout() {
+        let mut plugin: EffectPlugin<Passthrough, TestParameters> = EffectPlugin::new(null_host());
+        for &(inputs, outputs, supported, expected) in [
+            (1, 1, true, 1),
+            (2, 2, true, 2),
+            (2, 1, false, 2),
+            (3, 3, false, 2),
+            (0, 0, false, 2),
+            (1, 1, true, 1),
+        ]
+        .iter()
+        {
+            let layout = format!("{} in, {} out", inputs, outputs);
+            assert_eq!(
+                plugin.set_channel_layout(inputs, outputs),
+                supported,
+                "{}",
+                layout
+            );
+            assert_eq!(plugin.channels_mut().len(), expected, "{}", layout);
+            let input = vec![vec![0.5; 64]; expected];
+            assert_eq!(process(&mut plugin, &input), input, "{}", layout);
+        }
+    }
+}
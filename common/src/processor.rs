@@ -0,0 +1,30 @@
+/// The DSP for a single audio channel of an effect. A plugin holds one
+/// processor per channel and feeds each one its channel's samples, so that
+/// effects only need to describe how one channel is processed.
+pub trait ChannelProcessor: Send {
+    /// The scaled parameters the processor reads, usually the plugin's
+    /// `Parameters` struct.
+    type Parameters;
+
+    /// Called whenever the host changes the sample rate.
+    fn set_sample_rate(&mut self, _sample_rate: f32) {}
+
+    /// Called at the start of every block with the current parameters. Large
+    /// blocks are split up, so this is called at least every
+    /// `EffectPlugin::sub_block_size` samples.
+    fn set_parameters(&mut self, _params: &Self::Parameters) {}
+
+    /// Process a single sample.
+    fn process_sample(&mut self, x: f32) -> f32;
+
+    /// Process a block of samples. `input` and `output` are the same length.
+    /// Processors which need to see a whole block at once may override this.
+    fn process_block(&mut self, input: &[f32], output: &mut [f32]) {
+        for (x, out) in input.iter().zip(output.iter_mut()) {
+            *out = self.process_sample(*x);
+        }
+    }
+
+    /// Clear any internal state, such as delay lines or filter history.
+    fn reset(&mut self);
+}
@@ -0,0 +1,164 @@
+/// How changes to a parameter are smoothed while processing. Each parameter
+/// picks one in its plugin's parameter table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// Jump straight to the new value.
+    None,
+    /// Move to the new value at a constant rate, taking the given number of
+    /// milliseconds.
+    Linear(f32),
+    /// Approach the new value quickly at first, then more slowly, with the
+    /// given time constant in milliseconds.
+    Glide(f32),
+}
+
+/// Smooths a single parameter value according to a `Smoothing`.
+pub struct Smoother {
+    smoothing: Smoothing,
+    value: f32,
+    target: f32,
+    // For `Linear`, how far the value moves each sample. For `Glide`, the
+    // one-pole filter coefficient.
+    step: f32,
+    // For `Linear`, the length of the ramp in samples.
+    ramp_samples: f32,
+}
+
+impl Smoother {
+    pub fn new(smoothing: Smoothing, value: f32, sample_rate: f32) -> Smoother {
+        let mut smoother = Smoother {
+            smoothing,
+            value,
+            target: value,
+            step: 0.0,
+            ramp_samples: 1.0,
+        };
+        smoother.set_sample_rate(sample_rate);
+        smoother
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        match self.smoothing {
+            Smoothing::None => {}
+            Smoothing::Linear(ms) => {
+                self.ramp_samples = (ms / 1000.0 * sample_rate).max(1.0);
+                self.step = (self.target - self.value) / self.ramp_samples;
+            }
+            Smoothing::Glide(ms) => {
+                let samples = (ms / 1000.0 * sample_rate).max(1.0);
+                self.step = (-1.0 / samples).exp();
+            }
+        }
+    }
+
+    /// Start moving towards `target`.
+    pub fn set_target(&mut self, target: f32) {
+        #[allow(clippy::float_cmp)]
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+        match self.smoothing {
+            Smoothing::None => self.value = target,
+            Smoothing::Linear(_) => self.step = (target - self.value) / self.ramp_samples,
+            Smoothing::Glide(_) => {}
+        }
+    }
+
+    pub fn get(&self) -> f32 {
+        self.value
+    }
+
+    /// Advance by one sample and return the new value.
+    pub fn advance(&mut self) -> f32 {
+        match self.smoothing {
+            Smoothing::None => self.value = self.target,
+            Smoothing::Linear(_) => {
+                let remaining = self.target - self.value;
+                self.value = if remaining.abs() <= self.step.abs() {
+                    self.target
+                } else {
+                    self.value + self.step
+                };
+            }
+            Smoothing::Glide(_) => {
+                self.value = self.target + (self.value - self.target) * self.step;
+            }
+        }
+        self.value
+    }
+
+    /// Advance by `samples` samples at once and return the new value. This
+    /// ends up in the same place as calling `advance` that many times.
+    pub fn advance_by(&mut self, samples: usize) -> f32 {
+        match self.smoothing {
+            Smoothing::None => self.value = self.target,
+            Smoothing::Linear(_) => {
+                let remaining = self.target - self.value;
+                let distance = self.step * samples as f32;
+                self.value = if remaining.abs() <= distance.abs() {
+                    self.target
+                } else {
+                    self.value + distance
+                };
+            }
+            Smoothing::Glide(_) => {
+                let decay = self.step.powi(samples as i32);
+                self.value = self.target + (self.value - self.target) * decay;
+            }
+        }
+        self.value
+    }
+
+    /// Jump straight to `value`, skipping any smoothing.
+    pub fn reset(&mut self, value: f32) {
+        self.value = value;
+        self.target = value;
+    }
+}
+
+/// Lags all `N` of a plugin's normalized parameter values behind the values
+/// set by the host, so that every control responds slowly, like the controls
+/// of analog gear. This is much slower than the smoothing which removes
+/// zipper noise, and is meant to be heard.
+pub struct ResponseLag<const N: usize> {
+    smoothers: [Smoother; N],
+}
+
+impl<const N: usize> ResponseLag<N> {
+    /// Lag behind changes by `smoothing`, starting from `values`.
+    pub fn new(smoothing: Smoothing, values: &[f32; N], sample_rate: f32) -> ResponseLag<N> {
+        ResponseLag {
+            smoothers: values.map(|value| Smoother::new(smoothing, value, sample_rate)),
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        for smoother in self.smoothers.iter_mut() {
+            smoother.set_sample_rate(sample_rate);
+        }
+    }
+
+    /// Advance every value by `samples` samples towards `targets`, and return
+    /// the lagged values.
+    pub fn process(&mut self, targets: &[f32; N], samples: usize) -> [f32; N] {
+        let mut values = [0.0; N];
+        for ((smoother, &target), value) in self
+            .smoothers
+            .iter_mut()
+            .zip(targets.iter())
+            .zip(values.iter_mut())
+        {
+            smoother.set_target(target);
+            *value = smoother.advance_by(samples);
+        }
+        values
+    }
+
+    /// Jump straight to `values`.
+    pub fn reset(&mut self, values: &[f32; N]) {
+        for (smoother, &value) in self.smoothers.iter_mut().zip(values.iter()) {
+            smoother.reset(value);
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Helpers for exercising plugins outside of a VST host, such as in unit tests.
+
+use std::os::raw::c_void;
+
+use vst::{api::AEffect, host::Host, plugin::HostCallback, util::AtomicFloat};
+
+use crate::{make_bool_strings, make_strings, mapping::Mapping, smoothing::Smoothing};
+
+/// A host callback which ignores every request from the plugin. Notifications
+/// such as `begin_edit`, `end_edit`, and `automate` do nothing, and queries
+/// such as `get_time_info` report that nothing is available.
+extern "C" fn null_host_callback(
+    _effect: *mut AEffect,
+    _opcode: i32,
+    _index: i32,
+    _value: isize,
+    _ptr: *mut c_void,
+    _opt: f32,
+) -> isize {
+    0
+}
+
+/// Returns a `HostCallback` which ignores everything the plugin asks of it.
+/// Unlike `HostCallback::default()`, this can be used with parameter setters,
+/// which notify the host of every change.
+///
+/// This is a function rather than a `NullHost` type because `HostCallback` is
+/// a concrete struct, not a trait, so parameters can only ever hold one.
+pub fn null_host() -> HostCallback {
+    HostCallback::wrap(null_host_callback, std::ptr::null_mut())
+}
+
+/// A small set of parameters generated by the parameter macros, for checking
+/// the generated code without needing a full plugin.
+pub struct Parameters {
+    pub gain: f32,
+    pub enabled: bool,
+}
+
+impl From<&TestParameters> for Parameters {
+    fn from(params: &TestParameters) -> Self {
+        Parameters {
+            gain: params.gain.get() * 2.0,
+            enabled: params.enabled.get() > 0.5,
+        }
+    }
+}
+
+macro_rules! table {
+    ($macro:ident) => {
+        $macro! {
+        //  RawParameter identifier, ParameterType identifier
+            TestParameters,          TestParameterType;
+        //  variant  field_name  name       idx  default  smoothing        mapping              usable_min  usable_max  strings
+            Gain,    gain,       "Gain",    0,   0.5,     Smoothing::None, Mapping::Linear,     0.1,        0.9,        |x: f32| make_strings(x * 100.0, "%");
+            Enabled, enabled,    "Enabled", 1,   1.0,     Smoothing::None, Mapping::Stepped(2), 0.0,        1.0,        make_bool_strings;
+        }
+    };
+}
+
+impl TestParameterType {
+    pub const COUNT: usize = 2;
+}
+
+impl_all! {TestParameters, TestParameterType, table}
+
+impl TestParameters {
+    // None of the parameters can be typed in.
+    fn parse_text(&self, _parameter: TestParameterType, _text: &str) -> Option<f32> {
+        None
+    }
+
+    /// Create the parameters with their default values, using `null_host`.
+    pub fn new() -> TestParameters {
+        TestParameters::default(null_host())
+    }
+}
+
+impl Default for TestParameters {
+    fn default() -> TestParameters {
+        TestParameters::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use vst::plugin::PluginParameters;
+
+    use super::*;
+
+    #[test]
+    fn get_and_set() {
+        let params = TestParameters::new();
+        assert_eq!(params.get(TestParameterType::Gain), 0.5);
+        assert_eq!(params.get(TestParameterType::Enabled), 1.0);
+
+        params.set(0.25, TestParameterType::Gain);
+        params.set_parameter(1, 0.0);
+        assert_eq!(params.get_parameter(0), 0.25);
+        assert_eq!(params.get(TestParameterType::Enabled), 0.0);
+        // The null host ignores the notifications sent here.
+        params.flush_edits();
+
+        // Indices past the end of the table are ignored.
+        params.set_parameter(2, 1.0);
+        assert_eq!(params.get_parameter(2), 0.0);
+    }
+
+    /// Parameters are displayed from their scaled values.
+    #[test]
+    fn display() {
+        let params = TestParameters::new();
+        assert_eq!(params.get_parameter_name(0), "Gain");
+        assert_eq!(params.get_parameter_text(0), "100.00");
+        assert_eq!(params.get_parameter_label(0), "%");
+        assert_eq!(params.get_parameter_name(1), "Enabled");
+        assert_eq!(params.get_parameter_text(1), "ON");
+
+        params.set(0.25, TestParameterType::Gain);
+        params.set(0.0, TestParameterType::Enabled);
+        assert_eq!(params.get_parameter_text(0), "50.00");
+        assert_eq!(params.get_parameter_text(1), "OFF");
+        assert!(!params.string_to_parameter(0, "50".to_string()));
+    }
+
+    #[test]
+    fn snapshot() {
+        let params = TestParameters::new();
+        params.set(0.25, TestParameterType::Gain);
+        let snapshot = params.snapshot();
+        assert_eq!(snapshot.gain, 0.5);
+        assert!(snapshot.enabled);
+    }
+
+    static BEGIN_EDITS: AtomicUsize = AtomicUsize::new(0);
+
+    /// A host which counts `begin_edit` notifications, and ignores everything
+    /// else.
+    extern "C" fn counting_host(
+        _effect: *mut AEffect,
+        opcode: i32,
+        _index: i32,
+        _value: isize,
+        _ptr: *mut c_void,
+        _opt: f32,
+    ) -> isize {
+        // audioMasterBeginEdit
+        if opcode == 43 {
+            BEGIN_EDITS.fetch_add(1, Ordering::Relaxed);
+        }
+        0
+    }
+
+    /// `set` ignores a parameter being set to the value it already has, but
+    /// `set_forced` always stores the value and notifies the host.
+    #[test]
+    fn set_forced_skips_echo_guard() {
+        let params =
+            TestParameters::default(HostCallback::wrap(counting_host, std::ptr::null_mut()));
+        params.set(0.5, TestParameterType::Gain);
+        params.flush_edits();
+        assert_eq!(BEGIN_EDITS.load(Ordering::Relaxed), 0);
+
+        params.set_forced(0.5, TestParameterType::Gain);
+        assert_eq!(params.get(TestParameterType::Gain), 0.5);
+        params.flush_edits();
+        assert_eq!(BEGIN_EDITS.load(Ordering::Relaxed), 1);
+
+        params.set_forced(0.75, TestParameterType::Gain);
+        assert_eq!(params.get(TestParameterType::Gain), 0.75);
+        params.flush_edits();
+        assert_eq!(BEGIN_EDITS.load(Ordering::Relaxed), 2);
+    }
+
+    /// A value outside a parameter's usable range is clamped in the values
+    /// given to the processor, while the host still reads back the value it
+    /// set. Gain is only usable from 0.1 to 0.9.
+    #[test]
+    fn usable_range() {
+        let params = TestParameters::new();
+        let gain = i32::from(TestParameterType::Gain) as usize;
+        for &(value, expected) in [(0.0, 0.1), (0.5, 0.5), (1.0, 0.9)].iter() {
+            params.set(value, TestParameterType::Gain);
+            let mut values = params.all_normalized();
+            TestParameters::clamp_to_usable(&mut values);
+            assert_eq!(params.get(TestParameterType::Gain), value);
+            assert_eq!(values[gain], expected, "set gain to {}", value);
+        }
+    }
+}
+
+/// A table in which two parameters share an index, which `check_indices`
+/// should reject. Only the index checks are used, so the rest of what the
+/// table generates is dead code, and the shared index is an unreachable
+/// pattern.
+#[cfg(test)]
+#[allow(dead_code, unreachable_patterns)]
+mod malformed {
+    use vst::{host::Host, util::AtomicFloat};
+
+    use crate::{make_strings, mapping::Mapping, smoothing::Smoothing};
+
+    pub struct Parameters {
+        pub left: f32,
+        pub right: f32,
+    }
+
+    impl From<&MalformedParameters> for Parameters {
+        fn from(params: &MalformedParameters) -> Self {
+            Parameters {
+                left: params.left.get(),
+                right: params.right.get(),
+            }
+        }
+    }
+
+    macro_rules! table {
+        ($macro:ident) => {
+            $macro! {
+            //  RawParameter identifier, ParameterType identifier
+                MalformedParameters,     MalformedParameterType;
+            //  variant  field_name  name      idx  default  smoothing        mapping          usable_min  usable_max  strings
+                Left,    left,       "Left",   0,   0.5,     Smoothing::None, Mapping::Linear, 0.0,        1.0,        |x: f32| make_strings(x, "");
+                Right,   right,      "Right",  0,   0.5,     Smoothing::None, Mapping::Linear, 0.0,        1.0,        |x: f32| make_strings(x, "");
+            }
+        };
+    }
+
+    impl MalformedParameterType {
+        pub const COUNT: usize = 2;
+    }
+
+    impl_all! {MalformedParameters, MalformedParameterType, table}
+
+    impl MalformedParameters {
+        fn parse_text(&self, _parameter: MalformedParameterType, _text: &str) -> Option<f32> {
+            None
+        }
+    }
+
+    #[test]
+    fn shared_index_is_rejected() {
+        assert_eq!(
+            MalformedParameterType::check_indices(),
+            Err("Right has index 0, which doesn't convert back to it".to_string())
+        );
+    }
+
+    /// Debug builds refuse to make parameters from a malformed table.
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "Malformed parameter table")]
+    fn default_panics() {
+        MalformedParameters::default(super::null_host());
+    }
+}
@@ -0,0 +1,231 @@
+use vst::plugin::Plugin;
+
+use common::{plugin::EffectProcessor, processor::ChannelProcessor, test_util::null_host};
+
+use crate::{
+    parameters::{EdgeMode, Mode, ParameterType, RawParameters, StereoMode, Window},
+    processor::MedianProcessor,
+    MedianFilter, Parameters, TieBreak,
+};
+
+impl MedianFilter {
+    /// Run `input` through the filter as mono with a fixed configuration, and
+    /// return the output. This is checked against a golden file by a unit
+    /// test, to catch unintended changes to the filter's output. Only the
+    /// window size and wet/dry are set, so a change to any other parameter's
+    /// default changes the output too.
+    #[doc(hidden)]
+    pub fn dump_response(input: &[f32]) -> Vec<f32> {
+        // The edits made here are sent to a host which ignores them.
+        let mut filter = MedianFilter::new(null_host());
+        filter.params.set(1.0, ParameterType::WetDryLeft);
+        filter.params.set(0.25, ParameterType::WindowSize);
+        let mut output = input.to_vec();
+        filter.process_interleaved(&mut output, 1);
+        output
+    }
+
+    /// Regenerate the golden file the unit tests check `dump_response`
+    /// against, from the current code. Only do this once a change to the
+    /// output has been checked and is intended.
+    #[cfg(feature = "golden")]
+    pub fn write_golden() -> std::io::Result<()> {
+        let bytes: Vec<u8> = Self::dump_response(&golden_input())
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        std::fs::write(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/golden/response.bin"),
+            bytes,
+        )
+    }
+
+    /// Run a few sanity checks on the filtering code: an impulse, a DC signal,
+    /// and silence are each filtered at 0% and 100% wet. Returns an error
+    /// describing the first check that failed.
+    /// Hosts or CI can call this to verify that a build behaves correctly.
+    pub fn self_test() -> Result<(), String> {
+        const WINDOW_SIZE: usize = 5;
+        Self::check_channel(WINDOW_SIZE)
+    }
+
+    /// Parameters for the self test and unit tests: a fully wet filter with every other
+    /// effect turned off.
+    pub(crate) fn test_parameters(window_size: usize) -> Parameters {
+        let mut params = RawParameters::default(null_host()).snapshot();
+        params.window_size = Window::new(window_size);
+        params.stereo_mode = StereoMode::LeftRight;
+        params.wet_dry_left = 1.0;
+        params.full_wet = false;
+        params.mode = Mode::Smooth;
+        params.compensate_dry = false;
+        params.limit = false;
+        params.bypass = false;
+        params
+    }
+
+    /// Run the self test's checks against a channel with a window size of
+    /// `window_size`.
+    fn check_channel(window_size: usize) -> Result<(), String> {
+        let mut params = Self::test_parameters(window_size);
+        // None of the test signals change over the first window, so replicating
+        // the first sample matches the reference output in `check_channel_with`.
+        params.edge_mode = EdgeMode::Replicate;
+        // The reference takes the upper middle sample of even windows.
+        params.tie_break = TieBreak::Upper;
+
+        let len = window_size * 4;
+        // The impulse is placed after the window has filled so that the
+        // expected output doesn't depend on how partial windows are handled.
+        let mut impulse = vec![0.0; len];
+        impulse[window_size] = 1.0;
+        let signals = [
+            ("impulse", impulse),
+            ("DC", vec![0.5; len]),
+            ("silence", vec![0.0; len]),
+        ];
+
+        for (name, input) in signals.iter() {
+            params.wet_dry_left = 0.0;
+            Self::check_passthrough(name, &mut MedianProcessor::new(0, &params), input)?;
+            params.wet_dry_left = 1.0;
+            Self::check_channel_with(name, &mut MedianProcessor::new(0, &params), input)?;
+        }
+        Ok(())
+    }
+
+    /// Check that `processor`, which should be fully dry, passes `input`
+    /// through unchanged. `name` describes `input` in the error.
+    fn check_passthrough(
+        name: &str,
+        processor: &mut MedianProcessor,
+        input: &[f32],
+    ) -> Result<(), String> {
+        let mut output = vec![0.0; input.len()];
+        processor.process_block(input, &mut output);
+        check_finite(name, &output, "0% wet")?;
+        #[allow(clippy::float_cmp)]
+        if output != input {
+            return Err(format!("{}: 0% wet does not pass input through", name));
+        }
+        Ok(())
+    }
+
+    /// Run `input` through `processor`, which should be fully wet, replicate
+    /// its edges, and take the upper of two middle samples, then check that
+    /// its window is intact and that it output the median of each window.
+    /// `name` describes `input` in the error.
+    fn check_channel_with(
+        name: &str,
+        processor: &mut MedianProcessor,
+        input: &[f32],
+    ) -> Result<(), String> {
+        let window_size = processor.filter.window_size();
+        let mut output = vec![0.0; input.len()];
+        processor.process_block(input, &mut output);
+        check_finite(name, &output, "100% wet")?;
+        processor
+            .filter
+            .validate()
+            .map_err(|err| format!("{}: {}", name, err))?;
+        for i in 0..input.len() {
+            let start = (i + 1).saturating_sub(window_size);
+            let mut window = input[start..=i].to_vec();
+            window.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let expected = window[window.len() / 2];
+            if (output[i] - expected).abs() > 1.0e-6 {
+                return Err(format!(
+                    "{}: expected {} at sample {} (100% wet), got {}",
+                    name, expected, i, output[i]
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return an error if any sample of `output` isn't finite. `name` and `wet`
+/// describe the signal and the wet level in the error.
+fn check_finite(name: &str, output: &[f32], wet: &str) -> Result<(), String> {
+    match output.iter().position(|x| !x.is_finite()) {
+        Some(i) => Err(format!(
+            "{}: non-finite output at sample {} ({})",
+            name, i, wet
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The input `dump_response` is checked with: noise, which the filter smooths,
+/// then a ramp, which it only delays, then clicks on silence, which it takes
+/// out. The noise is generated with integer arithmetic, so the input is the
+/// same on every platform.
+#[cfg(any(test, feature = "golden"))]
+fn golden_input() -> Vec<f32> {
+    let mut seed: u32 = 1;
+    let noise = (0..1024).map(move |_| {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        (seed >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+    });
+    let ramp = (0..512).map(|i| i as f32 / 512.0);
+    let clicks = (0..512).map(|i| if i % 64 == 32 { 1.0 } else { 0.0 });
+    noise.chain(ramp).chain(clicks).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_test_passes() {
+        assert_eq!(MedianFilter::self_test(), Ok(()));
+    }
+
+    /// The checks fail on a processor whose window has been broken.
+    #[test]
+    fn check_channel_catches_a_broken_window() {
+        let mut params = MedianFilter::test_parameters(5);
+        params.edge_mode = EdgeMode::Replicate;
+        params.tie_break = TieBreak::Upper;
+        let mut processor = MedianProcessor::new(0, &params);
+        // Constant over the first window, then a ramp, so that the window holds
+        // different samples by the end.
+        let input: Vec<f32> = (0..20).map(|i| i.max(5) as f32 / 20.0).collect();
+        assert_eq!(
+            MedianFilter::check_channel_with("ramp", &mut processor, &input),
+            Ok(())
+        );
+
+        processor.filter.corrupt();
+        assert!(MedianFilter::check_channel_with("ramp", &mut processor, &[]).is_err());
+    }
+
+    /// The output of `MedianFilter::dump_response` for `golden_input`, as
+    /// little endian f32s.
+    const GOLDEN_RESPONSE: &[u8] = include_bytes!("../golden/response.bin");
+
+    /// `dump_response` still matches the golden file. If a change to the
+    /// output is intended, regenerate the file with `write_golden`. The
+    /// tolerance allows for small differences between platforms' math
+    /// libraries.
+    #[test]
+    fn golden_response() {
+        let output = MedianFilter::dump_response(&golden_input());
+        let golden: Vec<f32> = GOLDEN_RESPONSE
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        assert_eq!(output.len(), golden.len());
+        for (i, (&x, &expected)) in output.iter().zip(golden.iter()).enumerate() {
+            assert!(
+                (x - expected).abs() <= 1.0e-5,
+                "sample {} is {}, expected {}",
+                i,
+                x,
+                expected
+            );
+        }
+    }
+}
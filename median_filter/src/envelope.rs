@@ -0,0 +1,80 @@
+use crate::{window::SortedWindow, TieBreak, MAX_WINDOW_SIZE};
+
+/// Follows the amplitude of a signal by taking the median of its absolute
+/// value over a sliding window. Unlike an RMS or peak envelope, a few loud
+/// spikes in the window barely move it, which makes it a robust estimate of
+/// the signal's underlying level.
+pub struct MedianEnvelope {
+    window: SortedWindow,
+    window_size: usize,
+    level: f32,
+}
+
+impl MedianEnvelope {
+    pub fn new(window_size: usize) -> MedianEnvelope {
+        MedianEnvelope {
+            window: SortedWindow::with_capacity(window_size, MAX_WINDOW_SIZE),
+            window_size,
+            level: 0.0,
+        }
+    }
+
+    /// Change the window size. This clears the envelope.
+    pub fn set_window_size(&mut self, window_size: usize) {
+        if window_size != self.window_size {
+            self.window.set_window_size(window_size);
+            self.window_size = window_size;
+            self.level = 0.0;
+        }
+    }
+
+    /// Feed a sample into the envelope and return the new level.
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.window.consume(x.abs());
+        self.level = self.window.median(TieBreak::Average);
+        self.level
+    }
+
+    /// The level after the last sample.
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    pub fn reset(&mut self) {
+        self.window.clear();
+        self.level = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sparse loud spikes barely move the median envelope of a sine wave,
+    /// while they swamp its RMS level.
+    #[test]
+    fn ignores_spikes() {
+        const AMPLITUDE: f32 = 0.5;
+        const WINDOW_SIZE: usize = 441;
+        let mut envelope = MedianEnvelope::new(WINDOW_SIZE);
+        let mut sum_of_squares = 0.0;
+        for i in 0..WINDOW_SIZE * 4 {
+            let spike = if i % 100 == 0 { 20.0 } else { 0.0 };
+            let x = (i as f32 * std::f32::consts::TAU / 100.3).sin() * AMPLITUDE + spike;
+            envelope.process(x);
+            if i >= WINDOW_SIZE * 3 {
+                sum_of_squares += x * x;
+            }
+        }
+        // Half of a sine's samples are above sin(45°) of its amplitude.
+        let expected = AMPLITUDE * std::f32::consts::FRAC_1_SQRT_2;
+        let level = envelope.level();
+        assert!(
+            (level - expected).abs() <= expected * 0.05,
+            "level is {}",
+            level
+        );
+        let rms = (sum_of_squares / WINDOW_SIZE as f32).sqrt();
+        assert!(rms >= expected * 2.0, "RMS level is {}", rms);
+    }
+}
@@ -0,0 +1,93 @@
+use common::{db_to_gain, make_strings, quantize_index};
+
+/// How gain parameters are shown to the user, either in decibels or as linear
+/// multipliers. Only the display changes; the parameters are stored and
+/// automated the same way in either case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GainDisplay {
+    Decibels,
+    Linear,
+}
+
+impl GainDisplay {
+    pub fn from_normalized(x: f32) -> GainDisplay {
+        match quantize_index(x, 2) {
+            0 => GainDisplay::Decibels,
+            _ => GainDisplay::Linear,
+        }
+    }
+}
+
+impl std::fmt::Display for GainDisplay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GainDisplay::Decibels => write!(f, "dB"),
+            GainDisplay::Linear => write!(f, "Linear"),
+        }
+    }
+}
+
+/// The value of a gain parameter, along with how it should be displayed.
+#[derive(Debug, Clone, Copy)]
+pub struct Gain {
+    pub db: f32,
+    pub display: GainDisplay,
+}
+
+impl Gain {
+    /// The gain as a linear multiplier.
+    pub fn linear(self) -> f32 {
+        db_to_gain(self.db)
+    }
+
+    /// The gain as text, in the units chosen by `display`.
+    pub fn strings(self) -> (String, String) {
+        match self.display {
+            GainDisplay::Decibels => make_strings(self.db, "dB"),
+            GainDisplay::Linear => make_strings(self.linear(), "x"),
+        }
+    }
+}
+
+/// The range of a gain parameter, in decibels.
+#[derive(Debug, Clone, Copy)]
+pub struct GainRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl GainRange {
+    /// Convert a normalized parameter value to decibels.
+    pub fn to_db(self, normalized: f32) -> f32 {
+        self.min + normalized * (self.max - self.min)
+    }
+
+    /// Convert decibels to a normalized parameter value, clamped to the range.
+    pub fn to_normalized(self, db: f32) -> f32 {
+        ((db - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+    }
+}
+
+/// Parse a gain typed in by the user, either in decibels ("6 dB") or as a
+/// linear multiplier ("2 x"). A number without a unit is read in the units of
+/// `display`. Returns the gain in decibels.
+pub fn parse_gain(text: &str, display: GainDisplay) -> Option<f32> {
+    let text = text.trim().to_ascii_lowercase();
+    let (number, display) = if let Some(number) = text.strip_suffix("db") {
+        (number, GainDisplay::Decibels)
+    } else if let Some(number) = text.strip_suffix('x') {
+        (number, GainDisplay::Linear)
+    } else {
+        (text.as_str(), display)
+    };
+    let value: f32 = number.trim().parse().ok()?;
+    if value.is_nan() {
+        return None;
+    }
+    match display {
+        GainDisplay::Decibels => Some(value),
+        // A multiplier of zero is silence, which is negative infinity decibels.
+        GainDisplay::Linear if value <= 0.0 => Some(f32::NEG_INFINITY),
+        GainDisplay::Linear => Some(20.0 * value.log10()),
+    }
+}
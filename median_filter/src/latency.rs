@@ -0,0 +1,137 @@
+use std::ptr::null_mut;
+
+use vst::plugin::HostCallback;
+
+use common::quantize_index;
+
+/// `audioMasterIOChanged`, which asks the host to re-read the plugin's inputs,
+/// outputs and initial delay. vst-rs doesn't wrap this opcode.
+const IO_CHANGED: i32 = 13;
+/// With `LatencyUpdates::Coarse`, latency changes smaller than this many
+/// samples aren't reported.
+pub const COARSE_LATENCY_STEP: usize = 64;
+
+/// When changes in the plugin's latency are reported to the host. Some hosts
+/// rebuild their delay compensation every time the latency changes, which can
+/// glitch the audio if it happens during playback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatencyUpdates {
+    /// Report every change.
+    Immediate,
+    /// Only report changes of at least `COARSE_LATENCY_STEP` samples.
+    Coarse,
+    /// Only report changes while the transport is stopped.
+    WhenStopped,
+}
+
+impl LatencyUpdates {
+    pub fn from_normalized(x: f32) -> LatencyUpdates {
+        match quantize_index(x, 3) {
+            0 => LatencyUpdates::Immediate,
+            1 => LatencyUpdates::Coarse,
+            _ => LatencyUpdates::WhenStopped,
+        }
+    }
+}
+
+impl std::fmt::Display for LatencyUpdates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LatencyUpdates::Immediate => write!(f, "Immediate"),
+            LatencyUpdates::Coarse => write!(f, "Coarse"),
+            LatencyUpdates::WhenStopped => write!(f, "When Stopped"),
+        }
+    }
+}
+
+/// Keeps track of the latency the host was last told about, and tells the host
+/// when it changes.
+pub struct LatencyReporter {
+    reported: usize,
+}
+
+impl LatencyReporter {
+    pub fn new(latency: usize) -> LatencyReporter {
+        LatencyReporter { reported: latency }
+    }
+
+    /// The latency the host was last told about, in samples.
+    pub fn reported(&self) -> usize {
+        self.reported
+    }
+
+    /// Returns true if a change in latency to `latency` should be reported
+    /// under `updates`. `playing` is whether the host's transport is playing.
+    pub fn should_report(&self, latency: usize, updates: LatencyUpdates, playing: bool) -> bool {
+        if latency == self.reported {
+            return false;
+        }
+        match updates {
+            LatencyUpdates::Immediate => true,
+            LatencyUpdates::Coarse => {
+                let change = latency.max(self.reported) - latency.min(self.reported);
+                change >= COARSE_LATENCY_STEP
+            }
+            LatencyUpdates::WhenStopped => !playing,
+        }
+    }
+
+    /// Tell the host about the new latency if `should_report` allows it.
+    /// Returns true if the host was told.
+    pub fn update(
+        &mut self,
+        host: &HostCallback,
+        latency: usize,
+        updates: LatencyUpdates,
+        playing: bool,
+    ) -> bool {
+        if !self.should_report(latency, updates, playing) {
+            return false;
+        }
+        self.reported = latency;
+
+        let effect = host.raw_effect();
+        if let Some(callback) = host.raw_callback() {
+            if !effect.is_null() {
+                // Safety: the effect pointer is the one the host created the
+                // plugin with, and stays valid for as long as the plugin exists.
+                unsafe {
+                    (*effect).initialDelay = latency as i32;
+                }
+                callback(effect, IO_CHANGED, 0, 0, null_mut(), 0.0);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coarse_updates_skip_small_changes() {
+        let host = HostCallback::default();
+        let mut reporter = LatencyReporter::new(10);
+        let small = 10 + COARSE_LATENCY_STEP - 1;
+        assert!(!reporter.update(&host, small, LatencyUpdates::Coarse, true));
+        assert_eq!(reporter.reported(), 10);
+        let large = 10 + COARSE_LATENCY_STEP;
+        assert!(reporter.update(&host, large, LatencyUpdates::Coarse, true));
+        assert_eq!(reporter.reported(), large);
+        // Back down by a small step is skipped too.
+        assert!(!reporter.update(&host, large - 1, LatencyUpdates::Coarse, true));
+    }
+
+    #[test]
+    fn immediate_and_stopped_updates() {
+        let host = HostCallback::default();
+        let mut reporter = LatencyReporter::new(10);
+        assert!(reporter.update(&host, 11, LatencyUpdates::Immediate, true));
+        assert!(!reporter.update(&host, 12, LatencyUpdates::WhenStopped, true));
+        assert!(reporter.update(&host, 12, LatencyUpdates::WhenStopped, false));
+        assert_eq!(reporter.reported(), 12);
+        // An unchanged latency is never reported.
+        assert!(!reporter.update(&host, 12, LatencyUpdates::Immediate, false));
+    }
+}
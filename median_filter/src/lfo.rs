@@ -0,0 +1,172 @@
+use common::quantize_index;
+use vst::api::{TimeInfo, TimeInfoFlags};
+
+/// The tempo the LFO runs at when the host doesn't report one, in beats per
+/// minute.
+const DEFAULT_TEMPO: f64 = 120.0;
+
+/// The shape of the LFO's waveform. Every shape starts each cycle at its
+/// lowest point, so that a cycle lines up with the beat it starts on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    /// Low for the first half of each cycle, and high for the second half.
+    Square,
+}
+
+impl LfoShape {
+    pub fn from_normalized(x: f32) -> LfoShape {
+        match quantize_index(x, 3) {
+            0 => LfoShape::Sine,
+            1 => LfoShape::Triangle,
+            _ => LfoShape::Square,
+        }
+    }
+
+    /// The value of the waveform at `phase`, from 0.0 to 1.0.
+    fn value(self, phase: f64) -> f32 {
+        let phase = phase as f32;
+        match self {
+            LfoShape::Sine => 0.5 - 0.5 * (phase * std::f32::consts::TAU).cos(),
+            LfoShape::Triangle => 1.0 - (2.0 * phase - 1.0).abs(),
+            LfoShape::Square => {
+                if phase < 0.5 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for LfoShape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LfoShape::Sine => write!(f, "Sine"),
+            LfoShape::Triangle => write!(f, "Triangle"),
+            LfoShape::Square => write!(f, "Square"),
+        }
+    }
+}
+
+/// A length of time as a note division, such as the length of an LFO cycle
+/// or of a gate step. Bars are assumed to be in 4/4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteDivision {
+    FourBars,
+    TwoBars,
+    Whole,
+    Half,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ThirtySecond,
+}
+
+impl NoteDivision {
+    pub fn from_normalized(x: f32) -> NoteDivision {
+        match quantize_index(x, 8) {
+            0 => NoteDivision::FourBars,
+            1 => NoteDivision::TwoBars,
+            2 => NoteDivision::Whole,
+            3 => NoteDivision::Half,
+            4 => NoteDivision::Quarter,
+            5 => NoteDivision::Eighth,
+            6 => NoteDivision::Sixteenth,
+            _ => NoteDivision::ThirtySecond,
+        }
+    }
+
+    /// The length of the division, in quarter notes.
+    pub fn beats(self) -> f64 {
+        match self {
+            NoteDivision::FourBars => 16.0,
+            NoteDivision::TwoBars => 8.0,
+            NoteDivision::Whole => 4.0,
+            NoteDivision::Half => 2.0,
+            NoteDivision::Quarter => 1.0,
+            NoteDivision::Eighth => 0.5,
+            NoteDivision::Sixteenth => 0.25,
+            NoteDivision::ThirtySecond => 0.125,
+        }
+    }
+}
+
+impl std::fmt::Display for NoteDivision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NoteDivision::FourBars => write!(f, "4 Bars"),
+            NoteDivision::TwoBars => write!(f, "2 Bars"),
+            NoteDivision::Whole => write!(f, "1/1"),
+            NoteDivision::Half => write!(f, "1/2"),
+            NoteDivision::Quarter => write!(f, "1/4"),
+            NoteDivision::Eighth => write!(f, "1/8"),
+            NoteDivision::Sixteenth => write!(f, "1/16"),
+            NoteDivision::ThirtySecond => write!(f, "1/32"),
+        }
+    }
+}
+
+/// A low frequency oscillator which follows the host's tempo. The position
+/// within the song is tracked in quarter notes, so that the LFO stays in
+/// sync with the song whatever its rate.
+#[derive(Clone, Copy)]
+pub struct Lfo {
+    // The song position, in quarter notes.
+    position: f64,
+    tempo: f64,
+    sample_rate: f32,
+}
+
+impl Lfo {
+    pub fn new(sample_rate: f32) -> Lfo {
+        Lfo {
+            position: 0.0,
+            tempo: DEFAULT_TEMPO,
+            sample_rate,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// The song position, in quarter notes.
+    pub fn position(&self) -> f64 {
+        self.position
+    }
+
+    /// Jump to the host's song position and tempo at the start of a block.
+    /// Whatever the host doesn't report is left as it was, so without a
+    /// song position, the LFO runs freely from where it is.
+    pub fn sync(&mut self, time_info: Option<TimeInfo>) {
+        if let Some(time_info) = time_info {
+            let flags = TimeInfoFlags::from_bits_truncate(time_info.flags);
+            if flags.contains(TimeInfoFlags::TEMPO_VALID) && time_info.tempo > 0.0 {
+                self.tempo = time_info.tempo;
+            }
+            if flags.contains(TimeInfoFlags::PPQ_POS_VALID) {
+                self.position = time_info.ppq_pos;
+            }
+        }
+    }
+
+    /// Advance by one sample and return the waveform's value, from 0.0 to
+    /// 1.0, at the previous position.
+    pub fn advance(&mut self, shape: LfoShape, rate: NoteDivision) -> f32 {
+        let phase = (self.position / rate.beats()).rem_euclid(1.0);
+        self.position += self.beats_per_sample();
+        shape.value(phase)
+    }
+
+    /// Advance by `samples` samples without reading the waveform.
+    pub fn skip(&mut self, samples: usize) {
+        self.position += self.beats_per_sample() * samples as f64;
+    }
+
+    fn beats_per_sample(&self) -> f64 {
+        self.tempo / 60.0 / self.sample_rate as f64
+    }
+}
@@ -1,151 +1,2379 @@
 #[macro_use]
 extern crate common;
 
-use std::sync::Arc;
+mod checks;
+pub mod envelope;
+pub mod gain;
+pub mod latency;
+pub mod lfo;
+pub mod limiter;
+pub mod meters;
+pub mod multiband;
+pub mod normalize;
+pub mod oversample;
+mod parameters;
+pub mod pitch;
+mod processor;
+pub mod safety;
+pub mod scope;
+pub mod shelf;
+pub mod window;
+
+use std::{
+    ops::Range,
+    sync::{atomic::Ordering, Arc},
+};
 
-use median::heap::Filter;
 use vst::{
-    api::Supported,
-    buffer::AudioBuffer,
+    api::{Supported, TimeInfo, TimeInfoFlags},
+    buffer::{AudioBuffer, SendEventBuffer},
     host::Host,
-    plugin::{CanDo, Category, HostCallback, Info, Plugin, PluginParameters},
-    util::AtomicFloat,
+    plugin::{CanDo, HostCallback, Info, Plugin, PluginParameters},
+};
+
+use common::{
+    cc_feedback::CcFeedback,
+    denormal::FlushDenormals,
+    interleave::{deinterleave, interleave},
+    plugin::{catch_faults, silence, EffectPlugin, MAX_CHANNELS},
+    processor::ChannelProcessor,
+    smoothing::{ResponseLag, Smoothing},
+    test_util::null_host,
 };
+use gain::GainRange;
+use latency::LatencyReporter;
+use lfo::Lfo;
+use meters::{BlockLevels, Meters, MonoFold};
+use normalize::InputNormalizer;
+use parameters::{cc_feedback_interval, MonoInput, ParameterType, RawParameters, StereoMode};
+pub use parameters::{Parameters, TieBreak};
+use processor::{MedianProcessor, Ramp};
+use safety::{SafetyMute, SafetySettings};
+use scope::Scope;
 
-use common::make_strings;
+/// The largest window size, in samples, that the filter may use. This bounds
+/// the memory used by the filter and delay lines.
+const MAX_WINDOW_SIZE: usize = 4096;
+/// The largest window size that can be set when the window is in samples.
+const MAX_WINDOW_SAMPLES: usize = 100;
+/// The largest window size that can be set when the window is in milliseconds.
+const MAX_WINDOW_MS: f32 = 20.0;
+/// The range of frequencies the window can be set to when the window is in
+/// Hz. The lowest frequency gives the largest window.
+const MIN_WINDOW_HZ: f32 = 20.0;
+const MAX_WINDOW_HZ: f32 = 20000.0;
+/// How long it takes to crossfade between the filtered and bypassed signal.
+const BYPASS_RAMP_MS: f32 = 10.0;
+/// The time constant used to measure loudness for `ConstantLoudness`.
+const LOUDNESS_WINDOW_MS: f32 = 300.0;
+/// The time constant used to smooth the `ConstantLoudness` gain.
+const LOUDNESS_SMOOTHING_MS: f32 = 50.0;
+/// The largest gain `ConstantLoudness` may apply to the filtered signal.
+/// `BypassGainMatch` may boost or cut the bypassed signal by up to as much.
+const MAX_LOUDNESS_GAIN: f32 = 4.0;
+/// How quickly `SafeMakeup` lets the makeup gain come back after a peak.
+const MAKEUP_RELEASE_MS: f32 = 200.0;
+/// The ranges of the gain parameters, in decibels.
+const THRESHOLD_RANGE: GainRange = GainRange {
+    min: -60.0,
+    max: 0.0,
+};
+const CEILING_RANGE: GainRange = GainRange {
+    min: -24.0,
+    max: 0.0,
+};
+const DRIVE_RANGE: GainRange = GainRange {
+    min: 0.0,
+    max: 24.0,
+};
+const OUTPUT_GAIN_RANGE: GainRange = GainRange {
+    min: -24.0,
+    max: 24.0,
+};
+const INPUT_GAIN_RANGE: GainRange = GainRange {
+    min: -24.0,
+    max: 24.0,
+};
+const SHELF_GAIN_RANGE: GainRange = GainRange {
+    min: -12.0,
+    max: 12.0,
+};
+const SILENCE_THRESHOLD_RANGE: GainRange = GainRange {
+    min: -90.0,
+    max: -30.0,
+};
+/// The sample rate used with the `deterministic` feature, whatever the host
+/// asks for.
+const DETERMINISTIC_SAMPLE_RATE: f32 = 44100.0;
+/// The largest offset `DcTrim` can add to the output, in either direction.
+const MAX_DC_TRIM: f32 = 0.2;
+/// How long every parameter takes to reach a new value with `VintageFeel`.
+const VINTAGE_LAG_MS: f32 = 40.0;
+/// The longest `StereoOffset`, in samples.
+const MAX_STEREO_OFFSET: usize = 32;
+/// The most `Stagger` can lengthen the right channel's window, in percent.
+const MAX_STAGGER: f32 = 10.0;
+/// The parameters which set a window size, which are held during a gesture
+/// with `set_window_commit_on_release`.
+const WINDOW_PARAMETERS: [ParameterType; 6] = [
+    ParameterType::WindowSize,
+    ParameterType::MidWindow,
+    ParameterType::SideWindow,
+    ParameterType::LowBandWindow,
+    ParameterType::MidBandWindow,
+    ParameterType::HighBandWindow,
+];
+/// How much input is captured to find its peak when normalizing the input.
+const NORMALIZE_CAPTURE_MS: f32 = 1000.0;
+/// The level normalizing the input brings its peak to, in dBFS.
+const NORMALIZE_TARGET_DB: f32 = -1.0;
+/// The least time between two batches of MIDI CC feedback.
+const CC_FEEDBACK_INTERVAL_MS: f32 = 20.0;
+/// The number of steps in the gate pattern.
+const GATE_STEPS: usize = 16;
+/// How long the gate takes to move between fully open and fully closed. This
+/// keeps steps from clicking.
+const GATE_RAMP_MS: f32 = 2.0;
+/// How long `SoftStart` takes to fade the output in after the plugin is
+/// loaded or resumed. Long enough to avoid a click, short enough to go
+/// unnoticed.
+const SOFT_START_MS: f32 = 10.0;
+/// The range of the `EnvelopeWindow` parameter, in milliseconds.
+const MIN_ENVELOPE_WINDOW_MS: f32 = 1.0;
+const MAX_ENVELOPE_WINDOW_MS: f32 = 50.0;
+/// The most periods `PitchSync` can fit in the window.
+const MAX_PERIODS: usize = 8;
+/// The largest bit depth `BitCrush` can be set to. At this depth, the signal
+/// isn't quantized at all.
+const MAX_BIT_DEPTH: u32 = 24;
+/// The largest factor `SampleRateReduce` can divide the sample rate by.
+const MAX_SAMPLE_RATE_REDUCTION: usize = 32;
+/// The gain applied to the difference heard with `Diagnostic::Null`, so that
+/// even small differences are audible.
+const NULL_MAKEUP_DB: f32 = 24.0;
+/// Scales the median absolute deviation so that it estimates the standard
+/// deviation of normally distributed noise.
+const MAD_TO_STANDARD_DEVIATION: f32 = 1.4826;
+/// The smallest `HampelK` that has an effect. With a smaller K, nearly every
+/// sample is replaced, which is just `Mode::Smooth`, so `HampelK`'s usable
+/// range in the parameter table starts at this K (0.05 normalized).
+const MIN_HAMPEL_K: f32 = 0.5;
+/// The time constants of the fast and slow envelopes used to detect
+/// transients for `TransientPreserve`.
+const TRANSIENT_FAST_MS: f32 = 1.0;
+const TRANSIENT_SLOW_MS: f32 = 50.0;
+/// The range of `WindowAttack` and `WindowRelease`, in milliseconds.
+const MIN_WINDOW_FOLLOW_MS: f32 = 1.0;
+const MAX_WINDOW_FOLLOW_MS: f32 = 1000.0;
 
-struct MedianFilter {
+pub struct MedianFilter {
+    // Runs the left (or mid) and right (or side) channels.
+    effect: EffectPlugin<MedianProcessor, RawParameters>,
+    // The same parameters as `effect` uses.
     params: Arc<RawParameters>,
-    left_filter: Filter<f32>,
-    right_filter: Filter<f32>,
-    last_window_size: usize,
+    last_stereo_mode: StereoMode,
+    offline: OfflineDetector,
+    latency: LatencyReporter,
+    // Copies of each channel's input, encoded to mid/side if needed. Some
+    // hosts process in-place, passing the same buffer for input and output,
+    // so the input is copied here before any output is written.
+    scratch: [Vec<f32>; 2],
+    meters: Arc<Meters>,
+    normalizer: Arc<InputNormalizer>,
+    scope: Arc<Scope>,
+    // Whether the CPU flushes denormals to zero during `process`.
+    flush_denormals: bool,
+    safety: SafetyMute,
+    // Whether the last sub-block was filtered once as mono, leaving the right
+    // channel's processor idle.
+    mono_input: bool,
+    // The parameters are read into `local_params`, a copy of the parameters
+    // which the host never sees. With `VintageFeel`, the copy lags behind
+    // `params`, and is advanced by `lag_samples` each time the parameters are
+    // read.
+    lag: ResponseLag<{ ParameterType::COUNT }>,
+    local_params: RawParameters,
+    lag_samples: usize,
+    // Whether preset loads are applied all at once. While a preset is being
+    // loaded, the last complete set of values read, `held_values`, is used.
+    atomic_preset_loads: bool,
+    held_values: [f32; ParameterType::COUNT],
+    // How long, in milliseconds, parameters take to glide to a newly loaded
+    // preset, the number of preset loads seen when the parameters were last
+    // read, and the glide in progress, if any. Presets loaded before the
+    // parameters are first read, such as when a project is opened, don't
+    // glide.
+    preset_glide_ms: f32,
+    preset_loads: Option<usize>,
+    glide: Option<PresetGlide>,
+    // Whether the window sizes are held at their last value while the user is
+    // in a gesture on them.
+    window_commit_on_release: bool,
+    // The wet/dry LFO at the start of the next sub-block. Each channel is
+    // given a copy, so that the channels stay in sync even when one of them
+    // sits idle.
+    lfo: Lfo,
+    // The gain applied to the output by the gate pattern.
+    gate: Ramp,
+    // The number of samples output since the plugin was loaded or last
+    // resumed, which is how far into the `SoftStart` fade the output is.
+    soft_start_samples: usize,
+    // Sends parameter changes to the host as MIDI CC messages.
+    cc_feedback: CcFeedback<ParameterType, { ParameterType::COUNT }>,
+    send_buffer: SendEventBuffer,
+    // Whether the host has set the sample rate yet, and whether audio was
+    // processed at the default sample rate before it did.
+    sample_rate_known: bool,
+    processed_before_sample_rate: bool,
+    // Whether open gestures are ended and edits flushed when the plugin is
+    // dropped.
+    shutdown_on_drop: bool,
 }
 
 impl Plugin for MedianFilter {
     fn new(host: HostCallback) -> Self {
+        // An editor's gestures go straight to the parameters, which send them
+        // on to the host, so with the `deterministic` feature the parameters
+        // get a host which ignores everything instead.
+        let host = if cfg!(feature = "deterministic") {
+            null_host()
+        } else {
+            host
+        };
+        let effect: EffectPlugin<MedianProcessor, RawParameters> = EffectPlugin::new(host);
+        let params = Arc::clone(effect.params());
+        let latency = params.snapshot().latency();
+        let values = params.all_normalized();
+        let lag = ResponseLag::new(Smoothing::Linear(VINTAGE_LAG_MS), &values, 44100.0);
         MedianFilter {
-            params: Arc::new(RawParameters::default(host)),
-            left_filter: Filter::new(50),
-            right_filter: Filter::new(50),
-            last_window_size: 50,
+            effect,
+            params,
+            last_stereo_mode: StereoMode::LeftRight,
+            offline: OfflineDetector::new(),
+            latency: LatencyReporter::new(latency),
+            scratch: [Vec::new(), Vec::new()],
+            meters: Arc::new(Meters::new()),
+            normalizer: Arc::new(InputNormalizer::new()),
+            scope: Arc::new(Scope::new()),
+            flush_denormals: false,
+            safety: SafetyMute::new(SafetySettings::default(), 44100.0),
+            mono_input: false,
+            lag,
+            local_params: RawParameters::default(HostCallback::default()),
+            lag_samples: 0,
+            atomic_preset_loads: true,
+            held_values: values,
+            preset_glide_ms: 0.0,
+            preset_loads: None,
+            glide: None,
+            window_commit_on_release: false,
+            lfo: Lfo::new(44100.0),
+            gate: Ramp::new(1.0, 44100.0, GATE_RAMP_MS),
+            soft_start_samples: 0,
+            cc_feedback: CcFeedback::new(cc_feedback_interval(44100.0)),
+            send_buffer: SendEventBuffer::new(ParameterType::COUNT),
+            sample_rate_known: false,
+            processed_before_sample_rate: false,
+            shutdown_on_drop: true,
         }
     }
 
     fn init(&mut self) {
-        let params = Parameters::from(self.params.as_ref());
-        self.last_window_size = params.window_size;
+        self.effect.init();
+        self.last_stereo_mode = self.params.snapshot().stereo_mode;
+    }
+
+    fn set_sample_rate(&mut self, rate: f32) {
+        // Much of the plugin divides by the sample rate, so a nonsensical one
+        // is ignored, keeping the last.
+        if !rate.is_finite() || rate <= 0.0 {
+            return;
+        }
+        let rate = if cfg!(feature = "deterministic") {
+            DETERMINISTIC_SAMPLE_RATE
+        } else {
+            rate
+        };
+        self.effect.set_sample_rate(rate);
+        self.safety.set_sample_rate(rate);
+        self.lag.set_sample_rate(rate);
+        self.lfo.set_sample_rate(rate);
+        self.gate.set_duration(rate, GATE_RAMP_MS);
+        self.cc_feedback
+            .set_min_interval(cc_feedback_interval(rate));
+        if self.processed_before_sample_rate && !self.sample_rate_known {
+            // Everything measured so far was measured at the default sample
+            // rate. The channels are already cleared by `effect`.
+            self.offline = OfflineDetector::new();
+            self.safety.reset();
+            self.mono_input = false;
+        }
+        self.sample_rate_known = true;
+        self.reset_if_changed();
+    }
+
+    fn resume(&mut self) {
+        // The host may have stopped calling `process` for a while, so fade in
+        // again, the same as after loading.
+        self.soft_start_samples = 0;
+    }
+
+    fn set_block_size(&mut self, size: i64) {
+        // Reserve space now so that `process` doesn't need to allocate.
+        for scratch in self.scratch.iter_mut() {
+            scratch.reserve((size.max(0) as usize).min(self.effect.sub_block_size()));
+        }
     }
 
     fn get_info(&self) -> Info {
         Info {
-            name: "Median Filter".to_string(),
-            vendor: "a2aaron".to_string(),
-            // Used by hosts to differentiate between plugins.
-            // Don't worry much about this now - just fill in a random number.
-            unique_id: 612413,
-            version: 1,
-            category: Category::Effect,
-            // Subtract one here due to "error" type
-            parameters: ParameterType::COUNT as i32,
-            // Two audio inputs
-            inputs: 2,
-            // Two channel audio!
-            outputs: 2,
-            // For now, fill in the rest of our fields with `Default` info.
-            ..Default::default()
+            // Tell the host how late the output is, so that it can delay other
+            // tracks to match.
+            initial_delay: self.latency.reported() as i32,
+            ..self.effect.get_info()
         }
     }
 
     fn can_do(&self, can_do: CanDo) -> Supported {
         match can_do {
-            CanDo::Bypass => Supported::Yes,
+            CanDo::Bypass | CanDo::SendEvents | CanDo::SendMidiEvent => Supported::Yes,
             _ => Supported::No,
         }
     }
 
     // Output audio given the current state of the VST
     fn process(&mut self, buffer: &mut AudioBuffer<f32>) {
-        self.reset_if_changed();
-        let params = Parameters::from(self.params.as_ref());
-        let wet_dry = params.wet_dry;
-        let num_samples = buffer.samples();
+        let _flush = if self.flush_denormals {
+            Some(FlushDenormals::new())
+        } else {
+            None
+        };
+        if catch_faults(buffer, |buffer| self.process_unguarded(buffer)) {
+            self.effect.recover_from_fault();
+        }
+        self.send_cc_feedback(buffer.samples());
+    }
+
+    // The raw parameters exposed to the host
+    fn get_parameter_object(&mut self) -> Arc<dyn PluginParameters> {
+        self.effect.get_parameter_object()
+    }
+}
+
+impl Drop for MedianFilter {
+    fn drop(&mut self) {
+        // A plugin made outside of a host, such as in a unit test, has no host
+        // to notify.
+        if self.shutdown_on_drop && self.params.host.raw_callback().is_some() {
+            self.shutdown();
+        }
+    }
+}
+
+impl MedianFilter {
+    /// The body of `process`, which may panic if there is a bug.
+    fn process_unguarded(&mut self, buffer: &mut AudioBuffer<f32>) {
+        let playing = self.read_transport();
+
+        // Use a single snapshot for each sub-block, so that the channels and
+        // the mid/side encoding can't disagree.
+        let params = self.reset_if_changed();
+        if !cfg!(feature = "deterministic") {
+            self.latency.update(
+                &self.params.host,
+                params.latency(),
+                params.latency_updates,
+                playing,
+            );
+        }
+
+        // Some hosts call `process` with empty buffers, just to keep time or
+        // to pass along parameter changes. There's nothing to process, and an
+        // empty buffer may not describe the real channel layout.
+        if buffer.samples() == 0 {
+            self.flush_edits();
+            return;
+        }
+
+        if !self
+            .effect
+            .set_channel_layout(buffer.input_count(), buffer.output_count())
+        {
+            silence(buffer);
+            self.flush_edits();
+            return;
+        }
 
+        let num_channels = self.effect.channels_mut().len();
         let (inputs, mut outputs) = buffer.split();
-        let left_input = &inputs[0];
-        let left_output = &mut outputs[0];
+        let mut input_slices: [&[f32]; MAX_CHANNELS] = [&[]; MAX_CHANNELS];
+        let mut output_slices: [&mut [f32]; MAX_CHANNELS] = Default::default();
+        for i in 0..num_channels {
+            input_slices[i] = inputs.get(i);
+            output_slices[i] = outputs.get_mut(i);
+        }
+        self.process_slices(
+            params,
+            &input_slices[..num_channels],
+            &mut output_slices[..num_channels],
+        );
+
+        self.flush_edits();
+    }
 
-        for i in 0..num_samples {
-            self.left_filter.consume(left_input[i]);
-            let out = if self.left_filter.is_empty() != 0 {
-                self.left_filter.median()
+    /// Ask the host for its clock and process level, updating the LFO and
+    /// whether the plugin is rendering offline. Returns true if the host's
+    /// transport is playing. Without the host's clock, the plugin always
+    /// assumes it is running in realtime, so the output doesn't depend on how
+    /// fast it renders.
+    fn read_transport(&mut self) -> bool {
+        // A plugin made outside of a host has no host to ask.
+        let (time_info, host_offline) =
+            if cfg!(feature = "deterministic") || self.params.host.raw_callback().is_none() {
+                (None, None)
             } else {
-                0.0
+                let time_info = self.params.host.get_time_info(
+                    (TimeInfoFlags::NANOSECONDS_VALID
+                        | TimeInfoFlags::PPQ_POS_VALID
+                        | TimeInfoFlags::TEMPO_VALID)
+                        .bits(),
+                );
+                (time_info, host_is_offline(&self.params.host))
             };
-            left_output[i] = left_input[i] * (1.0 - wet_dry) + out * wet_dry;
+        self.offline
+            .update(host_offline, time_info, self.params.sample_rate.get());
+        self.lfo.sync(time_info);
+        self.params
+            .offline
+            .store(self.offline.is_offline(), Ordering::Relaxed);
+        time_info.is_some_and(|time_info| {
+            TimeInfoFlags::from_bits_truncate(time_info.flags)
+                .contains(TimeInfoFlags::TRANSPORT_PLAYING)
+        })
+    }
+
+    /// Send the host MIDI CC messages for any mapped parameters which changed,
+    /// `samples` after the last call. With the `deterministic` feature,
+    /// nothing is sent.
+    fn send_cc_feedback(&mut self, samples: usize) {
+        if cfg!(feature = "deterministic") || self.params.host.raw_callback().is_none() {
+            return;
         }
+        let events = self
+            .cc_feedback
+            .update(&self.params.all_normalized(), samples);
+        if !events.is_empty() {
+            let mut host = self.params.host;
+            self.send_buffer
+                .send_events(events.iter().copied(), &mut host);
+        }
+    }
 
-        let right_input = &inputs[1];
-        let right_output = &mut outputs[1];
+    /// Notify the host of changed parameters, except with the `deterministic`
+    /// feature, which never calls the host.
+    fn flush_edits(&self) {
+        if !cfg!(feature = "deterministic") {
+            self.params.flush_edits();
+        }
+    }
 
-        for i in 0..num_samples {
-            self.right_filter.consume(right_input[i]);
-            let out = if self.right_filter.is_empty() != 0 {
-                self.right_filter.median()
-            } else {
-                0.0
+    /// Process one channel of `inputs` into each of `outputs`, splitting them
+    /// into sub-blocks so that large buffers are processed the same way as a
+    /// series of small ones. `params` are used for the first sub-block, and
+    /// the parameters are read again for each one after that.
+    fn process_slices(
+        &mut self,
+        params: Parameters,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+    ) {
+        let num_samples = inputs.iter().map(|input| input.len()).min().unwrap_or(0);
+        if !self.sample_rate_known && num_samples > 0 {
+            self.processed_before_sample_rate = true;
+        }
+        let sub_block_size = self.effect.sub_block_size();
+        let mut params = params;
+        for start in (0..num_samples).step_by(sub_block_size) {
+            if start > 0 {
+                params = self.reset_if_changed();
+            }
+            let end = (start + sub_block_size).min(num_samples);
+            self.process_sub_block(&params, inputs, outputs, start..end);
+        }
+    }
+
+    /// Process the samples of `inputs` and `outputs` in `range`.
+    fn process_sub_block(
+        &mut self,
+        params: &Parameters,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        range: Range<usize>,
+    ) {
+        let num_samples = range.len();
+        self.lag_samples += num_samples;
+        let sample_rate = self.params.sample_rate.get();
+        let mut input_levels = [BlockLevels::default(); MAX_CHANNELS];
+        let mut input_blocks: [&[f32]; MAX_CHANNELS] = [&[]; MAX_CHANNELS];
+        for ((levels, block), input) in input_levels
+            .iter_mut()
+            .zip(input_blocks.iter_mut())
+            .zip(inputs.iter())
+        {
+            *block = &input[range.clone()];
+            *levels = BlockLevels::new(block);
+        }
+        self.normalizer.capture(&input_blocks[..inputs.len()]);
+        self.scope.write(&input_blocks[..inputs.len()]);
+
+        let [left_scratch, right_scratch] = &mut self.scratch;
+        left_scratch.clear();
+        right_scratch.clear();
+        let channels = self.effect.channels_mut();
+        for channel in channels.iter_mut() {
+            channel.lfo = self.lfo;
+        }
+        let mut clock = self.lfo;
+        self.lfo.skip(num_samples);
+        let channels_len = channels.len();
+        let mono_input = channels.len() == 2
+            && params.stereo_mode == StereoMode::LeftRight
+            && match params.mono_input {
+                MonoInput::Off => false,
+                MonoInput::Auto => {
+                    params.channels_match() && inputs[0][range.clone()] == inputs[1][range.clone()]
+                }
+                MonoInput::Forced => true,
             };
-            right_output[i] = right_input[i] * (1.0 - wet_dry) + out * wet_dry;
+        if self.mono_input && !mono_input {
+            // The right channel's history is stale after sitting idle.
+            channels[1].reset();
         }
+        self.mono_input = mono_input;
+
+        if channels.len() == 1 {
+            // Mid/side and recorrelation need two channels, so a mono input is
+            // filtered as it is.
+            left_scratch.extend_from_slice(&inputs[0][range.clone()]);
+            channels[0].process_block(left_scratch, &mut outputs[0][range.clone()]);
+        } else if mono_input {
+            // Sum the channels, which leaves identical channels unchanged, and
+            // filter them once.
+            for (left, right) in inputs[0][range.clone()]
+                .iter()
+                .zip(inputs[1][range.clone()].iter())
+            {
+                left_scratch.push((left + right) / 2.0);
+            }
+            let (left_output, right_output) = outputs.split_at_mut(1);
+            let left_output = &mut left_output[0][range.clone()];
+            channels[0].process_block(left_scratch, left_output);
+            right_output[0][range.clone()].copy_from_slice(left_output);
+        } else {
+            match params.stereo_mode {
+                StereoMode::LeftRight => {
+                    left_scratch.extend_from_slice(&inputs[0][range.clone()]);
+                    right_scratch.extend_from_slice(&inputs[1][range.clone()]);
+                }
+                StereoMode::MidSide => {
+                    for (left, right) in inputs[0][range.clone()]
+                        .iter()
+                        .zip(inputs[1][range.clone()].iter())
+                    {
+                        left_scratch.push((left + right) / 2.0);
+                        right_scratch.push((left - right) / 2.0);
+                    }
+                }
+            }
+
+            let (left_output, right_output) = outputs.split_at_mut(1);
+            let left_output = &mut left_output[0][range.clone()];
+            let right_output = &mut right_output[0][range.clone()];
+            if params.stereo_mode == StereoMode::LeftRight
+                && (params.recorrelate > 0.0 || params.crossfeed > 0.0)
+            {
+                let (left, right) = channels.split_at_mut(1);
+                let (left, right) = (&mut left[0], &mut right[0]);
+                left.begin_block(left_scratch);
+                right.begin_block(right_scratch);
+                // At full crossfeed, both channels filter the mono sum.
+                let crossfeed = params.crossfeed / 2.0;
+                for i in 0..num_samples {
+                    let (left_input, right_input) = (left_scratch[i], right_scratch[i]);
+                    let (left_x, right_x) = (
+                        left.apply_input_gain(left_input),
+                        right.apply_input_gain(right_input),
+                    );
+                    let left_wet = left.filter(left_x + (right_x - left_x) * crossfeed);
+                    let right_wet = right.filter(right_x + (left_x - right_x) * crossfeed);
+                    // Pull each channel's wet signal towards the mono sum of both.
+                    let mono_wet = (left_wet + right_wet) / 2.0;
+                    let left_wet = left_wet + (mono_wet - left_wet) * params.recorrelate;
+                    let right_wet = right_wet + (mono_wet - right_wet) * params.recorrelate;
+                    left_output[i] = left.mix(left_input, left_x, left_wet);
+                    right_output[i] = right.mix(right_input, right_x, right_wet);
+                }
+                left.end_block();
+                right.end_block();
+            } else {
+                channels[0].process_block(left_scratch, left_output);
+                channels[1].process_block(right_scratch, right_output);
+            }
+
+            if params.stereo_mode == StereoMode::MidSide {
+                for (left, right) in left_output.iter_mut().zip(right_output.iter_mut()) {
+                    let (mid, side) = (*left, *right);
+                    *left = mid + side;
+                    *right = mid - side;
+                }
+            }
+        }
+
+        if params.gate {
+            let steps = params.gate_steps;
+            let step_beats = params.gate_rate.beats();
+            for i in range.clone() {
+                let step = (clock.position() / step_beats).rem_euclid(GATE_STEPS as f64) as usize;
+                clock.skip(1);
+                self.gate.set_target(steps[step.min(GATE_STEPS - 1)]);
+                let gain = self.gate.next();
+                for output in outputs.iter_mut() {
+                    output[i] *= gain;
+                }
+            }
+        } else {
+            self.gate = Ramp::new(1.0, sample_rate, GATE_RAMP_MS);
+        }
+
+        for i in range.clone() {
+            let peak = outputs
+                .iter()
+                .map(|output| output[i].abs())
+                .fold(0.0, f32::max);
+            let gain = self.safety.process(peak);
+            for output in outputs.iter_mut() {
+                output[i] *= gain;
+            }
+        }
+
+        let fade_samples = (SOFT_START_MS / 1000.0 * sample_rate) as usize;
+        if params.soft_start && self.soft_start_samples < fade_samples {
+            for (i, n) in range.clone().zip(self.soft_start_samples..fade_samples) {
+                let gain = n as f32 / fade_samples as f32;
+                for output in outputs.iter_mut() {
+                    output[i] *= gain;
+                }
+            }
+        }
+        self.soft_start_samples = self.soft_start_samples.saturating_add(num_samples);
+
+        let mut output_levels = [BlockLevels::default(); MAX_CHANNELS];
+        let mut output_blocks: [&[f32]; MAX_CHANNELS] = [&[]; MAX_CHANNELS];
+        for ((levels, block), output) in output_levels
+            .iter_mut()
+            .zip(output_blocks.iter_mut())
+            .zip(outputs.iter())
+        {
+            *block = &output[range.clone()];
+            *levels = BlockLevels::new(block);
+        }
+        let mono = MonoFold::new(&output_blocks[..outputs.len()]);
+        let active_channels = if mono_input { 1 } else { channels_len };
+        let envelope = self.effect.channels_mut()[..active_channels]
+            .iter()
+            .map(|channel| channel.envelope_level())
+            .fold(0.0, f32::max);
+        self.meters.update(
+            &input_levels[..inputs.len()],
+            &output_levels[..outputs.len()],
+            mono,
+            envelope,
+            num_samples,
+            sample_rate,
+        );
     }
 
-    // The raw parameters exposed to the host
-    fn get_parameter_object(&mut self) -> Arc<dyn PluginParameters> {
-        Arc::clone(&self.params) as Arc<dyn PluginParameters>
+    /// The most samples processed between parameter updates. See
+    /// `EffectPlugin::set_sub_block_size`.
+    pub fn set_sub_block_size(&mut self, size: usize) {
+        self.effect.set_sub_block_size(size);
     }
-}
 
-impl MedianFilter {
-    fn reset_if_changed(&mut self) {
-        let params = Parameters::from(self.params.as_ref());
-        if params.window_size != self.last_window_size {
-            self.left_filter = Filter::new(params.window_size);
-            self.right_filter = Filter::new(params.window_size);
-            self.last_window_size = params.window_size;
+    /// The plugin's level meters, for display in a GUI.
+    pub fn meters(&self) -> Arc<Meters> {
+        Arc::clone(&self.meters)
+    }
+
+    /// Returns true if the host processed audio before setting the sample
+    /// rate, which means that audio was processed at the default rate of
+    /// 44.1 kHz. Once the host sets the rate, all state from that audio is
+    /// cleared, so this is only a warning that the host misbehaved.
+    pub fn processed_before_sample_rate(&self) -> bool {
+        self.processed_before_sample_rate
+    }
+
+    /// Start measuring the peak of the input, for `finish_input_normalization`.
+    /// The capture lasts `NORMALIZE_CAPTURE_MS`.
+    pub fn start_input_normalization(&self) {
+        let samples = NORMALIZE_CAPTURE_MS / 1000.0 * self.params.sample_rate.get();
+        self.normalizer.start(samples as usize);
+    }
+
+    /// Once the capture started by `start_input_normalization` has finished,
+    /// set `InputGain` so that the loudest sample captured reaches
+    /// `NORMALIZE_TARGET_DB` going into the filter, and return the new gain
+    /// in decibels. This gives the filter the same level to work with
+    /// whatever the level of the source. Returns None, changing nothing, if
+    /// the capture hasn't finished or was silent. The gain is limited to the
+    /// range of `InputGain`.
+    pub fn finish_input_normalization(&self) -> Option<f32> {
+        let gain_db = self.normalizer.gain_db(NORMALIZE_TARGET_DB)?;
+        let normalized = INPUT_GAIN_RANGE.to_normalized(gain_db);
+        self.params.set_forced(normalized, ParameterType::InputGain);
+        Some(INPUT_GAIN_RANGE.to_db(normalized))
+    }
+
+    /// The input normalizer, so that a GUI can show the capture's progress.
+    pub fn input_normalizer(&self) -> Arc<InputNormalizer> {
+        Arc::clone(&self.normalizer)
+    }
+
+    /// The most recent input samples of each channel, for a waveform or
+    /// spectrum display in a GUI.
+    pub fn scope(&self) -> Arc<Scope> {
+        Arc::clone(&self.scope)
+    }
+
+    /// The window size the filter is currently using, in samples. In mid/side
+    /// mode, this is the mid window.
+    pub fn effective_window_samples(&self) -> usize {
+        self.params.snapshot().effective_window_samples()
+    }
+
+    /// The window size the filter is currently using, in milliseconds.
+    pub fn effective_window_ms(&self) -> f32 {
+        self.params
+            .snapshot()
+            .effective_window_ms(self.params.sample_rate.get())
+    }
+
+    /// The delay, in samples, between the plugin's input and output with the
+    /// current parameters. This is the delay reported to the host, though
+    /// the host may not have been told about a recent change yet, depending
+    /// on `Latency Updates`.
+    pub fn group_delay_samples(&self) -> f32 {
+        self.params.snapshot().group_delay_samples()
+    }
+
+    /// Bypass the filter, passing the input through unchanged. The output is
+    /// crossfaded to avoid clicks when toggling bypass.
+    pub fn set_bypass(&mut self, bypass: bool) {
+        let value = if bypass { 1.0 } else { 0.0 };
+        self.params.set(value, ParameterType::Bypass);
+    }
+
+    /// Flush denormal floats to zero while processing, using the CPU's flags.
+    /// This avoids the CPU slowing down while a filter's tail decays towards
+    /// silence. The flags are only set on the thread calling `process`, and
+    /// are restored before `process` returns. Does nothing on architectures
+    /// other than x86, x86_64 and aarch64.
+    pub fn set_denormal_handling(&mut self, enabled: bool) {
+        self.flush_denormals = enabled;
+    }
+
+    /// Apply preset loads all at once. While the host is loading a preset,
+    /// the parameters are written one at a time, so a block processed in the
+    /// middle could see a mix of the old and new preset. With this on, which
+    /// is the default, such a block keeps using the parameters from before
+    /// the load instead.
+    pub fn set_atomic_preset_loads(&mut self, enabled: bool) {
+        self.atomic_preset_loads = enabled;
+    }
+
+    /// Glide every parameter from its old value to its value in a newly
+    /// loaded preset over `glide_ms` milliseconds, instead of jumping, which
+    /// can click. Stepped parameters, such as modes and switches, still jump
+    /// straight to their new values. A `glide_ms` of zero, the default, turns
+    /// the glide off.
+    pub fn set_preset_glide(&mut self, glide_ms: f32) {
+        self.preset_glide_ms = glide_ms.max(0.0);
+    }
+
+    /// Only apply changes to a window size once the user releases it. Each
+    /// new window size reallocates and refills the filter, and dragging a
+    /// knob passes through dozens of them. With this on, the filter keeps its
+    /// window size between `RawParameters::begin_gesture` and `end_gesture`,
+    /// while the display follows the knob.
+    pub fn set_window_commit_on_release(&mut self, enabled: bool) {
+        self.window_commit_on_release = enabled;
+    }
+
+    /// Call `shutdown` when the plugin is dropped. On by default, so that a
+    /// plugin removed in the middle of a knob drag doesn't leave the host
+    /// waiting for an end_edit.
+    pub fn set_shutdown_on_drop(&mut self, enabled: bool) {
+        self.shutdown_on_drop = enabled;
+    }
+
+    /// End any gestures still open, and notify the host of any unflushed
+    /// parameter changes. With the `deterministic` feature, which never calls
+    /// the host, this does nothing.
+    pub fn shutdown(&mut self) {
+        if !cfg!(feature = "deterministic") {
+            self.params.shutdown();
         }
     }
-}
 
-struct Parameters {
-    window_size: usize,
-    wet_dry: f32,
-}
+    /// The safety mute, which silences the output if it stays dangerously
+    /// loud. Its `reason` says why it last muted the output.
+    pub fn safety_mute(&self) -> &SafetyMute {
+        &self.safety
+    }
+
+    pub fn set_safety_mute(&mut self, settings: SafetySettings) {
+        self.safety.set_settings(settings);
+    }
+
+    /// Send changes to `parameter` out as MIDI CC number `cc`, so that
+    /// controllers with motorized faders follow the plugin. None stops
+    /// sending it. Changes are sent at most every `CC_FEEDBACK_INTERVAL_MS`.
+    pub fn set_cc_feedback(&mut self, parameter: ParameterType, cc: Option<u8>) {
+        self.cc_feedback.set_mapping(parameter, cc);
+    }
+
+    /// Set the MIDI channel, from 0 to 15, that CC feedback is sent on.
+    pub fn set_cc_feedback_channel(&mut self, channel: u8) {
+        self.cc_feedback.set_channel(channel);
+    }
+
+    /// Count the samples in a channel's window into `counts.len()` equally
+    /// sized buckets spanning `min` to `max`, showing the distribution the
+    /// median is drawn from. See `SortedWindow::histogram`.
+    #[cfg(feature = "analysis")]
+    pub fn window_histogram(&mut self, channel: usize, min: f32, max: f32, counts: &mut [usize]) {
+        if let Some(channel) = self.effect.channels_mut().get(channel) {
+            channel.filter.histogram(min, max, counts);
+        }
+    }
+
+    /// Unmute the output after the safety mute latched.
+    pub fn reset_safety_mute(&mut self) {
+        self.safety.reset();
+    }
+
+    /// Process interleaved audio with `channels` channels in place, for use
+    /// outside of a VST host, such as offline rendering or tests. Unsupported
+    /// channel layouts, and any partial frame at the end, are silenced. This
+    /// allocates, so it shouldn't be called from a realtime audio thread.
+    pub fn process_interleaved(&mut self, samples: &mut [f32], channels: usize) {
+        if !self.effect.set_channel_layout(channels, channels) {
+            samples.fill(0.0);
+            return;
+        }
+        let inputs = deinterleave(samples, channels);
+        let mut outputs = inputs.clone();
+        let input_slices: Vec<&[f32]> = inputs.iter().map(Vec::as_slice).collect();
+        let mut output_slices: Vec<&mut [f32]> =
+            outputs.iter_mut().map(Vec::as_mut_slice).collect();
+        let params = self.reset_if_changed();
+        self.process_slices(params, &input_slices, &mut output_slices);
+        let processed = interleave(&outputs);
+        samples[..processed.len()].copy_from_slice(&processed);
+        samples[processed.len()..].fill(0.0);
+        self.flush_edits();
+    }
 
-impl From<&RawParameters> for Parameters {
-    fn from(params: &RawParameters) -> Self {
+    /// Read the current parameters, clamped to their usable ranges. With
+    /// `VintageFeel`, every parameter lags behind the host's value instead,
+    /// taking `VINTAGE_LAG_MS` to reach it. See `set_atomic_preset_loads` for
+    /// what happens while a preset is being loaded.
+    fn read_parameters(&mut self) -> Parameters {
+        let mut values = if self.atomic_preset_loads {
+            self.params
+                .consistent_normalized()
+                .unwrap_or(self.held_values)
+        } else {
+            self.params.all_normalized()
+        };
+        if self.window_commit_on_release {
+            for &parameter in WINDOW_PARAMETERS.iter() {
+                if self.params.in_gesture(parameter) {
+                    let index = i32::from(parameter) as usize;
+                    values[index] = self.held_values[index];
+                }
+            }
+        }
+        self.held_values = values;
+        RawParameters::clamp_to_usable(&mut values);
+        let samples = std::mem::take(&mut self.lag_samples);
+        let values = self.glide_presets(values, samples);
+        let vintage_feel = values[i32::from(ParameterType::VintageFeel) as usize] > 0.5;
+        if vintage_feel {
+            self.local_params
+                .set_all_normalized(&self.lag.process(&values, samples));
+        } else {
+            self.lag.reset(&values);
+            self.local_params.set_all_normalized(&values);
+        }
+        self.local_params
+            .sample_rate
+            .set(self.params.sample_rate.get());
+        self.local_params.offline.store(
+            self.params.offline.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        // The lagged `VintageFeel` would take a while to turn on.
         Parameters {
-            window_size: ((params.window_size.get() * 100.0) as usize).max(1),
-            wet_dry: params.wet_dry.get(),
+            vintage_feel,
+            ..self.local_params.snapshot()
+        }
+    }
+
+    /// Ramp `values` from the values in use before the last preset load, if
+    /// it was loaded less than the preset glide time ago. `samples` is how
+    /// many samples were processed since the last call.
+    fn glide_presets(
+        &mut self,
+        values: [f32; ParameterType::COUNT],
+        samples: usize,
+    ) -> [f32; ParameterType::COUNT] {
+        let preset_loads = Some(self.params.preset_loads());
+        let mut samples = samples;
+        if self.preset_loads.is_some() && preset_loads != self.preset_loads {
+            self.glide = if self.preset_glide_ms > 0.0 {
+                Some(PresetGlide::new(
+                    self.preset_glide_ms,
+                    &self.local_params.all_normalized(),
+                    self.params.sample_rate.get(),
+                ))
+            } else {
+                None
+            };
+            // The glide starts from the values in use until now.
+            samples = 0;
+        }
+        self.preset_loads = preset_loads;
+        match &mut self.glide {
+            Some(glide) => {
+                let glided = glide.process(&values, samples);
+                if glide.is_done() {
+                    self.glide = None;
+                }
+                glided
+            }
+            None => values,
+        }
+    }
+
+    /// Bring the channels up to date with the current parameters, and return
+    /// the parameters they were given. The host may change parameters at any
+    /// time, including between `init` and the first call to `process` (such
+    /// as when loading a preset), so this is called at the start of every
+    /// block, before any audio is processed.
+    fn reset_if_changed(&mut self) -> Parameters {
+        let params = self.read_parameters();
+        self.effect.set_parameters(&params);
+        // The history of each channel is meaningless after switching between
+        // left/right and mid/side, so start over.
+        if params.stereo_mode != self.last_stereo_mode {
+            for channel in self.effect.channels_mut() {
+                channel.reset();
+            }
+            self.last_stereo_mode = params.stereo_mode;
         }
+        params
     }
 }
 
-macro_rules! table {
-    ($macro:ident) => {
-        $macro! {
-        //  RawParameter identifier, ParameterType identifier
-            RawParameters,           ParameterType;
-        //  variant      field_name    name            idx  default  strings
-            WetDry,      wet_dry,      "Wet/Dry",      0,   0.5,     |x: f32| make_strings(x * 100.0, "% Wet");
-            WindowSize,  window_size,  "Window Size",  1,   0.5,     |x: usize| (format!("{}", x), " Samples".to_string());
+/// Ramps every continuous parameter from its value before a preset load to
+/// its value in the loaded preset, taking a fixed amount of time.
+struct PresetGlide {
+    lag: ResponseLag<{ ParameterType::COUNT }>,
+    // How many more samples the ramp lasts.
+    remaining: usize,
+}
+
+impl PresetGlide {
+    /// Start a glide of `glide_ms` milliseconds from `values`.
+    fn new(glide_ms: f32, values: &[f32; ParameterType::COUNT], sample_rate: f32) -> PresetGlide {
+        PresetGlide {
+            lag: ResponseLag::new(Smoothing::Linear(glide_ms), values, sample_rate),
+            remaining: (glide_ms / 1000.0 * sample_rate).ceil() as usize,
         }
-    };
+    }
+
+    /// Advance by `samples` samples towards `targets`, returning the glided
+    /// values. Stepped parameters jump straight to their targets, since the
+    /// values in between would pass through other modes.
+    fn process(
+        &mut self,
+        targets: &[f32; ParameterType::COUNT],
+        samples: usize,
+    ) -> [f32; ParameterType::COUNT] {
+        let mut values = self.lag.process(targets, samples);
+        for &parameter in ParameterType::all() {
+            if RawParameters::get_mapping(parameter).steps().is_some() {
+                let index = i32::from(parameter) as usize;
+                values[index] = targets[index];
+            }
+        }
+        self.remaining = self.remaining.saturating_sub(samples);
+        values
+    }
+
+    /// Whether the glide has reached the loaded preset.
+    fn is_done(&self) -> bool {
+        self.remaining == 0
+    }
 }
 
-impl ParameterType {
-    pub const COUNT: usize = 2;
+/// Ask the host whether it is rendering offline, using its current process
+/// level. Returns None if the host doesn't say.
+fn host_is_offline(host: &HostCallback) -> Option<bool> {
+    // `audioMasterGetCurrentProcessLevel` and the levels it returns, from the
+    // VST 2.4 SDK. vst-rs doesn't wrap this opcode.
+    const GET_CURRENT_PROCESS_LEVEL: i32 = 23;
+    const PROCESS_LEVEL_UNKNOWN: isize = 0;
+    const PROCESS_LEVEL_OFFLINE: isize = 4;
+
+    let callback = host.raw_callback()?;
+    let level = callback(
+        host.raw_effect(),
+        GET_CURRENT_PROCESS_LEVEL,
+        0,
+        0,
+        std::ptr::null_mut(),
+        0.0,
+    );
+    match level {
+        PROCESS_LEVEL_UNKNOWN => None,
+        level => Some(level == PROCESS_LEVEL_OFFLINE),
+    }
 }
 
-impl_all! {RawParameters, ParameterType, table}
+/// Tracks whether the host is rendering offline (such as during a bounce).
+/// The host's process level is used when the host reports one. Otherwise, this
+/// is guessed by comparing how fast the host's sample position advances
+/// against the host's system clock. If the host doesn't report either of
+/// these, the host is assumed to be rendering in realtime.
+struct OfflineDetector {
+    // The sample position and system time (in nanoseconds) of the last block.
+    last_time: Option<(f64, f64)>,
+    offline: bool,
+}
+
+impl OfflineDetector {
+    /// How many times faster than realtime the host must render before it is
+    /// considered to be rendering offline.
+    const OFFLINE_SPEED: f64 = 2.0;
+
+    fn new() -> OfflineDetector {
+        OfflineDetector {
+            last_time: None,
+            offline: false,
+        }
+    }
+
+    /// Update the detector for the next block. `host_offline` is what the
+    /// host reported from its process level, if anything.
+    fn update(
+        &mut self,
+        host_offline: Option<bool>,
+        time_info: Option<TimeInfo>,
+        sample_rate: f32,
+    ) {
+        if let Some(offline) = host_offline {
+            self.last_time = None;
+            self.offline = offline;
+            return;
+        }
+        let time_info = match time_info {
+            Some(time_info)
+                if TimeInfoFlags::from_bits_truncate(time_info.flags)
+                    .contains(TimeInfoFlags::NANOSECONDS_VALID) =>
+            {
+                time_info
+            }
+            _ => {
+                self.last_time = None;
+                self.offline = false;
+                return;
+            }
+        };
+
+        let now = (time_info.sample_pos, time_info.nanoseconds);
+        if let Some((last_sample_pos, last_nanoseconds)) = self.last_time {
+            let elapsed_samples = now.0 - last_sample_pos;
+            let elapsed_nanoseconds = now.1 - last_nanoseconds;
+            // Skip blocks where the transport jumped backwards or the clock
+            // didn't advance, since the speed can't be measured.
+            if elapsed_samples > 0.0 && elapsed_nanoseconds > 0.0 {
+                let rendered_seconds = elapsed_samples / sample_rate as f64;
+                let elapsed_seconds = elapsed_nanoseconds / 1.0e9;
+                self.offline = rendered_seconds > elapsed_seconds * Self::OFFLINE_SPEED;
+            }
+        }
+        self.last_time = Some(now);
+    }
+
+    fn is_offline(&self) -> bool {
+        self.offline
+    }
+}
 
 // Export symbols for main
 vst::plugin_main!(MedianFilter);
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    use common::{mapping::Mapping, plugin::EffectProcessor};
+
+    use super::*;
+    use crate::{
+        limiter::LOOKAHEAD_SAMPLES,
+        meters::MeterMode,
+        parameters::{window_strings, LimitMode},
+        scope::SCOPE_SAMPLES,
+    };
+
+    /// A host which reports the process level `LEVEL`, and nothing else.
+    extern "C" fn host_at_level<const LEVEL: isize>(
+        _effect: *mut vst::api::AEffect,
+        opcode: i32,
+        _index: i32,
+        _value: isize,
+        _ptr: *mut std::os::raw::c_void,
+        _opt: f32,
+    ) -> isize {
+        if opcode == 23 {
+            LEVEL
+        } else {
+            0
+        }
+    }
+
+    /// The realtime cap limits the window unless the host reports that it is
+    /// rendering offline. A host which doesn't report its process level, and
+    /// doesn't report its clock either, is assumed to be in realtime.
+    #[test]
+    fn offline_cap() {
+        let hosts: [(vst::api::HostCallbackProc, bool); 4] = [
+            (host_at_level::<0>, false),
+            (host_at_level::<1>, false),
+            (host_at_level::<2>, false),
+            (host_at_level::<4>, true),
+        ];
+        for &(host, offline) in &hosts {
+            let mut filter = MedianFilter::new(HostCallback::wrap(host, std::ptr::null_mut()));
+            // A cap of 40 samples, below the largest window of 100 samples.
+            filter
+                .params
+                .set(40.0 / MAX_WINDOW_SIZE as f32, ParameterType::RealtimeCap);
+            filter.params.set(1.0, ParameterType::WindowSize);
+            filter.read_transport();
+
+            // With the `deterministic` feature, the host is never asked.
+            let offline = offline && !cfg!(feature = "deterministic");
+            let expected = if offline { MAX_WINDOW_SAMPLES } else { 40 };
+            assert_eq!(filter.params.offline.load(Ordering::Relaxed), offline);
+            assert_eq!(
+                Parameters::from(&*filter.params).window_size.samples,
+                expected
+            );
+        }
+    }
+
+    /// Windows set in milliseconds follow the sample rate, both in the
+    /// parameters and in the channels.
+    #[test]
+    fn sample_rate_change_resizes_ms_windows() {
+        let mut filter = MedianFilter::new(null_host());
+        filter.params.set(1.0, ParameterType::WindowUnit);
+        // 10 ms.
+        filter.params.set(0.5, ParameterType::WindowSize);
+        for &rate in [44100.0, 96000.0].iter() {
+            filter.set_sample_rate(rate);
+            let rate = if cfg!(feature = "deterministic") {
+                DETERMINISTIC_SAMPLE_RATE
+            } else {
+                rate
+            };
+            let expected = 0.010 * rate;
+            let window = Parameters::from(&*filter.params).window_size.samples;
+            assert!(
+                (window as f32 - expected).abs() <= 1.0,
+                "{} samples at {} Hz",
+                window,
+                rate
+            );
+            for channel in filter.effect.channels_mut() {
+                assert_eq!(channel.window_size, window);
+            }
+        }
+    }
+
+    /// Some hosts process in place, passing the same buffers as the input and
+    /// the output. That must give the same output as separate buffers, even
+    /// though the dry signal is delayed.
+    #[test]
+    fn in_place_buffers() {
+        const SAMPLES: usize = 512;
+        let input: Vec<f32> = (0..SAMPLES).map(|i| (i as f32 * 0.37).sin()).collect();
+        let process = |in_place: bool| {
+            let mut filter = MedianFilter::new(null_host());
+            filter.params.set(1.0, ParameterType::CompensateDry);
+            filter.params.set(0.5, ParameterType::WetDryLeft);
+            let mut inputs = [input.clone(), input.clone()];
+            let mut outputs = [vec![0.0; SAMPLES], vec![0.0; SAMPLES]];
+            let mut output_ptrs: Vec<*mut f32> = if in_place {
+                inputs.iter_mut().map(|x| x.as_mut_ptr()).collect()
+            } else {
+                outputs.iter_mut().map(|x| x.as_mut_ptr()).collect()
+            };
+            let input_ptrs: Vec<*const f32> = if in_place {
+                output_ptrs.iter().map(|&x| x as *const f32).collect()
+            } else {
+                inputs.iter().map(|x| x.as_ptr()).collect()
+            };
+            let mut buffer = unsafe {
+                AudioBuffer::from_raw(2, 2, input_ptrs.as_ptr(), output_ptrs.as_mut_ptr(), SAMPLES)
+            };
+            filter.process(&mut buffer);
+            if in_place {
+                inputs
+            } else {
+                outputs
+            }
+        };
+        let output = process(true);
+        assert_ne!(output[0], input);
+        assert_eq!(output, process(false));
+    }
+
+    /// The window size shown to the user is the window the channels actually
+    /// use, whatever the unit, ForceOdd, and the realtime cap do to it.
+    #[test]
+    fn window_display_matches_filter() {
+        let mut filter = MedianFilter::new(null_host());
+        filter.set_sample_rate(44100.0);
+        filter
+            .params
+            .set(40.0 / MAX_WINDOW_SIZE as f32, ParameterType::RealtimeCap);
+        let index = i32::from(ParameterType::WindowSize);
+        for &unit in [0.0, 0.5, 1.0].iter() {
+            for &force_odd in [0.0, 1.0].iter() {
+                for &window_size in [0.0, 0.1, 0.25, 0.5, 1.0].iter() {
+                    filter.params.set(unit, ParameterType::WindowUnit);
+                    filter.params.set(force_odd, ParameterType::ForceOdd);
+                    filter.params.set(window_size, ParameterType::WindowSize);
+                    filter.process_interleaved(&mut [0.0; 64], 2);
+
+                    let used = filter.effect.channels_mut()[0].window_size;
+                    let text = filter.params.get_parameter_text(index);
+                    let shown = if filter.params.get_parameter_label(index) == " Hz" {
+                        let hz: f32 = text.parse().unwrap();
+                        (filter.params.sample_rate.get() / hz).round() as usize
+                    } else {
+                        text.parse().unwrap()
+                    };
+                    assert_eq!(
+                        shown, used,
+                        "unit {}, force odd {}, window size {}",
+                        unit, force_odd, window_size
+                    );
+                }
+            }
+        }
+    }
+
+    /// A centered mono signal has no side, so in M/S mode the side window has
+    /// no effect on it.
+    #[test]
+    fn mid_side_ignores_side_window_for_mono() {
+        let process = |side_window: f32| {
+            let mut filter = MedianFilter::new(null_host());
+            filter.params.set(1.0, ParameterType::StereoMode);
+            filter.params.set(0.1, ParameterType::MidWindow);
+            filter.params.set(side_window, ParameterType::SideWindow);
+            filter.params.set(1.0, ParameterType::WetDryLeft);
+            let mut samples: Vec<f32> = (0..2000)
+                .map(|i| (i / 2) as f32 * 0.37)
+                .map(f32::sin)
+                .collect();
+            filter.process_interleaved(&mut samples, 2);
+            samples
+        };
+        let output = process(0.05);
+        for pair in output.chunks(2) {
+            assert_eq!(pair[0], pair[1]);
+        }
+        for &side_window in [0.5, 1.0].iter() {
+            assert_eq!(process(side_window), output, "side window {}", side_window);
+        }
+    }
+
+    /// A preset loaded between `init` and the first block takes effect before
+    /// any audio is processed, so the filter starts out warmed up at the
+    /// preset's window instead of clicking as it fills.
+    #[test]
+    fn preset_before_first_block() {
+        let mut filter = MedianFilter::new(null_host());
+        filter.init();
+        let preset = RawParameters::default(null_host());
+        preset.set(0.2, ParameterType::WindowSize);
+        preset.set(0.5, ParameterType::EdgeMode);
+        preset.set(1.0, ParameterType::WetDryLeft);
+        filter.params.load_preset_data(&preset.get_preset_data());
+
+        let mut samples = vec![0.5; 256];
+        filter.process_interleaved(&mut samples, 2);
+        assert_eq!(filter.effect.channels_mut()[0].window_size, 20);
+        assert_eq!(samples, vec![0.5; 256]);
+    }
+
+    /// Run `filter` on `inputs`, one buffer per channel, through `process`,
+    /// and return its output.
+    fn process_buffers(filter: &mut MedianFilter, inputs: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        let samples = inputs[0].len();
+        let mut outputs = vec![vec![0.0; samples]; inputs.len()];
+        let input_ptrs: Vec<*const f32> = inputs.iter().map(|x| x.as_ptr()).collect();
+        let mut output_ptrs: Vec<*mut f32> = outputs.iter_mut().map(|x| x.as_mut_ptr()).collect();
+        let mut buffer = unsafe {
+            AudioBuffer::from_raw(
+                inputs.len(),
+                outputs.len(),
+                input_ptrs.as_ptr(),
+                output_ptrs.as_mut_ptr(),
+                samples,
+            )
+        };
+        filter.process(&mut buffer);
+        outputs
+    }
+
+    /// With CompensateDry on, the whole output is delayed by half the window,
+    /// and the host is told so through the initial delay.
+    #[test]
+    fn initial_delay_matches_window() {
+        let mut filter = MedianFilter::new(null_host());
+        filter.params.set(0.21, ParameterType::WindowSize);
+        // Report every change, however small.
+        filter.params.set(0.0, ParameterType::LatencyUpdates);
+        assert_eq!(filter.effective_window_samples(), 21);
+        for &(compensate_dry, expected) in [(1.0, 10), (0.0, 0)].iter() {
+            filter
+                .params
+                .set(compensate_dry, ParameterType::CompensateDry);
+            assert_eq!(filter.params.snapshot().latency(), expected);
+            process_buffers(&mut filter, &[vec![0.0; 64], vec![0.0; 64]]);
+            // The deterministic build never reports latency changes.
+            if !cfg!(feature = "deterministic") {
+                assert_eq!(filter.get_info().initial_delay, expected as i32);
+            }
+        }
+    }
+
+    /// The effective window in samples and in milliseconds agree, and match
+    /// the window the channels use.
+    #[test]
+    fn effective_window_getters_agree() {
+        let mut filter = MedianFilter::new(null_host());
+        // In milliseconds, so that the window depends on the sample rate.
+        filter.params.set(1.0, ParameterType::WindowUnit);
+        for &rate in [44100.0, 48000.0, 96000.0].iter() {
+            filter.set_sample_rate(rate);
+            let rate = filter.params.sample_rate.get();
+            for &window_size in [0.0, 0.3, 0.77, 1.0].iter() {
+                filter.params.set(window_size, ParameterType::WindowSize);
+                filter.process_interleaved(&mut [0.0; 64], 2);
+                let samples = filter.effective_window_samples();
+                assert_eq!(samples, filter.effect.channels_mut()[0].window_size);
+                let ms = filter.effective_window_ms();
+                assert!((ms / 1000.0 * rate - samples as f32).abs() < 1.0e-3);
+            }
+        }
+    }
+
+    /// At full Recorrelate, both channels' filtered signals are pulled all the
+    /// way to their mono sum, so a fully wet output is the same on both sides.
+    /// At zero, each channel is filtered on its own, without regard to the
+    /// other.
+    #[test]
+    fn recorrelate_amounts() {
+        let sine: Vec<f32> = (0..2000).map(|i| (i as f32 * 0.37).sin()).collect();
+        let cosine: Vec<f32> = (0..2000).map(|i| (i as f32 * 0.05).cos()).collect();
+        let process = |recorrelate: f32, left: &[f32], right: &[f32]| {
+            let mut filter = MedianFilter::new(null_host());
+            filter.params.set(1.0, ParameterType::WetDryLeft);
+            filter.params.set(recorrelate, ParameterType::Recorrelate);
+            let mut samples = interleave(&[left.to_vec(), right.to_vec()]);
+            filter.process_interleaved(&mut samples, 2);
+            deinterleave(&samples, 2)
+        };
+
+        // Skip the start, while the Wet/Dry knob glides up to fully wet.
+        let output = process(1.0, &sine, &cosine);
+        for (i, (l, r)) in output[0]
+            .iter()
+            .zip(output[1].iter())
+            .enumerate()
+            .skip(1000)
+        {
+            assert!((l - r).abs() < 1.0e-6, "{} and {} at sample {}", l, r, i);
+        }
+        let silence = vec![0.0; sine.len()];
+        assert_eq!(
+            process(0.0, &sine, &cosine)[0],
+            process(0.0, &sine, &silence)[0]
+        );
+        assert_ne!(
+            process(1.0, &sine, &cosine)[0],
+            process(1.0, &sine, &silence)[0]
+        );
+    }
+
+    /// Every parameter can be found again from its display name, and by its
+    /// field name.
+    #[test]
+    fn parameter_names_round_trip() {
+        for &parameter in ParameterType::all() {
+            assert_eq!(parameter.to_string().parse(), Ok(parameter));
+        }
+        assert_eq!("window_size".parse(), Ok(ParameterType::WindowSize));
+        assert_eq!("Not A Parameter".parse::<ParameterType>(), Err(()));
+    }
+
+    thread_local! {
+        // The number of allocations made on this thread since it started
+        // counting, or `None` if it isn't counting. Other tests run on their
+        // own threads, so they don't disturb the count.
+        static ALLOCATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+    }
+
+    /// The system allocator, counting allocations on threads which ask it to.
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            // The thread-local may already be gone while a thread exits.
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get().map(|n| n + 1)));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// The number of allocations `f` makes on this thread.
+    fn count_allocations(f: impl FnOnce()) -> usize {
+        ALLOCATIONS.with(|count| count.set(Some(0)));
+        f();
+        ALLOCATIONS.with(|count| count.replace(None)).unwrap()
+    }
+
+    /// Processing never allocates, even when the window size changes, the
+    /// channels are reset, or the stereo mode switches.
+    #[test]
+    fn process_never_allocates() {
+        const SAMPLES: usize = 512;
+        let inputs: Vec<Vec<f32>> = (0..2)
+            .map(|channel| {
+                (0..SAMPLES)
+                    .map(|i| (i as f32 * 0.05 + channel as f32).sin())
+                    .collect()
+            })
+            .collect();
+        let mut outputs = vec![vec![0.0; SAMPLES]; 2];
+        let input_ptrs: Vec<*const f32> = inputs.iter().map(|x| x.as_ptr()).collect();
+        let mut output_ptrs: Vec<*mut f32> = outputs.iter_mut().map(|x| x.as_mut_ptr()).collect();
+        let mut buffer = unsafe {
+            AudioBuffer::from_raw(2, 2, input_ptrs.as_ptr(), output_ptrs.as_mut_ptr(), SAMPLES)
+        };
+
+        let mut filter = MedianFilter::new(null_host());
+        filter.set_sample_rate(44100.0);
+        filter.set_block_size(SAMPLES as i64);
+        // Warm up, so that anything done once on the first block is done.
+        filter.process(&mut buffer);
+
+        filter.params.set(0.8, ParameterType::WindowSize);
+        assert_eq!(
+            count_allocations(|| filter.process(&mut buffer)),
+            0,
+            "window size change"
+        );
+
+        assert_eq!(
+            count_allocations(|| {
+                for channel in filter.effect.channels_mut() {
+                    channel.reset();
+                }
+                filter.process(&mut buffer);
+            }),
+            0,
+            "reset"
+        );
+
+        filter.params.set(1.0, ParameterType::StereoMode);
+        assert_eq!(
+            count_allocations(|| filter.process(&mut buffer)),
+            0,
+            "stereo mode switch"
+        );
+    }
+
+    /// A single large buffer is processed exactly the same as the same
+    /// samples split into sub-block sized buffers.
+    #[test]
+    fn sub_blocks_match_whole_buffer() {
+        const LENGTH: usize = 100_000;
+        let left: Vec<f32> = (0..LENGTH)
+            .map(|i| (i as f32 * 0.01).sin() + if i % 1000 == 0 { 1.0 } else { 0.0 })
+            .collect();
+        let right: Vec<f32> = (0..LENGTH).map(|i| (i as f32 * 0.003).cos()).collect();
+
+        let mut whole = MedianFilter::new(null_host());
+        let mut whole_output = [vec![0.0; LENGTH], vec![0.0; LENGTH]];
+        let [whole_left, whole_right] = &mut whole_output;
+        let params = whole.reset_if_changed();
+        whole.process_slices(params, &[&left, &right], &mut [whole_left, whole_right]);
+
+        let mut split = MedianFilter::new(null_host());
+        let mut split_output = [vec![0.0; LENGTH], vec![0.0; LENGTH]];
+        let [split_left, split_right] = &mut split_output;
+        let sub_block_size = split.effect.sub_block_size();
+        for start in (0..LENGTH).step_by(sub_block_size) {
+            let end = (start + sub_block_size).min(LENGTH);
+            let params = split.reset_if_changed();
+            split.process_slices(
+                params,
+                &[&left[start..end], &right[start..end]],
+                &mut [&mut split_left[start..end], &mut split_right[start..end]],
+            );
+        }
+
+        assert_eq!(whole_output, split_output);
+    }
+
+    /// Identical channels come out bit-identical when filtered as mono, and
+    /// forced mono makes different channels identical too.
+    #[test]
+    fn mono_input() {
+        const LENGTH: usize = 2048;
+        let sine: Vec<f32> = (0..LENGTH).map(|i| (i as f32 * 0.01).sin()).collect();
+        let cosine: Vec<f32> = (0..LENGTH).map(|i| (i as f32 * 0.01).cos()).collect();
+        for &(mode, value, right) in [
+            (MonoInput::Auto, 0.5, &sine),
+            (MonoInput::Forced, 1.0, &sine),
+            (MonoInput::Forced, 1.0, &cosine),
+        ]
+        .iter()
+        {
+            let mut filter = MedianFilter::new(null_host());
+            filter.params.set(value, ParameterType::MonoInput);
+            let mut left_output = vec![0.0; LENGTH];
+            let mut right_output = vec![0.0; LENGTH];
+            let params = filter.reset_if_changed();
+            assert_eq!(params.mono_input, mode);
+            filter.process_slices(
+                params,
+                &[&sine, right],
+                &mut [&mut left_output, &mut right_output],
+            );
+            assert!(filter.mono_input, "{} didn't filter as mono", mode);
+            let bits = |output: &[f32]| output.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits(&left_output), bits(&right_output), "{}", mode);
+        }
+    }
+
+    /// An empty block is handled without panicking, and leaves the meters
+    /// alone in every mode.
+    #[test]
+    fn empty_block() {
+        for &mode in [MeterMode::Peak, MeterMode::Rms, MeterMode::Vu].iter() {
+            let mut filter = MedianFilter::new(null_host());
+            filter.meters.set_mode(mode);
+            process_buffers(&mut filter, &[vec![0.5; 64], vec![0.5; 64]]);
+            let before = filter.meters.snapshot();
+
+            assert_eq!(
+                process_buffers(&mut filter, &[vec![], vec![]]),
+                vec![vec![]; 2]
+            );
+            filter.meters.update(
+                &[BlockLevels::new(&[]); 2],
+                &[BlockLevels::new(&[]); 2],
+                MonoFold::default(),
+                0.0,
+                0,
+                44100.0,
+            );
+            assert_eq!(filter.meters.snapshot(), before, "{}", mode);
+        }
+    }
+
+    /// Full crossfeed makes both channels filter the same signal, so that
+    /// fully wet outputs are equal even for different inputs.
+    #[test]
+    fn full_crossfeed() {
+        const LENGTH: usize = 4096;
+        // Long enough for the wet/dry smoothing to finish moving.
+        const SETTLED: usize = 2048;
+        let left: Vec<f32> = (0..LENGTH).map(|i| (i as f32 * 0.01).sin()).collect();
+        let right: Vec<f32> = (0..LENGTH)
+            .map(|i| (i as f32 * 0.037).cos() * 0.3)
+            .collect();
+        let mut filter = MedianFilter::new(null_host());
+        filter.params.set(1.0, ParameterType::WetDryLeft);
+        filter.params.set(1.0, ParameterType::WetDryRight);
+        filter.params.set(1.0, ParameterType::Crossfeed);
+        let output = process_buffers(&mut filter, &[left, right]);
+        for (i, (l, r)) in output[0]
+            .iter()
+            .zip(output[1].iter())
+            .enumerate()
+            .skip(SETTLED)
+        {
+            assert!((l - r).abs() <= 1e-6, "sample {}", i);
+        }
+    }
+
+    /// Two plugins produce bit-identical output for the same input, even when
+    /// the host asks them for different sample rates. Dither is on, so this
+    /// also checks that the noise is seeded the same way.
+    #[cfg(feature = "deterministic")]
+    #[test]
+    fn deterministic_output() {
+        const LENGTH: usize = 4096;
+        let left: Vec<f32> = (0..LENGTH).map(|i| (i as f32 * 0.01).sin()).collect();
+        let right: Vec<f32> = (0..LENGTH).map(|i| (i as f32 * 0.02).cos()).collect();
+        let render = |sample_rate: f32| {
+            let mut filter = MedianFilter::new(null_host());
+            filter.set_sample_rate(sample_rate);
+            // The wet/dry is smoothed over a fixed time, which is a different
+            // number of samples at each sample rate.
+            filter.params.set(1.0, ParameterType::WetDryLeft);
+            filter.params.set(0.3, ParameterType::BitCrush);
+            filter.params.set(1.0, ParameterType::Dither);
+            let output = process_buffers(&mut filter, &[left.clone(), right.clone()]);
+            output
+                .iter()
+                .map(|channel| channel.iter().map(|x| x.to_bits()).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(render(48000.0), render(96000.0));
+    }
+
+    /// With the `deterministic` feature, the host is never called, not even
+    /// for an editor's gestures.
+    #[cfg(feature = "deterministic")]
+    #[test]
+    fn deterministic_never_calls_host() {
+        thread_local! {
+            static HOST_CALLS: Cell<usize> = const { Cell::new(0) };
+        }
+        extern "C" fn counting_host(
+            _effect: *mut vst::api::AEffect,
+            _opcode: i32,
+            _index: i32,
+            _value: isize,
+            _ptr: *mut std::os::raw::c_void,
+            _opt: f32,
+        ) -> isize {
+            HOST_CALLS.with(|calls| calls.set(calls.get() + 1));
+            0
+        }
+
+        let mut filter = MedianFilter::new(HostCallback::wrap(counting_host, std::ptr::null_mut()));
+        filter.set_cc_feedback(ParameterType::WetDryLeft, Some(20));
+        filter.params.begin_gesture(ParameterType::WindowSize);
+        filter.params.set(0.5, ParameterType::WindowSize);
+        filter.params.end_gesture(ParameterType::WindowSize);
+        filter.params.set(0.25, ParameterType::WetDryLeft);
+        process_buffers(&mut filter, &[vec![0.5; 64], vec![0.5; 64]]);
+        drop(filter);
+        assert_eq!(HOST_CALLS.with(Cell::get), 0);
+    }
+
+    /// With `VintageFeel`, a step change to a parameter rises steadily, and
+    /// takes `VINTAGE_LAG_MS` to fully reach the channels.
+    #[test]
+    fn vintage_feel_lags_changes() {
+        const SAMPLE_RATE: f32 = 44100.0;
+        const BLOCK_SIZE: usize = 64;
+        let lag_samples = (VINTAGE_LAG_MS / 1000.0 * SAMPLE_RATE) as usize;
+        let mut filter = MedianFilter::new(null_host());
+        filter.set_sample_rate(SAMPLE_RATE);
+        filter.params.set(1.0, ParameterType::VintageFeel);
+        filter.params.set(1.0, ParameterType::Recorrelate);
+        let input = [0.0; BLOCK_SIZE];
+        let mut output = [[0.0; BLOCK_SIZE]; 2];
+        let mut elapsed = 0;
+        let mut last = 0.0;
+        loop {
+            let params = filter.reset_if_changed();
+            let recorrelate = params.recorrelate;
+            if recorrelate == 1.0 {
+                break;
+            }
+            assert!(
+                recorrelate >= last,
+                "fell to {} after {} samples",
+                recorrelate,
+                elapsed
+            );
+            assert!(
+                elapsed <= lag_samples + BLOCK_SIZE,
+                "still {} after {} samples",
+                recorrelate,
+                elapsed
+            );
+            last = recorrelate;
+            let [left, right] = &mut output;
+            filter.process_slices(params, &[&input, &input], &mut [left, right]);
+            elapsed += BLOCK_SIZE;
+        }
+        assert!(
+            elapsed + BLOCK_SIZE >= lag_samples,
+            "done after only {} samples",
+            elapsed
+        );
+    }
+
+    /// The gate scales the output by each step of the pattern in turn, with
+    /// the steps lined up with the song position.
+    #[test]
+    fn gate_follows_pattern() {
+        use std::convert::TryFrom;
+
+        const SAMPLE_RATE: f32 = 44100.0;
+        const TEMPO: f64 = 120.0;
+        // A sixteenth note at 120 BPM.
+        let step_samples = SAMPLE_RATE as f64 * 60.0 / TEMPO / 4.0;
+        let length = (step_samples * GATE_STEPS as f64) as usize + 1;
+        let mut filter = MedianFilter::new(null_host());
+        filter.set_sample_rate(SAMPLE_RATE);
+        filter.params.set(1.0, ParameterType::Gate);
+        let pattern: Vec<f32> = (0..GATE_STEPS)
+            .map(|step| (step % 5) as f32 / 4.0)
+            .collect();
+        let first_step: i32 = ParameterType::GateStep1.into();
+        for (step, &gain) in pattern.iter().enumerate() {
+            let parameter = ParameterType::try_from(first_step + step as i32).unwrap();
+            filter.params.set(gain, parameter);
+        }
+        filter.lfo.sync(Some(TimeInfo {
+            ppq_pos: 0.0,
+            tempo: TEMPO,
+            flags: (TimeInfoFlags::PPQ_POS_VALID | TimeInfoFlags::TEMPO_VALID).bits(),
+            ..Default::default()
+        }));
+        // A constant input comes straight through the filter.
+        let input = vec![0.5; length];
+        let mut output = [vec![0.0; length], vec![0.0; length]];
+        let params = filter.reset_if_changed();
+        let [left, right] = &mut output;
+        filter.process_slices(params, &[&input, &input], &mut [left, right]);
+        for (step, &gain) in pattern.iter().enumerate() {
+            let i = ((step as f64 + 0.5) * step_samples) as usize;
+            let expected = 0.5 * gain;
+            assert!(
+                (output[0][i] - expected).abs() <= 1e-4,
+                "step {} is {}",
+                step,
+                output[0][i]
+            );
+        }
+    }
+
+    /// The group delay adds up the delay of every feature which delays the
+    /// output, and the host is told the same delay.
+    #[test]
+    fn group_delay_adds_up() {
+        let filter = MedianFilter::new(null_host());
+        let params = &filter.params;
+        params.set(0.0, ParameterType::WindowUnit);
+        params.set(1.0, ParameterType::CompensateDry);
+        params.set(1.0, ParameterType::ForceOdd);
+        let window_delay = (params.snapshot().window_size.samples - 1) as f32 / 2.0;
+        assert_eq!(filter.group_delay_samples(), window_delay);
+
+        params.set(1.0, ParameterType::Limit);
+        params.set(1.0, ParameterType::LimitMode);
+        assert_eq!(params.snapshot().limit_mode, LimitMode::Lookahead);
+        // Split into two bands, with the high band's window the largest.
+        params.set(0.5, ParameterType::Bands);
+        params.set(1.0, ParameterType::HighBandWindow);
+        let band_window = params.snapshot().high_band_window.samples;
+        let expected = (band_window - 1) as f32 / 2.0 + LOOKAHEAD_SAMPLES as f32;
+        assert_eq!(filter.group_delay_samples(), expected);
+        assert_eq!(params.snapshot().latency(), expected as usize);
+    }
+
+    /// Each channel's meters show that channel's own level, while the
+    /// combined meters show the loudest channel.
+    #[test]
+    fn channel_meters() {
+        const LEVELS: [f32; 2] = [0.25, 0.75];
+        let mut filter = MedianFilter::new(null_host());
+        process_buffers(&mut filter, &[vec![LEVELS[0]; 256], vec![LEVELS[1]; 256]]);
+        let values = filter.meters.snapshot();
+        assert_eq!(values.channels, 2);
+        for (channel, &level) in LEVELS.iter().enumerate() {
+            assert!(
+                (values.input_channels[channel] - level).abs() <= 1e-6,
+                "channel {}",
+                channel
+            );
+            assert!(
+                (values.output_channels[channel] - level).abs() <= 1e-6,
+                "channel {}",
+                channel
+            );
+        }
+        assert_eq!(values.input, LEVELS[1]);
+    }
+
+    /// Presets round trip through `get_preset_data`, and loading presets while
+    /// processing never gives the processor a mix of two presets. Every
+    /// parameter in each preset has the same value, so a mix shows up as
+    /// differing values.
+    #[test]
+    fn preset_loads_are_atomic() {
+        const LOADS: usize = 2000;
+        let mut filter = MedianFilter::new(null_host());
+        let presets = [0.2, 0.4].map(|value| {
+            filter
+                .params
+                .load_normalized(&[value; ParameterType::COUNT]);
+            filter.params.get_preset_data()
+        });
+        filter.params.load_preset_data(&presets[0]);
+        assert_eq!(filter.params.all_normalized(), [0.2; ParameterType::COUNT]);
+        // A read which overlaps a load keeps the last values read, so start
+        // from a read of the first preset rather than the defaults.
+        filter.read_parameters();
+
+        let params = Arc::clone(&filter.params);
+        let done = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 0..LOADS {
+                    params.load_preset_data(&presets[(i + 1) % 2]);
+                }
+                done.store(true, Ordering::Release);
+            });
+            while !done.load(Ordering::Acquire) {
+                filter.read_parameters();
+                let values = filter.local_params.all_normalized();
+                assert!(
+                    values.iter().all(|&value| value == values[0]),
+                    "processed a mix of presets: {:?}",
+                    filter.local_params
+                );
+            }
+        });
+    }
+
+    /// `process_interleaved` gives the same output as processing each
+    /// channel's buffer.
+    #[test]
+    fn interleaved_matches_buffers() {
+        const LENGTH: usize = 1000;
+        let left: Vec<f32> = (0..LENGTH).map(|i| (i as f32 * 0.01).sin()).collect();
+        let right: Vec<f32> = (0..LENGTH).map(|i| (i as f32 * 0.03).cos()).collect();
+        let new_filter = || {
+            let mut filter = MedianFilter::new(null_host());
+            filter.set_sample_rate(44100.0);
+            filter.params.set(1.0, ParameterType::WetDryLeft);
+            filter
+        };
+        let mut expected = [vec![0.0; LENGTH], vec![0.0; LENGTH]];
+        let mut filter = new_filter();
+        let [left_output, right_output] = &mut expected;
+        let params = filter.reset_if_changed();
+        filter.process_slices(params, &[&left, &right], &mut [left_output, right_output]);
+        let mut output = interleave(&[left, right]);
+        new_filter().process_interleaved(&mut output, 2);
+        assert_eq!(deinterleave(&output, 2), expected);
+    }
+
+    /// The filter keeps its own id, rather than one derived from its name.
+    #[test]
+    fn unique_id() {
+        let info = MedianFilter::new(null_host()).get_info();
+        assert_eq!(info.unique_id, 612413);
+    }
+
+    /// Changing a parameter mapped to a CC queues a message with its new
+    /// value, no sooner than `CC_FEEDBACK_INTERVAL_MS` after the last one, and
+    /// unmapped parameters send nothing.
+    #[test]
+    fn cc_feedback() {
+        let interval = cc_feedback_interval(44100.0);
+        let mut filter = MedianFilter::new(null_host());
+        filter.set_cc_feedback_channel(2);
+        filter.set_cc_feedback(ParameterType::WetDryLeft, Some(20));
+        let update = |filter: &mut MedianFilter, samples: usize| {
+            let values = filter.params.all_normalized();
+            filter
+                .cc_feedback
+                .update(&values, samples)
+                .iter()
+                .map(|event| event.data)
+                .collect::<Vec<_>>()
+        };
+        // The current value is sent as soon as the parameter is mapped.
+        let expected = (filter.params.get(ParameterType::WetDryLeft) * 127.0).round() as u8;
+        assert_eq!(update(&mut filter, 0), [[0xB2, 20, expected]]);
+
+        filter.params.set(0.25, ParameterType::WetDryLeft);
+        filter.params.set(1.0, ParameterType::Bypass);
+        assert!(update(&mut filter, interval - 1).is_empty());
+        assert_eq!(update(&mut filter, 1), [[0xB2, 20, 32]]);
+        assert!(update(&mut filter, interval).is_empty());
+    }
+
+    /// With `set_window_commit_on_release`, dragging the window size resizes
+    /// the filter once, when the drag ends, while the display follows the
+    /// drag.
+    #[test]
+    fn window_commit_on_release() {
+        let mut filter = MedianFilter::new(null_host());
+        filter.set_window_commit_on_release(true);
+        filter.params.set(0.0, ParameterType::ForceOdd);
+        let mut window_size = filter.reset_if_changed().window_size.samples;
+        let mut resizes = Vec::new();
+        let mut read = |filter: &mut MedianFilter| {
+            filter.reset_if_changed();
+            let resized = filter.effect.channels_mut()[0].window_size;
+            if resized != window_size {
+                resizes.push(resized);
+                window_size = resized;
+            }
+        };
+
+        filter.params.begin_gesture(ParameterType::WindowSize);
+        for i in 0..=50 {
+            filter
+                .params
+                .set(0.1 + i as f32 * 0.01, ParameterType::WindowSize);
+            read(&mut filter);
+        }
+        let shown = filter.params.get_strings(ParameterType::WindowSize);
+        filter.params.end_gesture(ParameterType::WindowSize);
+        read(&mut filter);
+
+        let expected = Parameters::from(&*filter.params).window_size.samples;
+        assert_eq!(resizes, [expected]);
+        assert_eq!(shown, window_strings(expected, false));
+    }
+
+    /// Normalizing a quiet input sets `InputGain` so that its peak reaches
+    /// `NORMALIZE_TARGET_DB` going into the filter.
+    #[test]
+    fn input_normalization() {
+        const PEAK: f32 = 0.25;
+        const LENGTH: usize = 44100;
+        let input: Vec<f32> = (0..LENGTH)
+            .map(|i| PEAK * (i as f32 * std::f32::consts::TAU / 100.0).sin())
+            .collect();
+        let mut filter = MedianFilter::new(null_host());
+        filter.set_sample_rate(44100.0);
+        filter.start_input_normalization();
+        assert!(filter.finish_input_normalization().is_none());
+        process_buffers(&mut filter, &[input.clone(), input.clone()]);
+        assert!(filter.finish_input_normalization().is_some());
+
+        // With a window of one sample, the filter passes on its input.
+        let mut params = MedianFilter::test_parameters(1);
+        params.input_gain = Parameters::from(&*filter.params).input_gain;
+        let mut output = vec![0.0; LENGTH];
+        MedianProcessor::new(0, &params).process_block(&input, &mut output);
+        let peak_db = 20.0 * meters::peak(&output).log10();
+        assert!(
+            (peak_db - NORMALIZE_TARGET_DB).abs() <= 0.1,
+            "peak is {} dB",
+            peak_db
+        );
+    }
+
+    /// Processing before the sample rate is set is noted, and once it is set,
+    /// processing starts over cleanly at the new rate. Nonsensical sample
+    /// rates are ignored.
+    #[test]
+    fn process_before_sample_rate() {
+        const LENGTH: usize = 256;
+        let mut filter = MedianFilter::new(null_host());
+        filter.params.set(1.0, ParameterType::WetDryLeft);
+        let loud = [0.5; LENGTH];
+        let mut output = [[0.0; LENGTH]; 2];
+        let [left, right] = &mut output;
+        let params = filter.reset_if_changed();
+        filter.process_slices(params, &[&loud, &loud], &mut [left, right]);
+
+        filter.set_sample_rate(0.0);
+        filter.set_sample_rate(f32::NAN);
+        filter.set_sample_rate(48000.0);
+        let expected = if cfg!(feature = "deterministic") {
+            DETERMINISTIC_SAMPLE_RATE
+        } else {
+            48000.0
+        };
+        assert!(filter.processed_before_sample_rate());
+        for channel in filter.effect.channels_mut() {
+            assert_eq!(channel.sample_rate, expected);
+        }
+
+        // Nothing from before the sample rate was set is heard.
+        let silence = [0.0; LENGTH];
+        let [left, right] = &mut output;
+        let params = filter.reset_if_changed();
+        filter.process_slices(params, &[&silence, &silence], &mut [left, right]);
+        assert_eq!(output, [[0.0; LENGTH]; 2]);
+    }
+
+    /// Each parameter's mapping matches the values it shows. A stepped
+    /// parameter shows exactly as many values as it has steps, and a
+    /// continuous one many more. Halfway along its range, a linear parameter
+    /// shows the middle of the values at either end, and a log parameter
+    /// their geometric mean.
+    #[test]
+    fn parameter_mappings() {
+        const POINTS: usize = 1000;
+        let filter = MedianFilter::new(null_host());
+        let params = &filter.params;
+        // Otherwise the right wet/dry would show the left's value.
+        params.set(0.0, ParameterType::LinkWetDry);
+        for &parameter in ParameterType::all() {
+            let mapping = RawParameters::get_mapping(parameter);
+            let default = params.get(parameter);
+            let show = |x: f32| {
+                params.set(x, parameter);
+                params.get_strings(parameter).0
+            };
+            let mut shown: Vec<String> = (0..=POINTS)
+                .map(|i| show(i as f32 / POINTS as f32))
+                .collect();
+            let value = |x: f32| {
+                let text = show(x);
+                text.parse::<f32>()
+                    .unwrap_or_else(|_| panic!("{} shows \"{}\", not a number", parameter, text))
+            };
+            // Values outside the usable range show the nearest end of it.
+            let (min, max) = RawParameters::get_usable_range(parameter);
+            let ends = match mapping {
+                Mapping::Linear | Mapping::Log => {
+                    Some((value(min), value((min + max) / 2.0), value(max)))
+                }
+                Mapping::Stepped(_) => None,
+            };
+            params.set(default, parameter);
+
+            shown.sort();
+            shown.dedup();
+            match mapping.steps() {
+                Some(steps) => assert_eq!(shown.len(), steps, "{}", parameter),
+                None => assert!(
+                    shown.len() > 64,
+                    "{} shows {} values",
+                    parameter,
+                    shown.len()
+                ),
+            }
+
+            if let Some((low, middle, high)) = ends {
+                let expected = match mapping {
+                    Mapping::Log => (low * high).sqrt(),
+                    _ => (low + high) / 2.0,
+                };
+                assert!(
+                    (middle - expected).abs() <= 0.02 * (high - low).abs(),
+                    "{} is {:?}, but shows {} halfway between {} and {}",
+                    parameter,
+                    mapping,
+                    middle,
+                    low,
+                    high
+                );
+            }
+        }
+    }
+
+    /// The mono meter scores an in-phase signal as fully mono compatible, and
+    /// an anti-phase signal, which cancels out in the mono sum, as not
+    /// compatible at all.
+    #[test]
+    fn mono_meter() {
+        const LENGTH: usize = 1024;
+        let left: Vec<f32> = (0..LENGTH).map(|i| 0.5 * (i as f32 * 0.05).sin()).collect();
+        let inverted: Vec<f32> = left.iter().map(|x| -x).collect();
+
+        let mut filter = MedianFilter::new(null_host());
+        process_buffers(&mut filter, &[left.clone(), left.clone()]);
+        let values = filter.meters.snapshot();
+        assert!(
+            values.mono_compatibility > 0.99,
+            "{}",
+            values.mono_compatibility
+        );
+        assert!(
+            values.mono_difference_db() > -0.1,
+            "{} dB",
+            values.mono_difference_db()
+        );
+
+        let mut filter = MedianFilter::new(null_host());
+        process_buffers(&mut filter, &[left, inverted]);
+        let values = filter.meters.snapshot();
+        assert!(
+            values.mono_compatibility < 0.01,
+            "{}",
+            values.mono_compatibility
+        );
+        assert!(
+            values.mono_difference_db() < -40.0,
+            "{} dB",
+            values.mono_difference_db()
+        );
+    }
+
+    /// Dropping the plugin in the middle of a gesture sends the host the
+    /// end_edit matching the gesture's begin_edit, and flushes any other
+    /// edits.
+    #[test]
+    fn shutdown_closes_gestures() {
+        const BEGIN_EDIT: i32 = 43;
+        const END_EDIT: i32 = 44;
+        thread_local! {
+            static EDITS: std::cell::RefCell<Vec<(i32, i32)>> =
+                const { std::cell::RefCell::new(Vec::new()) };
+        }
+        extern "C" fn record(
+            _effect: *mut vst::api::AEffect,
+            opcode: i32,
+            index: i32,
+            _value: isize,
+            _ptr: *mut std::os::raw::c_void,
+            _opt: f32,
+        ) -> isize {
+            EDITS.with(|edits| edits.borrow_mut().push((opcode, index)));
+            0
+        }
+
+        for &shutdown_on_drop in &[true, false] {
+            EDITS.with(|edits| edits.borrow_mut().clear());
+            let host = HostCallback::wrap(record, std::ptr::null_mut());
+            let mut filter = MedianFilter::new(host);
+            filter.set_shutdown_on_drop(shutdown_on_drop);
+            filter.params.begin_gesture(ParameterType::WindowSize);
+            filter.params.set(0.4, ParameterType::WindowSize);
+            filter.params.set(0.3, ParameterType::WetDryLeft);
+            drop(filter);
+
+            let window: i32 = ParameterType::WindowSize.into();
+            let wet_dry: i32 = ParameterType::WetDryLeft.into();
+            let expected = if cfg!(feature = "deterministic") {
+                vec![]
+            } else if shutdown_on_drop {
+                vec![
+                    (BEGIN_EDIT, window),
+                    (END_EDIT, window),
+                    (BEGIN_EDIT, wet_dry),
+                    (END_EDIT, wet_dry),
+                ]
+            } else {
+                vec![(BEGIN_EDIT, window)]
+            };
+            let edits = EDITS.with(|edits| edits.borrow().clone());
+            assert_eq!(edits, expected, "shutdown on drop: {}", shutdown_on_drop);
+        }
+    }
+
+    /// With a preset glide, a continuous parameter ramps steadily to its value
+    /// in a newly loaded preset, arriving after the glide time, while a
+    /// stepped parameter jumps there straight away.
+    #[test]
+    fn preset_glide() {
+        const SAMPLE_RATE: f32 = 44100.0;
+        const BLOCK_SIZE: usize = 64;
+        const GLIDE_MS: f32 = 50.0;
+        let glide_samples = (GLIDE_MS / 1000.0 * SAMPLE_RATE) as usize;
+        let wet_dry = i32::from(ParameterType::WetDryLeft) as usize;
+        let mode = i32::from(ParameterType::Mode) as usize;
+        let mut filter = MedianFilter::new(null_host());
+        filter.set_sample_rate(SAMPLE_RATE);
+        filter.set_preset_glide(GLIDE_MS);
+        filter.reset_if_changed();
+
+        let start = filter.params.get(ParameterType::WetDryLeft);
+        let mut preset = filter.params.all_normalized();
+        preset[wet_dry] = 1.0;
+        preset[mode] = 1.0;
+        filter.params.load_normalized(&preset);
+        // The most the wet/dry may move in one block.
+        let max_step = (1.0 - start) * BLOCK_SIZE as f32 / glide_samples as f32 + 1.0e-4;
+
+        let input = [0.0; BLOCK_SIZE];
+        let mut output = [[0.0; BLOCK_SIZE]; 2];
+        let mut elapsed = 0;
+        let mut last = start;
+        loop {
+            let params = filter.reset_if_changed();
+            assert_eq!(
+                filter.local_params.get(ParameterType::Mode),
+                1.0,
+                "after {} samples",
+                elapsed
+            );
+            let value = filter.local_params.get(ParameterType::WetDryLeft);
+            if value == 1.0 {
+                break;
+            }
+            assert!(
+                elapsed <= glide_samples + BLOCK_SIZE,
+                "still {} after {} samples",
+                value,
+                elapsed
+            );
+            assert!(
+                value >= last && value - last <= max_step,
+                "went from {} to {} after {} samples",
+                last,
+                value,
+                elapsed
+            );
+            last = value;
+            let [left, right] = &mut output;
+            filter.process_slices(params, &[&input, &input], &mut [left, right]);
+            elapsed += BLOCK_SIZE;
+        }
+        assert!(
+            elapsed + BLOCK_SIZE >= glide_samples,
+            "done after only {} samples",
+            elapsed
+        );
+    }
+
+    /// The scope holds the last samples of each channel's input, and a GUI
+    /// thread reading it while audio is processed always sees an unbroken run
+    /// of samples.
+    #[test]
+    fn scope_reads_are_never_torn() {
+        const BLOCK_SIZE: usize = 100;
+        const BLOCKS: usize = 100;
+        let mut filter = MedianFilter::new(null_host());
+        filter.set_sample_rate(44100.0);
+        let scope = filter.scope();
+        assert!(scope.read(0).is_none());
+        // Each channel is a ramp, so any gap or mix of two blocks shows up as
+        // a step other than 1.0 between samples.
+        let left: Vec<f32> = (0..BLOCK_SIZE * BLOCKS).map(|i| i as f32).collect();
+        let right: Vec<f32> = left.iter().map(|x| -x).collect();
+        let done = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|threads| {
+            let reader = threads.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    if let Some(samples) = scope.read(0) {
+                        for pair in samples.windows(2) {
+                            assert_eq!(pair[1] - pair[0], 1.0, "read a jump from {}", pair[0]);
+                        }
+                    }
+                }
+            });
+            let mut output = [[0.0; BLOCK_SIZE]; 2];
+            for (left, right) in left.chunks(BLOCK_SIZE).zip(right.chunks(BLOCK_SIZE)) {
+                let params = filter.reset_if_changed();
+                let [left_output, right_output] = &mut output;
+                filter.process_slices(params, &[left, right], &mut [left_output, right_output]);
+            }
+            done.store(true, Ordering::Relaxed);
+            reader.join().unwrap();
+        });
+
+        for (channel, input) in [&left, &right].iter().enumerate() {
+            let expected = &input[input.len() - SCOPE_SAMPLES..];
+            assert_eq!(
+                scope.read(channel).as_deref(),
+                Some(expected),
+                "channel {}",
+                channel
+            );
+        }
+        assert!(scope.read(2).is_none());
+    }
+
+    /// Every usable range in the parameter table is a part of 0.0 to 1.0, and
+    /// Hampel K is clamped to its usable range while the host still reads
+    /// back the value it set.
+    #[test]
+    fn usable_ranges() {
+        for &parameter in ParameterType::all() {
+            let (min, max) = RawParameters::get_usable_range(parameter);
+            assert!(
+                0.0 <= min && min < max && max <= 1.0,
+                "{} has a usable range of {} to {}",
+                parameter,
+                min,
+                max
+            );
+        }
+
+        let mut filter = MedianFilter::new(null_host());
+        let index = ParameterType::HampelK.into();
+        filter.params.set_parameter(index, 0.0);
+        assert_eq!(filter.params.get_parameter(index), 0.0);
+        assert_eq!(filter.read_parameters().hampel_k, MIN_HAMPEL_K);
+    }
+
+    /// `SoftStart` fades the output in over its first `SOFT_START_MS` after
+    /// the plugin is loaded and again after it is resumed, compared with the
+    /// same filter without it.
+    #[test]
+    fn soft_start_fades_in() {
+        const SAMPLE_RATE: f32 = 44100.0;
+        let fade_samples = (SOFT_START_MS / 1000.0 * SAMPLE_RATE) as usize;
+        let length = 2 * fade_samples;
+        let mut filters = [false, true].map(|soft_start| {
+            let mut filter = MedianFilter::new(null_host());
+            filter.set_sample_rate(SAMPLE_RATE);
+            filter
+                .params
+                .set(soft_start as i32 as f32, ParameterType::SoftStart);
+            filter
+        });
+        let input: Vec<f32> = (0..length).map(|i| (i as f32 * 0.05).sin()).collect();
+        for pass in ["load", "resume"].iter() {
+            let [plain, faded] = filters.each_mut().map(|filter| {
+                let mut output = [vec![0.0; length], vec![0.0; length]];
+                let params = filter.reset_if_changed();
+                let [left, right] = &mut output;
+                filter.process_slices(params, &[&input, &input], &mut [left, right]);
+                filter.resume();
+                output
+            });
+            // Without a soft start, the output isn't delayed past the fade.
+            assert!(plain[0][..fade_samples].iter().any(|&x| x != 0.0));
+            for (i, (&x, &expected)) in faded[0].iter().zip(plain[0].iter()).enumerate() {
+                let expected = expected * (i as f32 / fade_samples as f32).min(1.0);
+                assert!(
+                    (x - expected).abs() <= 1e-4,
+                    "expected {} at sample {} after {}, got {}",
+                    expected,
+                    i,
+                    pass,
+                    x
+                );
+            }
+        }
+    }
+}
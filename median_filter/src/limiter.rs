@@ -0,0 +1,99 @@
+/// How far ahead the lookahead limiter looks, in samples. This is also the
+/// latency it adds.
+pub const LOOKAHEAD_SAMPLES: usize = 64;
+/// How long the limiter takes to recover from full gain reduction.
+const RELEASE_MS: f32 = 50.0;
+
+/// A peak limiter which delays its input by `LOOKAHEAD_SAMPLES`, so that it
+/// can see peaks coming and turn the gain down smoothly before they arrive.
+/// Unlike soft clipping, this never changes the shape of the waveform, so it
+/// doesn't add distortion.
+pub struct LookaheadLimiter {
+    // The last `LOOKAHEAD_SAMPLES + 1` samples, as a ring buffer.
+    buffer: [f32; LOOKAHEAD_SAMPLES + 1],
+    // The index of the most recently pushed sample.
+    position: usize,
+    gain: f32,
+    // How far the gain rises each sample while releasing.
+    release_step: f32,
+}
+
+impl LookaheadLimiter {
+    pub fn new(sample_rate: f32) -> LookaheadLimiter {
+        let mut limiter = LookaheadLimiter {
+            buffer: [0.0; LOOKAHEAD_SAMPLES + 1],
+            position: 0,
+            gain: 1.0,
+            release_step: 0.0,
+        };
+        limiter.set_sample_rate(sample_rate);
+        limiter
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.release_step = 1.0 / (RELEASE_MS / 1000.0 * sample_rate).max(1.0);
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer = [0.0; LOOKAHEAD_SAMPLES + 1];
+        self.gain = 1.0;
+    }
+
+    /// Push `x` into the limiter and return the sample from
+    /// `LOOKAHEAD_SAMPLES` ago, turned down so that it doesn't exceed
+    /// `ceiling`.
+    pub fn process(&mut self, x: f32, ceiling: f32) -> f32 {
+        let len = self.buffer.len();
+        self.position = (self.position + 1) % len;
+        self.buffer[self.position] = x;
+
+        // Each sample in the buffer needs the gain to be at or below
+        // `ceiling / |sample|` by the time it is output. Moving at least
+        // 1 / (samples until then + 1) of the way there each sample gets there
+        // just in time, while spreading the reduction over as long as possible.
+        let mut gain = (self.gain + self.release_step).min(1.0);
+        for delay in 0..len {
+            let sample = self.buffer[(self.position + len - delay) % len].abs();
+            if sample * gain <= ceiling {
+                continue;
+            }
+            let target = ceiling / sample;
+            let remaining = (LOOKAHEAD_SAMPLES - delay) as f32;
+            gain = gain.min(self.gain + (target - self.gain) / (remaining + 1.0));
+        }
+        self.gain = gain;
+
+        let output = self.buffer[(self.position + 1) % len];
+        output * self.gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A loud burst is kept under the ceiling, and the quiet signal before it
+    /// comes into view is only delayed.
+    #[test]
+    fn limits_bursts_without_touching_quiet_signal() {
+        const CEILING: f32 = 0.5;
+        let mut limiter = LookaheadLimiter::new(44100.0);
+        let input: Vec<f32> = (0..1024)
+            .map(|i| {
+                let sine = (i as f32 * std::f32::consts::TAU / 100.0).sin();
+                if (400..500).contains(&i) {
+                    sine * 4.0
+                } else {
+                    sine * 0.25
+                }
+            })
+            .collect();
+        for (i, &x) in input.iter().enumerate() {
+            let out = limiter.process(x, CEILING);
+            assert!(out.abs() <= CEILING + 1e-6, "sample {} is {}", i, out);
+            if (LOOKAHEAD_SAMPLES..400).contains(&i) {
+                assert_eq!(out, input[i - LOOKAHEAD_SAMPLES], "sample {}", i);
+            }
+        }
+    }
+}
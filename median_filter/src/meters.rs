@@ -0,0 +1,681 @@
+use std::sync::atomic::{fence, AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+use vst::util::AtomicFloat;
+
+use common::{db_to_gain, plugin::MAX_CHANNELS};
+
+/// How long a peak is held before it starts to decay.
+const PEAK_HOLD_MS: f32 = 500.0;
+/// How fast a peak decays after it has been held, in decibels per second.
+const PEAK_DECAY_DB_PER_SECOND: f32 = 20.0;
+/// The integration time of `MeterMode::Rms`.
+const RMS_WINDOW_MS: f32 = 300.0;
+/// The time constant of `MeterMode::Vu`. A VU meter reaches 99% of a steady
+/// tone's level in 300ms, which takes about 4.6 time constants.
+const VU_TIME_CONSTANT_MS: f32 = 300.0 / 4.6;
+/// A VU meter averages the rectified signal, but is calibrated so that a sine
+/// wave reads its RMS level. This is the ratio of a sine's RMS level to its
+/// average rectified level, π / (2√2).
+const VU_SINE_SCALE: f32 = std::f32::consts::PI / (2.0 * std::f32::consts::SQRT_2);
+/// The default time constant of the smoothed meter values.
+pub const DEFAULT_SMOOTHING_MS: f32 = 100.0;
+
+/// The ballistics used by the meters, so that a GUI can match the standard
+/// meter its users expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeterMode {
+    /// Holds the loudest recent sample, then lets it fall off gradually.
+    Peak,
+    /// The RMS level over the last 300ms.
+    Rms,
+    /// A VU meter, which reads the level of a sine wave as its RMS level, with
+    /// a 300ms rise time.
+    Vu,
+}
+
+impl MeterMode {
+    fn from_u8(x: u8) -> MeterMode {
+        match x {
+            0 => MeterMode::Peak,
+            1 => MeterMode::Rms,
+            _ => MeterMode::Vu,
+        }
+    }
+}
+
+impl std::fmt::Display for MeterMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MeterMode::Peak => write!(f, "Peak"),
+            MeterMode::Rms => write!(f, "RMS"),
+            MeterMode::Vu => write!(f, "VU"),
+        }
+    }
+}
+
+/// Levels measured by the plugin, for display in a GUI. These are updated once
+/// per block by the audio thread and may be read from any thread. Use
+/// `snapshot` to read every meter from the same block.
+pub struct Meters {
+    /// The loudest of the channels' levels.
+    pub input: LevelMeter,
+    pub output: LevelMeter,
+    // Each channel's own levels, so that the channels can be shown
+    // separately. Only the first `channels` are in use.
+    input_channels: Vec<LevelMeter>,
+    output_channels: Vec<LevelMeter>,
+    channels: AtomicUsize,
+    // The output summed to mono, and how well it survived. See `MonoFold`.
+    mono: LevelMeter,
+    mono_compatibility: AtomicFloat,
+    // The input's median absolute value, when `Envelope Meter` is on.
+    envelope: AtomicFloat,
+    envelope_smoothed: AtomicFloat,
+    mode: AtomicU8,
+    // The time constant of the smoothed values, in milliseconds.
+    smoothing_ms: AtomicFloat,
+    // Set by `set_mode`, so that the audio thread resets the meters before its
+    // next update. The audio thread is then the only thread writing levels.
+    reset_pending: AtomicBool,
+    // Incremented before and after every update, so it is odd while an update
+    // is in progress. A reader which sees the same even generation before and
+    // after reading saw a single, complete update.
+    generation: AtomicU64,
+}
+
+/// Every meter's value from the same block. See `Meters::snapshot`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeterValues {
+    /// The input level, as a linear amplitude.
+    pub input: f32,
+    /// The output level, as a linear amplitude.
+    pub output: f32,
+    /// Each channel's input and output level. Only the first `channels` are
+    /// in use, and the rest are 0.0.
+    pub input_channels: [f32; MAX_CHANNELS],
+    pub output_channels: [f32; MAX_CHANNELS],
+    pub channels: usize,
+    /// The level of the output summed to mono, as a linear amplitude.
+    pub mono: f32,
+    /// How well the output survives being summed to mono in the last block.
+    /// See `MonoFold::compatibility`.
+    pub mono_compatibility: f32,
+    /// The median absolute value of the input over the envelope window, or
+    /// 0.0 if the envelope meter is off.
+    pub envelope: f32,
+    /// The same levels, smoothed so that they change steadily from block to
+    /// block. These suit a GUI better than the raw levels above, which can
+    /// jump around between blocks. See `Meters::set_smoothing`.
+    pub input_smoothed: f32,
+    pub output_smoothed: f32,
+    pub mono_smoothed: f32,
+    pub envelope_smoothed: f32,
+    pub mode: MeterMode,
+    /// How many times the meters have been updated. This only changes when
+    /// there are new values.
+    pub generation: u64,
+}
+
+impl Meters {
+    pub fn new() -> Meters {
+        Meters {
+            input: LevelMeter::new(),
+            output: LevelMeter::new(),
+            input_channels: (0..MAX_CHANNELS).map(|_| LevelMeter::new()).collect(),
+            output_channels: (0..MAX_CHANNELS).map(|_| LevelMeter::new()).collect(),
+            channels: AtomicUsize::new(0),
+            mono: LevelMeter::new(),
+            mono_compatibility: AtomicFloat::new(1.0),
+            envelope: AtomicFloat::new(0.0),
+            envelope_smoothed: AtomicFloat::new(0.0),
+            mode: AtomicU8::new(MeterMode::Peak as u8),
+            smoothing_ms: AtomicFloat::new(DEFAULT_SMOOTHING_MS),
+            reset_pending: AtomicBool::new(false),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    pub fn mode(&self) -> MeterMode {
+        MeterMode::from_u8(self.mode.load(Ordering::Relaxed))
+    }
+
+    /// Change the ballistics of both meters. The meters restart from silence
+    /// on their next update, since each mode measures a different quantity.
+    pub fn set_mode(&self, mode: MeterMode) {
+        self.mode.store(mode as u8, Ordering::Relaxed);
+        self.reset_pending.store(true, Ordering::Relaxed);
+    }
+
+    /// The number of channels measured by the last update.
+    pub fn channels(&self) -> usize {
+        self.channels.load(Ordering::Relaxed)
+    }
+
+    /// The input meter of a single channel, or None if there is no such
+    /// channel.
+    pub fn input_channel(&self, channel: usize) -> Option<&LevelMeter> {
+        self.input_channels[..self.channels()].get(channel)
+    }
+
+    /// The output meter of a single channel, or None if there is no such
+    /// channel.
+    pub fn output_channel(&self, channel: usize) -> Option<&LevelMeter> {
+        self.output_channels[..self.channels()].get(channel)
+    }
+
+    /// The time constant of the smoothed values, in milliseconds.
+    pub fn smoothing(&self) -> f32 {
+        self.smoothing_ms.get()
+    }
+
+    /// Change the time constant of the smoothed values. With 0.0, they
+    /// follow the raw values exactly.
+    pub fn set_smoothing(&self, time_ms: f32) {
+        self.smoothing_ms.set(time_ms.max(0.0));
+    }
+
+    /// Update the meters with each channel's levels in the last block, the
+    /// output summed to mono, and the envelope at the block's end. This should
+    /// only be called from the audio thread. An empty block leaves the meters
+    /// as they are. Channels past `MAX_CHANNELS` are only included in the
+    /// combined meters.
+    pub fn update(
+        &self,
+        input: &[BlockLevels],
+        output: &[BlockLevels],
+        mono: MonoFold,
+        envelope: f32,
+        samples: usize,
+        sample_rate: f32,
+    ) {
+        if samples == 0 {
+            return;
+        }
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.generation.store(generation + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        let channels = input.len().min(output.len()).min(MAX_CHANNELS);
+        let reset = self.reset_pending.swap(false, Ordering::Relaxed);
+        if reset {
+            self.input.reset();
+            self.output.reset();
+            self.mono.reset();
+            self.envelope_smoothed.set(0.0);
+        }
+        // A channel which comes back after being removed starts from silence.
+        let first_reset = if reset { 0 } else { channels };
+        for meter in self.input_channels[first_reset..]
+            .iter()
+            .chain(self.output_channels[first_reset..].iter())
+        {
+            meter.reset();
+        }
+        self.channels.store(channels, Ordering::Relaxed);
+
+        let mode = self.mode();
+        let smoothing_ms = self.smoothing();
+        let combined = |levels: &[BlockLevels]| {
+            levels
+                .iter()
+                .copied()
+                .fold(BlockLevels::default(), BlockLevels::max)
+        };
+        self.input
+            .update(combined(input), mode, smoothing_ms, samples, sample_rate);
+        self.output
+            .update(combined(output), mode, smoothing_ms, samples, sample_rate);
+        for (meter, &levels) in self.input_channels.iter().zip(input.iter()) {
+            meter.update(levels, mode, smoothing_ms, samples, sample_rate);
+        }
+        for (meter, &levels) in self.output_channels.iter().zip(output.iter()) {
+            meter.update(levels, mode, smoothing_ms, samples, sample_rate);
+        }
+        self.mono
+            .update(mono.levels, mode, smoothing_ms, samples, sample_rate);
+        self.mono_compatibility.set(mono.compatibility);
+        self.envelope.set(envelope);
+        self.envelope_smoothed.set(smooth(
+            self.envelope_smoothed.get(),
+            envelope,
+            smoothing_ms,
+            samples,
+            sample_rate,
+        ));
+
+        self.generation.store(generation + 2, Ordering::Release);
+    }
+
+    /// Read every meter at once. If the audio thread is partway through an
+    /// update, this waits for it to finish, so the values never mix two
+    /// blocks.
+    pub fn snapshot(&self) -> MeterValues {
+        loop {
+            let generation = self.generation.load(Ordering::Acquire);
+            if generation & 1 == 0 {
+                let values = MeterValues {
+                    input: self.input.get(),
+                    output: self.output.get(),
+                    input_channels: read_channels(&self.input_channels),
+                    output_channels: read_channels(&self.output_channels),
+                    channels: self.channels(),
+                    mono: self.mono.get(),
+                    mono_compatibility: self.mono_compatibility.get(),
+                    envelope: self.envelope.get(),
+                    input_smoothed: self.input.get_smoothed(),
+                    output_smoothed: self.output.get_smoothed(),
+                    mono_smoothed: self.mono.get_smoothed(),
+                    envelope_smoothed: self.envelope_smoothed.get(),
+                    mode: self.mode(),
+                    generation: generation / 2,
+                };
+                fence(Ordering::Acquire);
+                if self.generation.load(Ordering::Relaxed) == generation {
+                    return values;
+                }
+            }
+            std::hint::spin_loop();
+        }
+    }
+}
+
+impl MeterValues {
+    /// How much quieter the output gets when summed to mono, in decibels, as
+    /// the difference between the mono meter and the loudest channel. This is
+    /// 0.0 when nothing is lost, and negative infinity when the channels
+    /// cancel out completely.
+    pub fn mono_difference_db(&self) -> f32 {
+        if self.output > 0.0 {
+            20.0 * (self.mono / self.output).log10()
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for Meters {
+    fn default() -> Meters {
+        Meters::new()
+    }
+}
+
+/// A single level meter, which follows the ballistics of a `MeterMode`.
+pub struct LevelMeter {
+    level: AtomicFloat,
+    // The number of samples left until the peak starts to decay.
+    hold: AtomicFloat,
+    // The running average for `MeterMode::Rms` (of the squared signal) and
+    // `MeterMode::Vu` (of the rectified signal).
+    average: AtomicFloat,
+    smoothed: AtomicFloat,
+}
+
+impl LevelMeter {
+    pub fn new() -> LevelMeter {
+        LevelMeter {
+            level: AtomicFloat::new(0.0),
+            hold: AtomicFloat::new(0.0),
+            average: AtomicFloat::new(0.0),
+            smoothed: AtomicFloat::new(0.0),
+        }
+    }
+
+    /// The current level, as a linear amplitude.
+    pub fn get(&self) -> f32 {
+        self.level.get()
+    }
+
+    /// The level smoothed over the meters' smoothing time constant.
+    pub fn get_smoothed(&self) -> f32 {
+        self.smoothed.get()
+    }
+
+    fn reset(&self) {
+        self.level.set(0.0);
+        self.hold.set(0.0);
+        self.average.set(0.0);
+        self.smoothed.set(0.0);
+    }
+
+    /// Update the meter with the levels of a block of `samples` samples, and
+    /// move the smoothed level towards the new level with the time constant
+    /// `smoothing_ms`.
+    pub fn update(
+        &self,
+        levels: BlockLevels,
+        mode: MeterMode,
+        smoothing_ms: f32,
+        samples: usize,
+        sample_rate: f32,
+    ) {
+        match mode {
+            MeterMode::Peak => self.update_peak(levels.peak, samples, sample_rate),
+            MeterMode::Rms => {
+                let mean_square =
+                    self.integrate(levels.mean_square, RMS_WINDOW_MS, samples, sample_rate);
+                self.level.set(mean_square.sqrt());
+            }
+            MeterMode::Vu => {
+                let mean_abs =
+                    self.integrate(levels.mean_abs, VU_TIME_CONSTANT_MS, samples, sample_rate);
+                self.level.set(mean_abs * VU_SINE_SCALE);
+            }
+        }
+        self.smoothed.set(smooth(
+            self.smoothed.get(),
+            self.level.get(),
+            smoothing_ms,
+            samples,
+            sample_rate,
+        ));
+    }
+
+    fn update_peak(&self, block_peak: f32, samples: usize, sample_rate: f32) {
+        let peak = self.level.get();
+        if block_peak >= peak {
+            self.level.set(block_peak);
+            self.hold.set(PEAK_HOLD_MS / 1000.0 * sample_rate);
+            return;
+        }
+
+        let hold = self.hold.get();
+        let decaying_samples = (samples as f32 - hold).max(0.0);
+        self.hold.set((hold - samples as f32).max(0.0));
+        let decayed = peak * peak_decay(sample_rate).powf(decaying_samples);
+        self.level.set(decayed.max(block_peak));
+    }
+
+    /// Move the running average towards a block's average, as a one-pole
+    /// lowpass with the time constant `time_ms` would over the whole block.
+    /// Returns the new running average.
+    fn integrate(&self, block_average: f32, time_ms: f32, samples: usize, sample_rate: f32) -> f32 {
+        let average = smooth(
+            self.average.get(),
+            block_average,
+            time_ms,
+            samples,
+            sample_rate,
+        );
+        self.average.set(average);
+        average
+    }
+}
+
+impl Default for LevelMeter {
+    fn default() -> LevelMeter {
+        LevelMeter::new()
+    }
+}
+
+/// The levels of one block of audio, which the meters are updated with.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockLevels {
+    /// The largest absolute value.
+    pub peak: f32,
+    /// The mean of the squared samples.
+    pub mean_square: f32,
+    /// The mean of the absolute values.
+    pub mean_abs: f32,
+}
+
+impl BlockLevels {
+    pub fn new(samples: &[f32]) -> BlockLevels {
+        if samples.is_empty() {
+            return BlockLevels::default();
+        }
+        let len = samples.len() as f32;
+        BlockLevels {
+            peak: peak(samples),
+            mean_square: samples.iter().map(|x| x * x).sum::<f32>() / len,
+            mean_abs: samples.iter().map(|x| x.abs()).sum::<f32>() / len,
+        }
+    }
+
+    /// The levels of whichever of the two blocks is louder, measured
+    /// separately for each level.
+    pub fn max(self, other: BlockLevels) -> BlockLevels {
+        BlockLevels {
+            peak: self.peak.max(other.peak),
+            mean_square: self.mean_square.max(other.mean_square),
+            mean_abs: self.mean_abs.max(other.mean_abs),
+        }
+    }
+}
+
+/// One block of output summed to mono, for checking that filtering the
+/// channels separately hasn't hurt how the output sounds in mono.
+#[derive(Debug, Clone, Copy)]
+pub struct MonoFold {
+    /// The levels of the mono sum, the mean of the channels.
+    pub levels: BlockLevels,
+    /// The energy of the mono sum as a fraction of the channels' mean energy.
+    /// This is 1.0 when the channels are identical, or for a single channel,
+    /// 0.5 when they are unrelated, and 0.0 when they cancel out completely.
+    /// A silent block scores 1.0, since nothing is lost.
+    pub compatibility: f32,
+}
+
+impl MonoFold {
+    /// Sum `channels` to mono. If the channels differ in length, only as many
+    /// samples as the shortest one has are used.
+    pub fn new(channels: &[&[f32]]) -> MonoFold {
+        let len = channels
+            .iter()
+            .map(|channel| channel.len())
+            .min()
+            .unwrap_or(0);
+        if len == 0 {
+            return MonoFold::default();
+        }
+        let scale = 1.0 / channels.len() as f32;
+        let mut levels = BlockLevels::default();
+        let mut channel_energy = 0.0;
+        for i in 0..len {
+            let mono = channels.iter().map(|channel| channel[i]).sum::<f32>() * scale;
+            levels.peak = levels.peak.max(mono.abs());
+            levels.mean_square += mono * mono;
+            levels.mean_abs += mono.abs();
+            channel_energy += channels
+                .iter()
+                .map(|channel| channel[i] * channel[i])
+                .sum::<f32>()
+                * scale;
+        }
+        let compatibility = if channel_energy > 1.0e-12 {
+            (levels.mean_square / channel_energy).min(1.0)
+        } else {
+            1.0
+        };
+        levels.mean_square /= len as f32;
+        levels.mean_abs /= len as f32;
+        MonoFold {
+            levels,
+            compatibility,
+        }
+    }
+}
+
+impl Default for MonoFold {
+    fn default() -> MonoFold {
+        MonoFold {
+            levels: BlockLevels::default(),
+            compatibility: 1.0,
+        }
+    }
+}
+
+/// The level of each of `meters`.
+fn read_channels(meters: &[LevelMeter]) -> [f32; MAX_CHANNELS] {
+    let mut levels = [0.0; MAX_CHANNELS];
+    for (level, meter) in levels.iter_mut().zip(meters.iter()) {
+        *level = meter.get();
+    }
+    levels
+}
+
+/// Move `previous` towards `target`, as a one-pole lowpass with the time
+/// constant `time_ms` would over a block of `samples` samples.
+pub fn smooth(previous: f32, target: f32, time_ms: f32, samples: usize, sample_rate: f32) -> f32 {
+    let decay = (-(samples as f32) / (time_ms / 1000.0 * sample_rate)).exp();
+    target + (previous - target) * decay
+}
+
+/// The amount a held peak is multiplied by each sample while it decays.
+pub fn peak_decay(sample_rate: f32) -> f32 {
+    db_to_gain(-PEAK_DECAY_DB_PER_SECOND / sample_rate)
+}
+
+/// The largest absolute value in `samples`.
+pub fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0, |peak, x| x.abs().max(peak))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// After a loud transient, a peak meter holds the peak for `PEAK_HOLD_MS`,
+    /// then lets it fall gradually over the silent blocks which follow.
+    #[test]
+    fn peak_holds_then_decays() {
+        const SAMPLE_RATE: f32 = 44100.0;
+        const BLOCK_SIZE: usize = 512;
+        let update = |meter: &LevelMeter, block: &[f32]| {
+            meter.update(
+                BlockLevels::new(block),
+                MeterMode::Peak,
+                DEFAULT_SMOOTHING_MS,
+                BLOCK_SIZE,
+                SAMPLE_RATE,
+            )
+        };
+        let meter = LevelMeter::new();
+        let mut transient = [0.0; BLOCK_SIZE];
+        transient[0] = 1.0;
+        update(&meter, &transient);
+        assert_eq!(meter.get(), 1.0);
+
+        let hold_blocks = (PEAK_HOLD_MS / 1000.0 * SAMPLE_RATE) as usize / BLOCK_SIZE;
+        let block_decay = db_to_gain(-PEAK_DECAY_DB_PER_SECOND * BLOCK_SIZE as f32 / SAMPLE_RATE);
+        let mut last = meter.get();
+        for block in 0..(2.0 * SAMPLE_RATE) as usize / BLOCK_SIZE {
+            update(&meter, &[0.0; BLOCK_SIZE]);
+            let level = meter.get();
+            if block < hold_blocks {
+                assert_eq!(level, 1.0, "block {}", block);
+            } else {
+                assert!(level < last, "block {} didn't decay", block);
+                assert!(
+                    level >= last * block_decay * 0.999,
+                    "block {} fell to {}",
+                    block,
+                    level
+                );
+            }
+            last = level;
+        }
+        assert!(last > 0.0);
+    }
+
+    /// The RMS meter settles on the RMS level of a 1 kHz tone.
+    #[test]
+    fn rms_settles_on_tone_level() {
+        const SAMPLE_RATE: f32 = 44100.0;
+        const BLOCK_SIZE: usize = 512;
+        const AMPLITUDE: f32 = 0.5;
+        let meters = Meters::new();
+        meters.set_mode(MeterMode::Rms);
+        let mut block = vec![0.0; BLOCK_SIZE];
+        let mut phase = 0.0f32;
+        // Two seconds is several times the RMS window.
+        for _ in 0..(2.0 * SAMPLE_RATE) as usize / BLOCK_SIZE {
+            for x in block.iter_mut() {
+                *x = AMPLITUDE * (phase * std::f32::consts::TAU).sin();
+                phase = (phase + 1000.0 / SAMPLE_RATE).fract();
+            }
+            let levels = BlockLevels::new(&block);
+            meters.update(
+                &[levels],
+                &[levels],
+                MonoFold::default(),
+                0.0,
+                BLOCK_SIZE,
+                SAMPLE_RATE,
+            );
+        }
+        let expected = AMPLITUDE / std::f32::consts::SQRT_2;
+        let level = meters.output.get();
+        assert!(
+            (level - expected).abs() <= expected * 0.01,
+            "expected {}, got {}",
+            expected,
+            level
+        );
+    }
+
+    /// Snapshots taken while another thread updates the meters never mix two
+    /// updates. The input and output always get the same levels, so a torn
+    /// snapshot would show them differing.
+    #[test]
+    fn snapshots_are_never_torn() {
+        const UPDATES: usize = 10_000;
+        let meters = Meters::new();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 1..=UPDATES {
+                    let levels = BlockLevels {
+                        peak: i as f32 / UPDATES as f32,
+                        ..Default::default()
+                    };
+                    meters.update(&[levels], &[levels], MonoFold::default(), 0.0, 64, 44100.0);
+                }
+            });
+            let mut last_generation = 0;
+            while last_generation != UPDATES as u64 {
+                let values = meters.snapshot();
+                assert_eq!(values.input, values.output);
+                assert!(values.generation >= last_generation);
+                last_generation = values.generation;
+            }
+        });
+    }
+
+    /// The smoothed meter values vary much less than the raw values when the
+    /// per-block values are noisy.
+    #[test]
+    fn smoothing_steadies_noisy_values() {
+        const BLOCKS: usize = 1000;
+        let meters = Meters::new();
+        let mut seed = 1u32;
+        let mut raw = Vec::with_capacity(BLOCKS);
+        let mut smoothed = Vec::with_capacity(BLOCKS);
+        for _ in 0..BLOCKS {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let envelope = (seed >> 8) as f32 / (1 << 24) as f32;
+            meters.update(
+                &[BlockLevels::default()],
+                &[BlockLevels::default()],
+                MonoFold::default(),
+                envelope,
+                512,
+                44100.0,
+            );
+            let values = meters.snapshot();
+            raw.push(values.envelope);
+            smoothed.push(values.envelope_smoothed);
+        }
+        // Skip the smoothed values' rise from silence.
+        let variance = |x: &[f32]| {
+            let x = &x[BLOCKS / 2..];
+            let mean = x.iter().sum::<f32>() / x.len() as f32;
+            x.iter().map(|x| (x - mean) * (x - mean)).sum::<f32>() / x.len() as f32
+        };
+        let (raw, smoothed) = (variance(&raw), variance(&smoothed));
+        assert!(
+            smoothed <= raw * 0.5,
+            "the variance only fell from {} to {}",
+            raw,
+            smoothed
+        );
+    }
+}
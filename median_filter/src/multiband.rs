@@ -0,0 +1,225 @@
+use crate::{
+    processor::{select_sample, DelayLine},
+    window::SortedWindow,
+    Parameters, MAX_WINDOW_SIZE,
+};
+
+/// The most bands the signal can be split into.
+pub const MAX_BANDS: usize = 3;
+
+/// A second order Butterworth lowpass filter.
+struct Lowpass {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    // The last two inputs and outputs.
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+    cutoff: f32,
+}
+
+impl Lowpass {
+    fn new() -> Lowpass {
+        Lowpass {
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+            cutoff: 0.0,
+        }
+    }
+
+    fn set_cutoff(&mut self, cutoff: f32, sample_rate: f32) {
+        #[allow(clippy::float_cmp)]
+        if cutoff == self.cutoff {
+            return;
+        }
+        self.cutoff = cutoff;
+        // The lowpass from the Audio EQ Cookbook, with a Q of 1/√2.
+        let cutoff = cutoff.min(sample_rate * 0.49);
+        let omega = std::f32::consts::TAU * cutoff / sample_rate;
+        let alpha = omega.sin() / std::f32::consts::SQRT_2;
+        let cos = omega.cos();
+        let a0 = 1.0 + alpha;
+        self.b0 = (1.0 - cos) / 2.0 / a0;
+        self.b1 = (1.0 - cos) / a0;
+        self.b2 = (1.0 - cos) / 2.0 / a0;
+        self.a1 = -2.0 * cos / a0;
+        self.a2 = (1.0 - alpha) / a0;
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+
+    fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.x2 = 0.0;
+        self.y1 = 0.0;
+        self.y2 = 0.0;
+    }
+}
+
+/// Splits a signal into low, mid and high bands which always sum back to the
+/// original signal. Each band is taken out of what is left after the bands
+/// below it, so the split is perfectly transparent, at the cost of shallower
+/// slopes than a Linkwitz-Riley crossover.
+pub struct BandSplitter {
+    lowpasses: [Lowpass; MAX_BANDS - 1],
+}
+
+impl BandSplitter {
+    pub fn new() -> BandSplitter {
+        BandSplitter {
+            lowpasses: [Lowpass::new(), Lowpass::new()],
+        }
+    }
+
+    /// Set the low/mid and mid/high crossover frequencies, in Hz.
+    pub fn set_crossovers(&mut self, crossovers: [f32; MAX_BANDS - 1], sample_rate: f32) {
+        for (lowpass, &cutoff) in self.lowpasses.iter_mut().zip(crossovers.iter()) {
+            lowpass.set_cutoff(cutoff, sample_rate);
+        }
+    }
+
+    /// Split `x` into low, mid and high bands. With two bands, the signal is
+    /// split at the low crossover and the mid band is silent.
+    pub fn split(&mut self, x: f32, bands: usize) -> [f32; MAX_BANDS] {
+        let low = self.lowpasses[0].process(x);
+        let rest = x - low;
+        if bands < MAX_BANDS {
+            [low, 0.0, rest]
+        } else {
+            let mid = self.lowpasses[1].process(rest);
+            [low, mid, rest - mid]
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for lowpass in self.lowpasses.iter_mut() {
+            lowpass.reset();
+        }
+    }
+}
+
+impl Default for BandSplitter {
+    fn default() -> BandSplitter {
+        BandSplitter::new()
+    }
+}
+
+/// The filter for a single band, with its own window size and wet/dry.
+struct Band {
+    filter: SortedWindow,
+    window_size: usize,
+    history: DelayLine,
+    // The band's output, delayed to line up with the band with the largest
+    // window.
+    output: DelayLine,
+}
+
+impl Band {
+    fn new(window_size: usize) -> Band {
+        Band {
+            filter: SortedWindow::with_capacity(window_size, MAX_WINDOW_SIZE),
+            window_size,
+            history: DelayLine::new(),
+            output: DelayLine::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.filter.clear();
+        self.history.clear();
+        self.output.clear();
+    }
+
+    /// Filter a sample of this band, mixing it with the band's dry signal by
+    /// `wet_dry`. The result lags the input by half of `max_window`.
+    fn process(&mut self, x: f32, wet_dry: f32, max_window: usize, params: &Parameters) -> f32 {
+        self.history.push(x);
+        self.filter.consume(x);
+        let lag = (self.window_size - 1) / 2;
+        let wet = select_sample(&self.filter, &self.history, self.window_size, params);
+        let dry = self.history.get(lag);
+        self.output.push(dry * (1.0 - wet_dry) + wet * wet_dry);
+        self.output.get((max_window - 1) / 2 - lag)
+    }
+}
+
+/// Splits the signal into bands and median filters each band separately, so
+/// that, for example, clicks can be removed from the highs without smearing
+/// the lows.
+pub struct Multiband {
+    splitter: BandSplitter,
+    bands: [Band; MAX_BANDS],
+    sample_rate: f32,
+}
+
+impl Multiband {
+    pub fn new(params: &Parameters) -> Multiband {
+        let windows = params.band_windows();
+        Multiband {
+            splitter: BandSplitter::new(),
+            bands: [
+                Band::new(windows[0]),
+                Band::new(windows[1]),
+                Band::new(windows[2]),
+            ],
+            sample_rate: 44100.0,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn set_parameters(&mut self, params: &Parameters) {
+        self.splitter
+            .set_crossovers(params.crossovers(), self.sample_rate);
+        for (band, &window_size) in self.bands.iter_mut().zip(params.band_windows().iter()) {
+            if band.window_size != window_size {
+                band.filter.set_window_size(window_size);
+                band.window_size = window_size;
+            }
+        }
+    }
+
+    /// Filter a sample, returning the sum of the filtered bands. The result
+    /// lags the input by half of the largest band window.
+    pub fn process(&mut self, x: f32, params: &Parameters) -> f32 {
+        let split = self.splitter.split(x, params.bands);
+        let max_window = params.max_band_window();
+        let wet_dry = params.band_wet_dry();
+        let mut sum = 0.0;
+        for (i, band) in self.bands.iter_mut().enumerate() {
+            if params.band_is_active(i) {
+                sum += band.process(split[i], wet_dry[i], max_window, params);
+            }
+        }
+        sum
+    }
+
+    pub fn reset(&mut self) {
+        self.splitter.reset();
+        for band in self.bands.iter_mut() {
+            band.clear();
+        }
+    }
+}
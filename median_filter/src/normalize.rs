@@ -0,0 +1,115 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Measures the peak of the input over a short capture, so that `InputGain`
+/// can be set to bring the loudest sample to a target level. The audio thread
+/// only records the peak, and the gain is worked out from it elsewhere, so
+/// this is shared between threads like `Meters`.
+///
+/// The samples left to capture and the peak so far are packed into a single
+/// atomic, so that a capture restarted by `start` while the audio thread is
+/// recording a block can never be mixed up with the capture before it.
+pub struct InputNormalizer {
+    // The samples left to capture in the upper 32 bits, and the bits of the
+    // loudest sample captured so far in the lower 32 bits. Zero samples left
+    // means the capture finished, and `IDLE` that none was ever started.
+    state: AtomicU64,
+}
+
+impl InputNormalizer {
+    // The samples left before any capture is started.
+    const IDLE: u32 = u32::MAX;
+
+    pub fn new() -> InputNormalizer {
+        InputNormalizer {
+            state: AtomicU64::new(pack(Self::IDLE, 0.0)),
+        }
+    }
+
+    /// Start capturing the next `samples` samples of input, forgetting any
+    /// earlier capture.
+    pub fn start(&self, samples: usize) {
+        let samples = samples.clamp(1, Self::IDLE as usize - 1) as u32;
+        self.state.store(pack(samples, 0.0), Ordering::Release);
+    }
+
+    /// Record the peak of a block of input, if capturing. Called from the
+    /// audio thread with the raw input of every channel.
+    pub fn capture(&self, inputs: &[&[f32]]) {
+        let len = inputs.iter().map(|input| input.len()).min().unwrap_or(0);
+        // If `start` is called while the block is measured, the exchange
+        // fails and the block is measured again for the new capture.
+        let _ = self
+            .state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |state| {
+                let (remaining, peak) = unpack(state);
+                if remaining == 0 || remaining == Self::IDLE {
+                    return None;
+                }
+                let samples = len.min(remaining as usize);
+                let peak = inputs
+                    .iter()
+                    .flat_map(|input| input[..samples].iter())
+                    .filter(|x| x.is_finite())
+                    .fold(peak, |peak, x| peak.max(x.abs()));
+                Some(pack(remaining - samples as u32, peak))
+            });
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        let (remaining, _) = unpack(self.state.load(Ordering::Acquire));
+        remaining != 0 && remaining != Self::IDLE
+    }
+
+    /// The peak of the last finished capture, or None if no capture has
+    /// finished since the last `start`.
+    pub fn peak(&self) -> Option<f32> {
+        match unpack(self.state.load(Ordering::Acquire)) {
+            (0, peak) => Some(peak),
+            _ => None,
+        }
+    }
+
+    /// The gain, in decibels, which brings the peak of the last finished
+    /// capture to `target_db`. Returns None if no capture has finished, or if
+    /// the input was silent.
+    pub fn gain_db(&self, target_db: f32) -> Option<f32> {
+        let peak = self.peak()?;
+        if peak <= 0.0 {
+            return None;
+        }
+        Some(target_db - 20.0 * peak.log10())
+    }
+}
+
+impl Default for InputNormalizer {
+    fn default() -> InputNormalizer {
+        InputNormalizer::new()
+    }
+}
+
+fn pack(remaining: u32, peak: f32) -> u64 {
+    (remaining as u64) << 32 | peak.to_bits() as u64
+}
+
+fn unpack(state: u64) -> (u32, f32) {
+    ((state >> 32) as u32, f32::from_bits(state as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_forgets_earlier_capture() {
+        let normalizer = InputNormalizer::new();
+        assert_eq!(normalizer.peak(), None);
+        normalizer.start(4);
+        normalizer.capture(&[&[1.0, -1.0]]);
+        assert!(normalizer.is_capturing());
+        normalizer.start(2);
+        assert_eq!(normalizer.peak(), None);
+        normalizer.capture(&[&[0.25, -0.5, 1.0]]);
+        assert!(!normalizer.is_capturing());
+        assert_eq!(normalizer.peak(), Some(0.5));
+    }
+}
@@ -0,0 +1,172 @@
+/// How many times the sample rate the oversampled signal runs at.
+pub const OVERSAMPLE_FACTOR: usize = 4;
+
+/// The length of the interpolation and decimation filters, at the oversampled
+/// rate. One more than a multiple of `OVERSAMPLE_FACTOR`, so that the filters
+/// delay the signal by a whole number of samples at the original rate.
+const TAPS: usize = 24 * OVERSAMPLE_FACTOR + 1;
+
+/// How many samples, at the original rate, `Oversampler::process` delays its
+/// input by.
+pub const OVERSAMPLE_DELAY: usize = (TAPS - 1) / OVERSAMPLE_FACTOR;
+
+// The number of input samples the interpolation filter spans.
+const INPUT_TAPS: usize = TAPS.div_ceil(OVERSAMPLE_FACTOR);
+
+/// Runs a nonlinear function at `OVERSAMPLE_FACTOR` times the sample rate, so
+/// that the harmonics it adds above the original Nyquist frequency are
+/// filtered out instead of folding back down as aliasing. Both filters are
+/// linear phase, so the output is the input delayed by exactly
+/// `OVERSAMPLE_DELAY` samples.
+pub struct Oversampler {
+    // A lowpass at the original Nyquist frequency, shared by the
+    // interpolation and decimation filters.
+    taps: [f32; TAPS],
+    // The last `INPUT_TAPS` input samples, and the last `TAPS` oversampled
+    // samples after the nonlinear function.
+    input: [f32; INPUT_TAPS],
+    input_position: usize,
+    shaped: [f32; TAPS],
+    shaped_position: usize,
+}
+
+impl Oversampler {
+    pub fn new() -> Oversampler {
+        // A windowed sinc, using a Blackman window.
+        let center = (TAPS - 1) as f32 / 2.0;
+        let cutoff = 0.5 / OVERSAMPLE_FACTOR as f32;
+        let mut taps = [0.0; TAPS];
+        for (i, tap) in taps.iter_mut().enumerate() {
+            let t = i as f32 - center;
+            let sinc = if 2 * i == TAPS - 1 {
+                2.0 * cutoff
+            } else {
+                (std::f32::consts::TAU * cutoff * t).sin() / (std::f32::consts::PI * t)
+            };
+            let phase = std::f32::consts::TAU * i as f32 / (TAPS - 1) as f32;
+            let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+            *tap = sinc * window;
+        }
+        // Normalize to unity gain at DC.
+        let sum: f32 = taps.iter().sum();
+        for tap in taps.iter_mut() {
+            *tap /= sum;
+        }
+        Oversampler {
+            taps,
+            input: [0.0; INPUT_TAPS],
+            input_position: 0,
+            shaped: [0.0; TAPS],
+            shaped_position: 0,
+        }
+    }
+
+    /// Run `x` through `shape` at the oversampled rate, returning the output
+    /// from `OVERSAMPLE_DELAY` samples ago.
+    pub fn process(&mut self, x: f32, shape: impl Fn(f32) -> f32) -> f32 {
+        self.input_position = (self.input_position + 1) % INPUT_TAPS;
+        self.input[self.input_position] = x;
+
+        let mut output = 0.0;
+        for phase in 0..OVERSAMPLE_FACTOR {
+            // Interpolate by filtering the input padded with zeros. Only every
+            // `OVERSAMPLE_FACTOR`th tap lands on an input sample, and the gain
+            // makes up for the zeros.
+            let mut upsampled = 0.0;
+            for (j, tap) in self.taps[phase..]
+                .iter()
+                .step_by(OVERSAMPLE_FACTOR)
+                .enumerate()
+            {
+                let index = (self.input_position + INPUT_TAPS - j) % INPUT_TAPS;
+                upsampled += tap * self.input[index];
+            }
+            let upsampled = upsampled * OVERSAMPLE_FACTOR as f32;
+
+            self.shaped_position = (self.shaped_position + 1) % TAPS;
+            self.shaped[self.shaped_position] = shape(upsampled);
+
+            // Decimate by keeping the first of each `OVERSAMPLE_FACTOR`
+            // samples, which lines up with the input sample.
+            if phase == 0 {
+                output = self
+                    .taps
+                    .iter()
+                    .enumerate()
+                    .map(|(k, tap)| tap * self.shaped[(self.shaped_position + TAPS - k) % TAPS])
+                    .sum();
+            }
+        }
+        output
+    }
+
+    pub fn reset(&mut self) {
+        self.input = [0.0; INPUT_TAPS];
+        self.shaped = [0.0; TAPS];
+    }
+}
+
+impl Default for Oversampler {
+    fn default() -> Oversampler {
+        Oversampler::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::saturate;
+
+    /// Oversampling cuts the aliasing from saturating a bright tone.
+    #[test]
+    fn cuts_aliasing() {
+        const LENGTH: usize = 4096;
+        // Around 5 kHz at 44.1 kHz. The 3rd harmonic is below Nyquist, but
+        // the 5th and up fold back down as aliasing, away from the harmonics.
+        const BIN: usize = 465;
+        const DRIVE: f32 = 24.0;
+        let phase = |i: usize, bin: usize| {
+            std::f32::consts::TAU * (bin * i % LENGTH) as f32 / LENGTH as f32
+        };
+        // The fraction of the output's energy that isn't in the tone's
+        // harmonics below Nyquist, in decibels.
+        let aliasing_db = |output: &[f32]| {
+            let total: f32 = output.iter().map(|x| x * x).sum();
+            let harmonics: f32 = [BIN, 3 * BIN]
+                .iter()
+                .map(|&bin| {
+                    let (re, im) =
+                        output
+                            .iter()
+                            .enumerate()
+                            .fold((0.0, 0.0), |(re, im), (i, x)| {
+                                (re + x * phase(i, bin).cos(), im - x * phase(i, bin).sin())
+                            });
+                    2.0 * (re * re + im * im) / LENGTH as f32
+                })
+                .sum();
+            10.0 * ((total - harmonics).max(1.0e-12) / total).log10()
+        };
+
+        // Run two periods of the tone, measuring the second, once the
+        // filters are full.
+        let mut oversampler = Oversampler::new();
+        let (naive, oversampled): (Vec<f32>, Vec<f32>) = (0..2 * LENGTH)
+            .map(|i| {
+                let x = 0.9 * phase(i, BIN).sin();
+                (
+                    saturate(x, DRIVE),
+                    oversampler.process(x, |x| saturate(x, DRIVE)),
+                )
+            })
+            .skip(LENGTH)
+            .unzip();
+        let (naive, oversampled) = (aliasing_db(&naive), aliasing_db(&oversampled));
+        assert!(
+            oversampled <= naive - 20.0,
+            "aliasing is {} dB oversampled and {} dB without",
+            oversampled,
+            naive
+        );
+    }
+}
@@ -0,0 +1,1342 @@
+use std::sync::atomic::Ordering;
+
+use vst::{host::Host, util::AtomicFloat};
+
+use common::{
+    ease_in_expo, make_bool_strings, make_rounded_strings, make_strings, mapping::Mapping,
+    quantize_index, smoothing::Smoothing,
+};
+
+use crate::{
+    gain::{parse_gain, Gain, GainDisplay, GainRange},
+    latency::LatencyUpdates,
+    lfo::{LfoShape, NoteDivision},
+    limiter::LOOKAHEAD_SAMPLES,
+    multiband::MAX_BANDS,
+    oversample::OVERSAMPLE_DELAY,
+    processor::Stage,
+    CC_FEEDBACK_INTERVAL_MS, CEILING_RANGE, DRIVE_RANGE, GATE_STEPS, INPUT_GAIN_RANGE,
+    MAX_BIT_DEPTH, MAX_DC_TRIM, MAX_ENVELOPE_WINDOW_MS, MAX_PERIODS, MAX_SAMPLE_RATE_REDUCTION,
+    MAX_STAGGER, MAX_STEREO_OFFSET, MAX_WINDOW_FOLLOW_MS, MAX_WINDOW_HZ, MAX_WINDOW_MS,
+    MAX_WINDOW_SAMPLES, MAX_WINDOW_SIZE, MIN_ENVELOPE_WINDOW_MS, MIN_HAMPEL_K,
+    MIN_WINDOW_FOLLOW_MS, MIN_WINDOW_HZ, OUTPUT_GAIN_RANGE, SHELF_GAIN_RANGE,
+    SILENCE_THRESHOLD_RANGE, THRESHOLD_RANGE,
+};
+
+/// How the filtered signal is produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mode {
+    /// Output the median of the window.
+    Smooth,
+    /// Output the input, except for samples that are further than `Threshold`
+    /// from the median of the window, which are replaced with the median.
+    DeClick,
+    /// Like `DeClick`, but the threshold is `Hampel K` times the spread of the
+    /// window (its median absolute deviation), so that it adapts to the
+    /// signal. This is a Hampel filter.
+    Hampel,
+}
+
+impl Mode {
+    fn from_normalized(x: f32) -> Mode {
+        match quantize_index(x, 3) {
+            0 => Mode::Smooth,
+            1 => Mode::DeClick,
+            _ => Mode::Hampel,
+        }
+    }
+}
+
+/// The unit the window size is set in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WindowUnit {
+    Samples,
+    /// The window is set as the lowest frequency it smooths. A window of N
+    /// samples smooths away detail faster than about `sample_rate / N` Hz.
+    Hertz,
+    Milliseconds,
+}
+
+impl WindowUnit {
+    fn from_normalized(x: f32) -> WindowUnit {
+        // Milliseconds stays at 1.0, where it was before Hertz was added, so
+        // that older presets keep their unit.
+        match quantize_index(x, 3) {
+            0 => WindowUnit::Samples,
+            1 => WindowUnit::Hertz,
+            _ => WindowUnit::Milliseconds,
+        }
+    }
+
+    /// Convert a normalized window size to a size in samples. This may be
+    /// larger than `MAX_WINDOW_SIZE`.
+    fn to_samples(self, x: f32, sample_rate: f32) -> usize {
+        let window_size = match self {
+            WindowUnit::Samples => (x * MAX_WINDOW_SAMPLES as f32) as usize,
+            WindowUnit::Hertz => (sample_rate / window_hz(x)).round() as usize,
+            WindowUnit::Milliseconds => (x * MAX_WINDOW_MS / 1000.0 * sample_rate) as usize,
+        };
+        window_size.max(1)
+    }
+}
+
+impl std::fmt::Display for WindowUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WindowUnit::Samples => write!(f, "Samples"),
+            WindowUnit::Hertz => write!(f, "Hz"),
+            WindowUnit::Milliseconds => write!(f, "ms"),
+        }
+    }
+}
+
+/// The frequency, in Hz, of a normalized window size in `WindowUnit::Hertz`.
+/// Turning the knob up lowers the frequency, so that, as in the other units,
+/// it makes the window larger.
+fn window_hz(x: f32) -> f32 {
+    MAX_WINDOW_HZ * (MIN_WINDOW_HZ / MAX_WINDOW_HZ).powf(x)
+}
+
+/// How the window is filled before any input has arrived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EdgeMode {
+    /// Fill the window with silence.
+    ZeroPad,
+    /// Fill the window with copies of the first sample.
+    Replicate,
+    /// Fill the window with the first few samples, mirrored around the first
+    /// sample.
+    Reflect,
+}
+
+impl EdgeMode {
+    fn from_normalized(x: f32) -> EdgeMode {
+        match quantize_index(x, 3) {
+            0 => EdgeMode::ZeroPad,
+            1 => EdgeMode::Replicate,
+            _ => EdgeMode::Reflect,
+        }
+    }
+}
+
+impl std::fmt::Display for EdgeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EdgeMode::ZeroPad => write!(f, "Zero Pad"),
+            EdgeMode::Replicate => write!(f, "Replicate"),
+            EdgeMode::Reflect => write!(f, "Reflect"),
+        }
+    }
+}
+
+/// Which pair of channels is filtered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StereoMode {
+    /// Filter the left and right channels, both using `WindowSize`.
+    LeftRight,
+    /// Filter the mid (L + R) and side (L - R) channels, using `MidWindow` and
+    /// `SideWindow`. The wet/dry knobs then apply to mid and side.
+    MidSide,
+}
+
+impl StereoMode {
+    fn from_normalized(x: f32) -> StereoMode {
+        match quantize_index(x, 2) {
+            0 => StereoMode::LeftRight,
+            _ => StereoMode::MidSide,
+        }
+    }
+}
+
+impl std::fmt::Display for StereoMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StereoMode::LeftRight => write!(f, "L/R"),
+            StereoMode::MidSide => write!(f, "M/S"),
+        }
+    }
+}
+
+/// How changes to `OutputGain` are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GainMode {
+    /// Ramp smoothly to the new gain over the course of a block.
+    PerSample,
+    /// Jump to the new gain at the start of each block. This skips the ramp,
+    /// but large changes can be heard as zipper noise.
+    PerBlock,
+}
+
+impl GainMode {
+    fn from_normalized(x: f32) -> GainMode {
+        match quantize_index(x, 2) {
+            0 => GainMode::PerSample,
+            _ => GainMode::PerBlock,
+        }
+    }
+}
+
+impl std::fmt::Display for GainMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GainMode::PerSample => write!(f, "Per Sample"),
+            GainMode::PerBlock => write!(f, "Per Block"),
+        }
+    }
+}
+
+/// Whether a stereo input is filtered as mono, filtering once and copying the
+/// result to both outputs. This saves work when a mono source is on a stereo
+/// bus, and guarantees that both outputs are identical.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MonoInput {
+    /// Always filter the channels separately.
+    Off,
+    /// Filter as mono whenever both channels are identical and set up the same
+    /// way.
+    Auto,
+    /// Always sum the channels to mono. Only the left channel's settings are
+    /// used.
+    Forced,
+}
+
+impl MonoInput {
+    fn from_normalized(x: f32) -> MonoInput {
+        match quantize_index(x, 3) {
+            0 => MonoInput::Off,
+            1 => MonoInput::Auto,
+            _ => MonoInput::Forced,
+        }
+    }
+}
+
+impl std::fmt::Display for MonoInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MonoInput::Off => write!(f, "Off"),
+            MonoInput::Auto => write!(f, "Auto"),
+            MonoInput::Forced => write!(f, "Forced"),
+        }
+    }
+}
+
+/// How `Limit` keeps the output under the ceiling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LimitMode {
+    /// Smoothly compress samples above the knee. This adds no latency, but
+    /// distorts loud peaks.
+    SoftClip,
+    /// Turn the gain down ahead of loud peaks. This doesn't distort, but adds
+    /// `LOOKAHEAD_SAMPLES` of latency.
+    Lookahead,
+}
+
+impl LimitMode {
+    fn from_normalized(x: f32) -> LimitMode {
+        match quantize_index(x, 2) {
+            0 => LimitMode::SoftClip,
+            _ => LimitMode::Lookahead,
+        }
+    }
+}
+
+impl std::fmt::Display for LimitMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitMode::SoftClip => write!(f, "Soft Clip"),
+            LimitMode::Lookahead => write!(f, "Lookahead"),
+        }
+    }
+}
+
+/// Outputs which help check what the filter is doing, instead of the normal
+/// output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Diagnostic {
+    /// The normal output.
+    Off,
+    /// A null test: the dry signal minus the normal output, turned up by
+    /// `NULL_MAKEUP_DB`. This is silent when the filter has no effect.
+    Null,
+}
+
+impl Diagnostic {
+    fn from_normalized(x: f32) -> Diagnostic {
+        match quantize_index(x, 2) {
+            0 => Diagnostic::Off,
+            _ => Diagnostic::Null,
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::Off => write!(f, "Off"),
+            Diagnostic::Null => write!(f, "Null Test"),
+        }
+    }
+}
+
+/// How the wet/dry knobs map to the amount of filtered signal. The knobs still
+/// store and display their linear position; the curve is applied when mixing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MixCurve {
+    Linear,
+    /// Changes slowly near fully dry and fully wet, and quickly in between.
+    SCurve,
+    /// Stays mostly dry for most of the knob's range, giving finer control over
+    /// small amounts of filtering.
+    Exponential,
+}
+
+impl MixCurve {
+    fn from_normalized(x: f32) -> MixCurve {
+        match quantize_index(x, 3) {
+            0 => MixCurve::Linear,
+            1 => MixCurve::SCurve,
+            _ => MixCurve::Exponential,
+        }
+    }
+
+    /// Map a wet/dry knob position to the amount of filtered signal.
+    pub(crate) fn apply(self, x: f32) -> f32 {
+        match self {
+            MixCurve::Linear => x,
+            MixCurve::SCurve => x * x * (3.0 - 2.0 * x),
+            MixCurve::Exponential => ease_in_expo(x),
+        }
+    }
+}
+
+impl std::fmt::Display for MixCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MixCurve::Linear => write!(f, "Linear"),
+            MixCurve::SCurve => write!(f, "S-Curve"),
+            MixCurve::Exponential => write!(f, "Exponential"),
+        }
+    }
+}
+
+/// Which part of the signal the filter smooths. The signal is split into its
+/// transients and the sustain between them, and only the chosen part goes
+/// through the filter. The other part is added back to the filtered signal
+/// untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Target {
+    Full,
+    /// Smooth the tails of sounds, keeping their attacks sharp.
+    SustainOnly,
+    /// Smooth the attacks of sounds, leaving their tails alone.
+    TransientOnly,
+}
+
+impl Target {
+    fn from_normalized(x: f32) -> Target {
+        match quantize_index(x, 3) {
+            0 => Target::Full,
+            1 => Target::SustainOnly,
+            _ => Target::TransientOnly,
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Full => write!(f, "Full"),
+            Target::SustainOnly => write!(f, "Sustain Only"),
+            Target::TransientOnly => write!(f, "Transient Only"),
+        }
+    }
+}
+
+/// Which value is used as the median when the window holds an even number of
+/// samples, and so has two middle samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TieBreak {
+    /// The mean of the two middle samples. This matches most reference
+    /// implementations, such as `numpy.median`.
+    Average,
+    /// The smaller of the two middle samples.
+    Lower,
+    /// The larger of the two middle samples.
+    Upper,
+}
+
+impl TieBreak {
+    fn from_normalized(x: f32) -> TieBreak {
+        match quantize_index(x, 3) {
+            0 => TieBreak::Average,
+            1 => TieBreak::Lower,
+            _ => TieBreak::Upper,
+        }
+    }
+}
+
+impl std::fmt::Display for TieBreak {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TieBreak::Average => write!(f, "Average"),
+            TieBreak::Lower => write!(f, "Lower"),
+            TieBreak::Upper => write!(f, "Upper"),
+        }
+    }
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mode::Smooth => write!(f, "Smooth"),
+            Mode::DeClick => write!(f, "De-Click"),
+            Mode::Hampel => write!(f, "Hampel"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Parameters {
+    pub(crate) window_size: Window,
+    pub(crate) wet_dry_left: f32,
+    // Equal to `wet_dry_left` when `link_wet_dry` is on.
+    pub(crate) wet_dry_right: f32,
+    pub(crate) link_wet_dry: bool,
+    pub(crate) compensate_dry: bool,
+    pub(crate) realtime_cap: usize,
+    pub(crate) mode: Mode,
+    pub(crate) threshold: Gain,
+    pub(crate) limit: bool,
+    pub(crate) ceiling: Gain,
+    pub(crate) bypass: bool,
+    pub(crate) force_odd: bool,
+    pub(crate) window_unit: WindowUnit,
+    pub(crate) constant_loudness: bool,
+    pub(crate) edge_mode: EdgeMode,
+    pub(crate) full_wet: bool,
+    pub(crate) drive: Gain,
+    pub(crate) saturate_before_filter: bool,
+    pub(crate) stereo_mode: StereoMode,
+    pub(crate) mid_window: Window,
+    pub(crate) side_window: Window,
+    pub(crate) tie_break: TieBreak,
+    // Whether samples quieter than `silence_threshold` are left out of the
+    // median, so that a window which is partly silence follows the rest of
+    // the signal instead of being pulled towards zero.
+    pub(crate) smart_window: bool,
+    pub(crate) silence_threshold: Gain,
+    pub(crate) hampel_k: f32,
+    pub(crate) latency_updates: LatencyUpdates,
+    pub(crate) transient_preserve: f32,
+    pub(crate) output_gain: Gain,
+    // A constant offset added to the output, after the output gain.
+    pub(crate) dc_trim: f32,
+    pub(crate) gain_mode: GainMode,
+    // How far each channel's wet signal is pulled towards the mono sum of
+    // both. Only used in left/right mode, since mid/side is already mono.
+    pub(crate) recorrelate: f32,
+    // How much of each channel is mixed into the other before filtering. Only
+    // the filter's input is mixed, so the dry signal stays as it was. Like
+    // `recorrelate`, this is only used in left/right mode.
+    pub(crate) crossfeed: f32,
+    pub(crate) mix_curve: MixCurve,
+    pub(crate) gain_display: GainDisplay,
+    pub(crate) diagnostic: Diagnostic,
+    pub(crate) limit_mode: LimitMode,
+    // The number of bands the signal is split into. With one band, the
+    // signal isn't split.
+    pub(crate) bands: usize,
+    // In Hz
+    pub(crate) low_crossover: f32,
+    // In Hz
+    pub(crate) high_crossover: f32,
+    pub(crate) low_band_window: Window,
+    pub(crate) mid_band_window: Window,
+    pub(crate) high_band_window: Window,
+    pub(crate) low_band_wet_dry: f32,
+    pub(crate) mid_band_wet_dry: f32,
+    pub(crate) high_band_wet_dry: f32,
+    pub(crate) bit_crush: u32,
+    // The factor the sample rate is divided by.
+    pub(crate) sample_rate_reduce: usize,
+    pub(crate) dither: bool,
+    pub(crate) mono_input: MonoInput,
+    // Whether the window follows the pitch of the input, and how many periods
+    // of it the window holds. The window changes with the input, so the
+    // latency reported to the host still follows `window_size`.
+    pub(crate) pitch_sync: bool,
+    pub(crate) periods: usize,
+    // Whether the envelope meter is measured, and the length of its window in
+    // milliseconds.
+    pub(crate) envelope: bool,
+    pub(crate) envelope_window: f32,
+    // Whether every parameter lags behind the host's value.
+    pub(crate) vintage_feel: bool,
+    pub(crate) lfo_rate: NoteDivision,
+    pub(crate) lfo_depth: f32,
+    pub(crate) lfo_shape: LfoShape,
+    // How many samples the right channel's filter input is delayed by.
+    pub(crate) stereo_offset: usize,
+    // Whether the output is scaled by the gate pattern, the length of each
+    // step, and the gain of each step.
+    pub(crate) gate: bool,
+    pub(crate) gate_rate: NoteDivision,
+    pub(crate) gate_steps: [f32; GATE_STEPS],
+    // Whether the bypassed signal is brought to the loudness of the processed
+    // signal.
+    pub(crate) bypass_gain_match: bool,
+    // The gain and frequency, in Hz, of the high shelf on the wet signal.
+    pub(crate) shelf_gain: Gain,
+    pub(crate) shelf_freq: f32,
+    // The gain applied to the input, before anything else.
+    pub(crate) input_gain: Gain,
+    // Whether the wet signal is inverted and added to the dry signal, leaving
+    // only what the filter removed.
+    pub(crate) wet_invert: bool,
+    pub(crate) target: Target,
+    // Whether the `ConstantLoudness` makeup is backed off to keep the filtered
+    // signal under the ceiling.
+    pub(crate) safe_makeup: bool,
+    // How far transients shrink the window, from 0.0 (not at all) to 1.0,
+    // and how fast, in milliseconds, the window shrinks and grows back.
+    pub(crate) dynamic_window: f32,
+    pub(crate) window_attack: f32,
+    pub(crate) window_release: f32,
+    // How much longer the right channel's window is than the left's, in
+    // percent.
+    pub(crate) stagger: f32,
+    // Whether the saturation runs at `OVERSAMPLE_FACTOR` times the sample
+    // rate, reducing aliasing at high drive.
+    pub(crate) oversample_drive: bool,
+    // Whether the output fades in over `SOFT_START_MS` after the plugin is
+    // loaded or resumed.
+    pub(crate) soft_start: bool,
+}
+
+impl Parameters {
+    /// The window sizes of the left and right channels, or of the mid and side
+    /// channels in mid/side mode. In left/right mode, `Stagger` lengthens the
+    /// right channel's window a little, so that the channels are filtered
+    /// slightly differently, which decorrelates them.
+    pub(crate) fn window_sizes(&self) -> (usize, usize) {
+        match self.stereo_mode {
+            StereoMode::LeftRight => {
+                let left = self.window_size.samples;
+                let right = (left as f32 * (1.0 + self.stagger / 100.0)).round() as usize;
+                (left, force_odd(right.min(MAX_WINDOW_SIZE), self.force_odd))
+            }
+            StereoMode::MidSide => (self.mid_window.samples, self.side_window.samples),
+        }
+    }
+
+    /// Whether the left and right channels are set up to filter the same input
+    /// the same way, so that a mono input only needs to be filtered once.
+    pub(crate) fn channels_match(&self) -> bool {
+        let (left, right) = self.window_sizes();
+        #[allow(clippy::float_cmp)]
+        let wet_dry_matches = self.wet_dry_left == self.wet_dry_right;
+        self.stereo_mode == StereoMode::LeftRight
+            && left == right
+            && wet_dry_matches
+            && self.stereo_offset == 0
+    }
+
+    /// The window size the filter is using, in samples. In mid/side mode, this
+    /// is the mid window.
+    pub(crate) fn effective_window_samples(&self) -> usize {
+        self.window_sizes().0
+    }
+
+    /// The window size the filter is using, in milliseconds.
+    pub(crate) fn effective_window_ms(&self, sample_rate: f32) -> f32 {
+        self.effective_window_samples() as f32 / sample_rate * 1000.0
+    }
+
+    /// The delay, in samples, between the plugin's input and output, summed
+    /// over everything which delays the whole output:
+    /// - The center of the window, which the dry signal is delayed to match
+    ///   when `compensates_dry` is true. Otherwise the dry signal isn't delayed
+    ///   and the output has no single delay, so this is left out. In mid/side
+    ///   mode, the larger of the two windows is used, and with more than one
+    ///   band, the largest band window, which the other bands are delayed to
+    ///   match.
+    /// - The band crossovers, which add nothing, since the bands always sum
+    ///   back to the original signal.
+    /// - The lookahead limiter's lookahead.
+    ///
+    /// `StereoOffset` only delays the right channel's filtered signal, so it
+    /// isn't included.
+    pub(crate) fn group_delay_samples(&self) -> f32 {
+        let (left, right) = self.window_sizes();
+        let window = if self.bands > 1 {
+            self.max_band_window()
+        } else {
+            left.max(right)
+        };
+        let window_delay = dry_delay(window, self.compensates_dry());
+        let crossover_delay = 0;
+        let oversample_delay = self.oversample_delay();
+        let limiter_delay = if self.limit && self.limit_mode == LimitMode::Lookahead {
+            LOOKAHEAD_SAMPLES
+        } else {
+            0
+        };
+        (window_delay + crossover_delay + oversample_delay + limiter_delay) as f32
+    }
+
+    /// How many samples oversampling the saturation delays the wet signal by.
+    pub(crate) fn oversample_delay(&self) -> usize {
+        if self.oversample_drive {
+            OVERSAMPLE_DELAY
+        } else {
+            0
+        }
+    }
+
+    /// Whether the dry signal is delayed to line up with the filtered signal.
+    /// With `wet_invert`, it always is, since otherwise the inverted wet
+    /// signal wouldn't cancel the dry signal.
+    pub(crate) fn compensates_dry(&self) -> bool {
+        self.compensate_dry || self.wet_invert
+    }
+
+    /// `group_delay_samples` in whole samples, as reported to the host.
+    pub(crate) fn latency(&self) -> usize {
+        self.group_delay_samples().round() as usize
+    }
+
+    /// The window sizes of the low, mid and high bands.
+    pub(crate) fn band_windows(&self) -> [usize; MAX_BANDS] {
+        [
+            self.low_band_window.samples,
+            self.mid_band_window.samples,
+            self.high_band_window.samples,
+        ]
+    }
+
+    /// The wet/dry of the low, mid and high bands.
+    pub(crate) fn band_wet_dry(&self) -> [f32; MAX_BANDS] {
+        [
+            self.low_band_wet_dry,
+            self.mid_band_wet_dry,
+            self.high_band_wet_dry,
+        ]
+    }
+
+    /// The low/mid and mid/high crossover frequencies, in Hz.
+    pub(crate) fn crossovers(&self) -> [f32; MAX_BANDS - 1] {
+        [
+            self.low_crossover,
+            self.high_crossover.max(self.low_crossover),
+        ]
+    }
+
+    /// Whether the band at `index` (0 for low, 1 for mid, 2 for high) is used.
+    /// With two bands, there is no mid band.
+    pub(crate) fn band_is_active(&self, index: usize) -> bool {
+        match self.bands {
+            1 => false,
+            2 => index != 1,
+            _ => true,
+        }
+    }
+
+    /// The largest window of the bands in use.
+    pub(crate) fn max_band_window(&self) -> usize {
+        let windows = self.band_windows();
+        (0..MAX_BANDS)
+            .filter(|&i| self.band_is_active(i))
+            .map(|i| windows[i])
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// The order of the stages the filtered signal passes through.
+    pub(crate) fn stages(&self) -> [Stage; 2] {
+        if self.saturate_before_filter {
+            [Stage::Saturate, Stage::Filter]
+        } else {
+            [Stage::Filter, Stage::Saturate]
+        }
+    }
+}
+
+/// The window size, in samples, that the filter actually uses for the raw
+/// `window_size` parameter value. This accounts for the window unit,
+/// `MAX_WINDOW_SIZE`, the realtime cap, and `ForceOdd`. Both `process` and the
+/// displayed parameter values go through this, so they always agree. Also
+/// returns true if a larger window was asked for and clamped to
+/// `MAX_WINDOW_SIZE`.
+fn effective_window_size(params: &RawParameters, window_size: f32) -> (usize, bool) {
+    let window_unit = WindowUnit::from_normalized(params.window_unit.get());
+    let requested = window_unit.to_samples(window_size, params.sample_rate.get());
+    let window_size = requested.min(MAX_WINDOW_SIZE);
+    // When rendering in realtime, the window is limited to the realtime cap to
+    // protect the CPU, while offline renders may use the full window size.
+    let window_size = if params.offline.load(Ordering::Relaxed) {
+        window_size
+    } else {
+        window_size.min(realtime_cap(params))
+    };
+    let clamped = requested > window_size && window_size == MAX_WINDOW_SIZE;
+    (
+        force_odd(window_size, params.force_odd.get() > 0.5),
+        clamped,
+    )
+}
+
+/// A window size in samples, along with how to display it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Window {
+    pub(crate) samples: usize,
+    // In `WindowUnit::Hertz`, the frequency of the window, shown instead of
+    // the number of samples.
+    hz: Option<f32>,
+    // Whether a larger window was asked for and clamped to `MAX_WINDOW_SIZE`.
+    clamped: bool,
+}
+
+impl Window {
+    pub(crate) fn new(samples: usize) -> Window {
+        Window {
+            samples,
+            hz: None,
+            clamped: false,
+        }
+    }
+
+    /// The window the filter actually uses for the raw `window_size`
+    /// parameter value. See `effective_window_size`.
+    fn effective(params: &RawParameters, window_size: f32) -> Window {
+        let (samples, clamped) = effective_window_size(params, window_size);
+        let hz = match WindowUnit::from_normalized(params.window_unit.get()) {
+            WindowUnit::Hertz => Some(params.sample_rate.get() / samples as f32),
+            _ => None,
+        };
+        Window {
+            samples,
+            hz,
+            clamped,
+        }
+    }
+
+    fn strings(self) -> (String, String) {
+        match self.hz {
+            Some(hz) => make_strings(hz, " Hz"),
+            None => window_strings(self.samples, self.clamped),
+        }
+    }
+}
+
+/// Display a window size in samples. Window sizes are clamped to
+/// `MAX_WINDOW_SIZE` rather than growing the filter's buffers, which are
+/// allocated up front, so a `clamped` window is marked to show that a larger
+/// window was asked for, such as a long window in milliseconds at a very high
+/// sample rate.
+pub(crate) fn window_strings(window_size: usize, clamped: bool) -> (String, String) {
+    let label = if clamped {
+        " Samples (max)"
+    } else {
+        " Samples"
+    };
+    (format!("{}", window_size), label.to_string())
+}
+
+/// The time, in milliseconds, of a normalized `WindowAttack` or
+/// `WindowRelease`.
+fn window_follow_ms(x: f32) -> f32 {
+    MIN_WINDOW_FOLLOW_MS * (MAX_WINDOW_FOLLOW_MS / MIN_WINDOW_FOLLOW_MS).powf(x)
+}
+
+/// The fewest samples between two batches of MIDI CC feedback.
+pub(crate) fn cc_feedback_interval(sample_rate: f32) -> usize {
+    (CC_FEEDBACK_INTERVAL_MS / 1000.0 * sample_rate) as usize
+}
+
+/// The envelope window, in samples, for an `EnvelopeWindow` in milliseconds.
+pub(crate) fn envelope_window_samples(window_ms: f32, sample_rate: f32) -> usize {
+    ((window_ms / 1000.0 * sample_rate).round() as usize).clamp(1, MAX_WINDOW_SIZE)
+}
+
+fn realtime_cap(params: &RawParameters) -> usize {
+    ((params.realtime_cap.get() * MAX_WINDOW_SIZE as f32) as usize).max(1)
+}
+
+/// How far the dry signal is delayed. The median of the last `window_size`
+/// samples lags the input by about half a window. When `compensate_dry` is on,
+/// the dry signal is delayed so that it stays in phase with the filtered
+/// signal. When it is off, the dry signal is mixed in as-is, which leaves the
+/// filtered signal lagging behind and causes comb filtering at intermediate
+/// wet/dry settings.
+pub(crate) fn dry_delay(window_size: usize, compensate_dry: bool) -> usize {
+    if compensate_dry {
+        (window_size - 1) / 2
+    } else {
+        0
+    }
+}
+
+/// If `enabled`, round `window_size` down to an odd number. With an odd window
+/// size, the median is always one of the samples in the window rather than the
+/// average of the two middle samples, which preserves edges better.
+fn force_odd(window_size: usize, enabled: bool) -> usize {
+    if enabled && window_size.is_multiple_of(2) {
+        window_size - 1
+    } else {
+        window_size
+    }
+}
+
+impl From<&RawParameters> for Parameters {
+    fn from(params: &RawParameters) -> Self {
+        use std::convert::TryFrom;
+        // When linked, the left knob controls the mix of both channels.
+        let link_wet_dry = params.link_wet_dry.get() > 0.5;
+        let gain_display = GainDisplay::from_normalized(params.gain_display.get());
+        let gain = |range: GainRange, value: &AtomicFloat| Gain {
+            db: range.to_db(value.get()),
+            display: gain_display,
+        };
+        Parameters {
+            window_size: Window::effective(params, params.window_size.get()),
+            wet_dry_left: params.wet_dry_left.get(),
+            wet_dry_right: if link_wet_dry {
+                params.wet_dry_left.get()
+            } else {
+                params.wet_dry_right.get()
+            },
+            link_wet_dry,
+            compensate_dry: params.compensate_dry.get() > 0.5,
+            realtime_cap: realtime_cap(params),
+            mode: Mode::from_normalized(params.mode.get()),
+            threshold: gain(THRESHOLD_RANGE, &params.threshold),
+            limit: params.limit.get() > 0.5,
+            ceiling: gain(CEILING_RANGE, &params.ceiling),
+            bypass: params.bypass.get() > 0.5,
+            force_odd: params.force_odd.get() > 0.5,
+            window_unit: WindowUnit::from_normalized(params.window_unit.get()),
+            constant_loudness: params.constant_loudness.get() > 0.5,
+            edge_mode: EdgeMode::from_normalized(params.edge_mode.get()),
+            full_wet: params.full_wet.get() > 0.5,
+            drive: gain(DRIVE_RANGE, &params.drive),
+            saturate_before_filter: params.saturate_before_filter.get() > 0.5,
+            stereo_mode: StereoMode::from_normalized(params.stereo_mode.get()),
+            mid_window: Window::effective(params, params.mid_window.get()),
+            side_window: Window::effective(params, params.side_window.get()),
+            tie_break: TieBreak::from_normalized(params.tie_break.get()),
+            smart_window: params.smart_window.get() > 0.5,
+            silence_threshold: gain(SILENCE_THRESHOLD_RANGE, &params.silence_threshold),
+            hampel_k: params.hampel_k.get() * 10.0,
+            latency_updates: LatencyUpdates::from_normalized(params.latency_updates.get()),
+            transient_preserve: params.transient_preserve.get(),
+            output_gain: gain(OUTPUT_GAIN_RANGE, &params.output_gain),
+            gain_mode: GainMode::from_normalized(params.gain_mode.get()),
+            recorrelate: params.recorrelate.get(),
+            crossfeed: params.crossfeed.get(),
+            dc_trim: (params.dc_trim.get() * 2.0 - 1.0) * MAX_DC_TRIM,
+            mix_curve: MixCurve::from_normalized(params.mix_curve.get()),
+            gain_display,
+            diagnostic: Diagnostic::from_normalized(params.diagnostic.get()),
+            limit_mode: LimitMode::from_normalized(params.limit_mode.get()),
+            bands: quantize_index(params.bands.get(), MAX_BANDS) + 1,
+            low_crossover: 20.0 * 100.0f32.powf(params.low_crossover.get()),
+            high_crossover: 500.0 * 40.0f32.powf(params.high_crossover.get()),
+            low_band_window: Window::effective(params, params.low_band_window.get()),
+            mid_band_window: Window::effective(params, params.mid_band_window.get()),
+            high_band_window: Window::effective(params, params.high_band_window.get()),
+            low_band_wet_dry: params.low_band_wet_dry.get(),
+            mid_band_wet_dry: params.mid_band_wet_dry.get(),
+            high_band_wet_dry: params.high_band_wet_dry.get(),
+            bit_crush: 1 + (params.bit_crush.get() * (MAX_BIT_DEPTH - 1) as f32).round() as u32,
+            sample_rate_reduce: 1
+                + (params.sample_rate_reduce.get() * (MAX_SAMPLE_RATE_REDUCTION - 1) as f32).round()
+                    as usize,
+            dither: params.dither.get() > 0.5,
+            mono_input: MonoInput::from_normalized(params.mono_input.get()),
+            pitch_sync: params.pitch_sync.get() > 0.5,
+            envelope: params.envelope.get() > 0.5,
+            envelope_window: MIN_ENVELOPE_WINDOW_MS
+                + params.envelope_window.get() * (MAX_ENVELOPE_WINDOW_MS - MIN_ENVELOPE_WINDOW_MS),
+            periods: 1 + (params.periods.get() * (MAX_PERIODS - 1) as f32).round() as usize,
+            vintage_feel: params.vintage_feel.get() > 0.5,
+            lfo_rate: NoteDivision::from_normalized(params.lfo_rate.get()),
+            lfo_depth: params.lfo_depth.get(),
+            lfo_shape: LfoShape::from_normalized(params.lfo_shape.get()),
+            stereo_offset: (params.stereo_offset.get() * MAX_STEREO_OFFSET as f32).round() as usize,
+            gate: params.gate.get() > 0.5,
+            gate_rate: NoteDivision::from_normalized(params.gate_rate.get()),
+            gate_steps: std::array::from_fn(|step| {
+                let index = i32::from(ParameterType::GateStep1) + step as i32;
+                ParameterType::try_from(index).map_or(0.0, |parameter| params.get(parameter))
+            }),
+            bypass_gain_match: params.bypass_gain_match.get() > 0.5,
+            shelf_gain: gain(SHELF_GAIN_RANGE, &params.shelf_gain),
+            shelf_freq: 2000.0 * 8.0f32.powf(params.shelf_freq.get()),
+            input_gain: gain(INPUT_GAIN_RANGE, &params.input_gain),
+            wet_invert: params.wet_invert.get() > 0.5,
+            target: Target::from_normalized(params.target.get()),
+            safe_makeup: params.safe_makeup.get() > 0.5,
+            dynamic_window: params.dynamic_window.get(),
+            window_attack: window_follow_ms(params.window_attack.get()),
+            window_release: window_follow_ms(params.window_release.get()),
+            stagger: params.stagger.get() * MAX_STAGGER,
+            oversample_drive: params.oversample_drive.get() > 0.5,
+            soft_start: params.soft_start.get() > 0.5,
+        }
+    }
+}
+
+impl RawParameters {
+    /// Parse a value typed in by the user, returning the normalized value.
+    /// Only the parameters shown in decibels can be typed in.
+    fn parse_text(&self, parameter: ParameterType, text: &str) -> Option<f32> {
+        let range = match parameter {
+            ParameterType::Threshold => THRESHOLD_RANGE,
+            ParameterType::Ceiling => CEILING_RANGE,
+            ParameterType::Drive => DRIVE_RANGE,
+            ParameterType::OutputGain => OUTPUT_GAIN_RANGE,
+            ParameterType::ShelfGain => SHELF_GAIN_RANGE,
+            ParameterType::InputGain => INPUT_GAIN_RANGE,
+            ParameterType::SilenceThreshold => SILENCE_THRESHOLD_RANGE,
+            _ => return None,
+        };
+        let display = GainDisplay::from_normalized(self.gain_display.get());
+        parse_gain(text, display).map(|db| range.to_normalized(db))
+    }
+}
+
+macro_rules! table {
+    ($macro:ident) => {
+        $macro! {
+        //  RawParameter identifier, ParameterType identifier
+            RawParameters,           ParameterType;
+        //  variant               field_name              name                  idx  default     smoothing                mapping               usable_min  usable_max  strings
+            WetDryLeft,           wet_dry_left,           "Wet/Dry L",          0,   0.5,        Smoothing::Linear(20.0), Mapping::Linear,      0.0,        1.0,        |x: f32| make_rounded_strings(x * 100.0, 0.1, "% Wet");
+            WindowSize,           window_size,            "Window Size",        1,   0.5,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: Window| x.strings();
+            CompensateDry,        compensate_dry,         "Compensate Dry",     2,   0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            RealtimeCap,          realtime_cap,           "Realtime Max",       3,   1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: usize| (format!("{}", x), " Samples".to_string());
+            Mode,                 mode,                   "Mode",               4,   0.0,        Smoothing::None,         Mapping::Stepped(3),  0.0,        1.0,        |x: Mode| (x.to_string(), "".to_string());
+            Threshold,            threshold,              "Threshold",          5,   0.5,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: Gain| x.strings();
+            Limit,                limit,                  "Limit",              6,   0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            Ceiling,              ceiling,                "Ceiling",            7,   1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: Gain| x.strings();
+            Bypass,               bypass,                 "Bypass",             8,   0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            ForceOdd,             force_odd,              "Force Odd",          9,   0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            WindowUnit,           window_unit,            "Window Unit",        10,  0.0,        Smoothing::None,         Mapping::Stepped(3),  0.0,        1.0,        |x: WindowUnit| (x.to_string(), "".to_string());
+            ConstantLoudness,     constant_loudness,      "Constant Loudness",  11,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            EdgeMode,             edge_mode,              "Edge Mode",          12,  0.0,        Smoothing::None,         Mapping::Stepped(3),  0.0,        1.0,        |x: EdgeMode| (x.to_string(), "".to_string());
+            FullWet,              full_wet,               "Full Wet",           13,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            Drive,                drive,                  "Drive",              14,  0.0,        Smoothing::Glide(50.0),  Mapping::Linear,      0.0,        1.0,        |x: Gain| x.strings();
+            SaturateBeforeFilter, saturate_before_filter, "Saturate First",     15,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            WetDryRight,          wet_dry_right,          "Wet/Dry R",          16,  0.5,        Smoothing::Linear(20.0), Mapping::Linear,      0.0,        1.0,        |x: f32| make_rounded_strings(x * 100.0, 0.1, "% Wet");
+            LinkWetDry,           link_wet_dry,           "Link Wet/Dry",       17,  1.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            StereoMode,           stereo_mode,            "Stereo Mode",        18,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        |x: StereoMode| (x.to_string(), "".to_string());
+            MidWindow,            mid_window,             "Mid Window",         19,  0.5,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: Window| x.strings();
+            SideWindow,           side_window,            "Side Window",        20,  0.5,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: Window| x.strings();
+            TieBreak,             tie_break,              "Tie Break",          21,  0.0,        Smoothing::None,         Mapping::Stepped(3),  0.0,        1.0,        |x: TieBreak| (x.to_string(), "".to_string());
+            HampelK,              hampel_k,               "Hampel K",           22,  0.3,        Smoothing::None,         Mapping::Linear,      0.05,       1.0,        |x: f32| make_strings(x.max(MIN_HAMPEL_K), "");
+            LatencyUpdates,       latency_updates,        "Latency Updates",    23,  0.5,        Smoothing::None,         Mapping::Stepped(3),  0.0,        1.0,        |x: LatencyUpdates| (x.to_string(), "".to_string());
+            TransientPreserve,    transient_preserve,     "Transient Preserve", 24,  0.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: f32| make_strings(x * 100.0, "%");
+            OutputGain,           output_gain,            "Output Gain",        25,  0.5,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: Gain| x.strings();
+            GainMode,             gain_mode,              "Gain Mode",          26,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        |x: GainMode| (x.to_string(), "".to_string());
+            Recorrelate,          recorrelate,            "Recorrelate",        27,  0.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: f32| make_strings(x * 100.0, "%");
+            MixCurve,             mix_curve,              "Mix Curve",          28,  0.0,        Smoothing::None,         Mapping::Stepped(3),  0.0,        1.0,        |x: MixCurve| (x.to_string(), "".to_string());
+            GainDisplay,          gain_display,           "Gain Display",       29,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        |x: GainDisplay| (x.to_string(), "".to_string());
+            Diagnostic,           diagnostic,             "Diagnostic",         30,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        |x: Diagnostic| (x.to_string(), "".to_string());
+            LimitMode,            limit_mode,             "Limit Mode",         31,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        |x: LimitMode| (x.to_string(), "".to_string());
+            Bands,                bands,                  "Bands",              32,  0.0,        Smoothing::None,         Mapping::Stepped(3),  0.0,        1.0,        |x: usize| (format!("{}", x), " Bands".to_string());
+            LowCrossover,         low_crossover,          "Low Crossover",      33,  0.5,        Smoothing::None,         Mapping::Log,         0.0,        1.0,        |x: f32| make_rounded_strings(x, 1.0, "Hz");
+            HighCrossover,        high_crossover,         "High Crossover",     34,  0.5,        Smoothing::None,         Mapping::Log,         0.0,        1.0,        |x: f32| make_rounded_strings(x, 1.0, "Hz");
+            LowBandWindow,        low_band_window,        "Low Band Window",    35,  0.5,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: Window| x.strings();
+            MidBandWindow,        mid_band_window,        "Mid Band Window",    36,  0.5,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: Window| x.strings();
+            HighBandWindow,       high_band_window,       "High Band Window",   37,  0.5,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: Window| x.strings();
+            LowBandWetDry,        low_band_wet_dry,       "Low Band Wet/Dry",   38,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: f32| make_rounded_strings(x * 100.0, 0.1, "% Wet");
+            MidBandWetDry,        mid_band_wet_dry,       "Mid Band Wet/Dry",   39,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: f32| make_rounded_strings(x * 100.0, 0.1, "% Wet");
+            HighBandWetDry,       high_band_wet_dry,      "High Band Wet/Dry",  40,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: f32| make_rounded_strings(x * 100.0, 0.1, "% Wet");
+            BitCrush,             bit_crush,              "Bit Crush",          41,  1.0,        Smoothing::None,         Mapping::Stepped(24), 0.0,        1.0,        |x: u32| (format!("{}", x), " Bits".to_string());
+            SampleRateReduce,     sample_rate_reduce,     "Sample Rate Reduce", 42,  0.0,        Smoothing::None,         Mapping::Stepped(32), 0.0,        1.0,        |x: usize| (format!("{}", x), "x".to_string());
+            Dither,               dither,                 "Dither",             43,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            MonoInput,            mono_input,             "Mono Input",         44,  0.0,        Smoothing::None,         Mapping::Stepped(3),  0.0,        1.0,        |x: MonoInput| (x.to_string(), "".to_string());
+            PitchSync,            pitch_sync,             "Pitch Sync",         45,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            Periods,              periods,                "Periods",            46,  0.0,        Smoothing::None,         Mapping::Stepped(8),  0.0,        1.0,        |x: usize| (format!("{}", x), " Periods".to_string());
+            Envelope,             envelope,               "Envelope Meter",     47,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            EnvelopeWindow,       envelope_window,        "Envelope Window",    48,  9.0 / 49.0, Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: f32| make_strings(x, "ms");
+            Crossfeed,            crossfeed,              "Crossfeed",          49,  0.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: f32| make_strings(x * 100.0, "%");
+            DcTrim,               dc_trim,                "DC Trim",            50,  0.5,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: f32| make_rounded_strings(x * 100.0, 0.01, "% FS");
+            VintageFeel,          vintage_feel,           "Vintage Feel",       51,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            LfoRate,              lfo_rate,               "LFO Rate",           52,  4.0 / 7.0,  Smoothing::None,         Mapping::Stepped(8),  0.0,        1.0,        |x: NoteDivision| (x.to_string(), "".to_string());
+            LfoDepth,             lfo_depth,              "LFO Depth",          53,  0.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: f32| make_strings(x * 100.0, "%");
+            LfoShape,             lfo_shape,              "LFO Shape",          54,  0.0,        Smoothing::None,         Mapping::Stepped(3),  0.0,        1.0,        |x: LfoShape| (x.to_string(), "".to_string());
+            StereoOffset,         stereo_offset,          "Stereo Offset",      55,  0.0,        Smoothing::None,         Mapping::Stepped(33), 0.0,        1.0,        |x: usize| (format!("{}", x), " Samples".to_string());
+            Gate,                 gate,                   "Gate",               56,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            GateRate,             gate_rate,              "Gate Rate",          57,  6.0 / 7.0,  Smoothing::None,         Mapping::Stepped(8),  0.0,        1.0,        |x: NoteDivision| (x.to_string(), "".to_string());
+            GateStep1,            gate_step_1,            "Gate Step 1",        58,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep2,            gate_step_2,            "Gate Step 2",        59,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep3,            gate_step_3,            "Gate Step 3",        60,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep4,            gate_step_4,            "Gate Step 4",        61,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep5,            gate_step_5,            "Gate Step 5",        62,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep6,            gate_step_6,            "Gate Step 6",        63,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep7,            gate_step_7,            "Gate Step 7",        64,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep8,            gate_step_8,            "Gate Step 8",        65,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep9,            gate_step_9,            "Gate Step 9",        66,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep10,           gate_step_10,           "Gate Step 10",       67,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep11,           gate_step_11,           "Gate Step 11",       68,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep12,           gate_step_12,           "Gate Step 12",       69,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep13,           gate_step_13,           "Gate Step 13",       70,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep14,           gate_step_14,           "Gate Step 14",       71,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep15,           gate_step_15,           "Gate Step 15",       72,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            GateStep16,           gate_step_16,           "Gate Step 16",       73,  1.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        @normalized |x: f32| make_strings(x * 100.0, "%");
+            BypassGainMatch,      bypass_gain_match,      "Bypass Gain Match",  74,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            ShelfGain,            shelf_gain,             "Shelf Gain",         75,  0.5,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: Gain| x.strings();
+            ShelfFreq,            shelf_freq,             "Shelf Freq",         76,  0.5,        Smoothing::None,         Mapping::Log,         0.0,        1.0,        |x: f32| make_rounded_strings(x, 1.0, "Hz");
+            InputGain,            input_gain,             "Input Gain",         77,  0.5,        Smoothing::Glide(50.0),  Mapping::Linear,      0.0,        1.0,        |x: Gain| x.strings();
+            WetInvert,            wet_invert,             "Wet Invert",         78,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            Target,               target,                 "Target",             79,  0.0,        Smoothing::None,         Mapping::Stepped(3),  0.0,        1.0,        |x: Target| (x.to_string(), "".to_string());
+            SafeMakeup,           safe_makeup,            "Safe Makeup",        80,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            DynamicWindow,        dynamic_window,         "Dynamic Window",     81,  0.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: f32| make_strings(x * 100.0, "%");
+            WindowAttack,         window_attack,          "Window Attack",      82,  1.0 / 3.0,  Smoothing::None,         Mapping::Log,         0.0,        1.0,        |x: f32| make_strings(x, "ms");
+            WindowRelease,        window_release,         "Window Release",     83,  2.0 / 3.0,  Smoothing::None,         Mapping::Log,         0.0,        1.0,        |x: f32| make_strings(x, "ms");
+            Stagger,              stagger,                "Stagger",            84,  0.0,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: f32| make_strings(x, "%");
+            OversampleDrive,      oversample_drive,       "Oversample Drive",   85,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            SmartWindow,          smart_window,           "Smart Window",       86,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+            SilenceThreshold,     silence_threshold,      "Silence Threshold",  87,  0.5,        Smoothing::None,         Mapping::Linear,      0.0,        1.0,        |x: Gain| x.strings();
+            SoftStart,            soft_start,             "Soft Start",         88,  0.0,        Smoothing::None,         Mapping::Stepped(2),  0.0,        1.0,        make_bool_strings;
+        }
+    };
+}
+
+impl ParameterType {
+    pub const COUNT: usize = 89;
+}
+
+impl_all! {RawParameters, ParameterType, table}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use vst::plugin::{HostCallback, PluginParameters};
+
+    use common::{descriptor::ParamDescriptor, smoothing::Smoother, test_util::null_host};
+
+    use super::*;
+
+    /// The Debug output names every parameter along with its current value.
+    #[test]
+    fn debug_shows_every_parameter() {
+        let params = RawParameters::default(null_host());
+        let debug = format!("{:?}", params);
+        for &parameter in ParameterType::all() {
+            let field = format!("{}: {:?}", parameter, params.get(parameter));
+            assert!(debug.contains(&field), "missing {:?} in {}", field, debug);
+        }
+    }
+
+    /// Freshly constructed parameters are all at their defaults, in table
+    /// order.
+    #[test]
+    fn all_normalized_defaults() {
+        let params = RawParameters::default(null_host());
+        let values = params.all_normalized();
+        for (i, &parameter) in ParameterType::all().iter().enumerate() {
+            assert_eq!(i32::from(parameter), i as i32);
+            assert_eq!(
+                values[i],
+                RawParameters::get_default(parameter),
+                "{}",
+                parameter
+            );
+        }
+    }
+
+    /// A parameter reports being at its default until it is changed.
+    #[test]
+    fn is_default_after_set() {
+        let params = RawParameters::default(null_host());
+        for &parameter in ParameterType::all() {
+            assert!(params.is_default(parameter), "{}", parameter);
+            let default = RawParameters::get_default(parameter);
+            params.set(if default > 0.5 { 0.0 } else { 1.0 }, parameter);
+            assert!(!params.is_default(parameter), "{}", parameter);
+        }
+    }
+
+    /// `all` lists every parameter once, in the same order as the table.
+    #[test]
+    fn all_parameters_in_table_order() {
+        let all = ParameterType::all();
+        assert_eq!(all.len(), ParameterType::COUNT);
+        assert_eq!(all.first(), Some(&ParameterType::WetDryLeft));
+        assert_eq!(all.last(), Some(&ParameterType::SoftStart));
+        for (i, &parameter) in all.iter().enumerate() {
+            assert_eq!(i32::from(parameter), i as i32, "{}", parameter);
+        }
+    }
+
+    /// Counts the begin_edit notifications sent to `counting_host`.
+    static BEGIN_EDITS: AtomicUsize = AtomicUsize::new(0);
+
+    extern "C" fn counting_host(
+        _effect: *mut vst::api::AEffect,
+        opcode: i32,
+        _index: i32,
+        _value: isize,
+        _ptr: *mut std::os::raw::c_void,
+        _opt: f32,
+    ) -> isize {
+        // audioMasterBeginEdit
+        if opcode == 43 {
+            BEGIN_EDITS.fetch_add(1, Ordering::Relaxed);
+        }
+        0
+    }
+
+    /// Many changes to a parameter within one block reach the host as one
+    /// notification, sent when the edits are flushed.
+    #[test]
+    fn edits_coalesce_per_block() {
+        let params =
+            RawParameters::default(HostCallback::wrap(counting_host, std::ptr::null_mut()));
+        for i in 0..1000 {
+            params.set(i as f32 / 1000.0, ParameterType::WindowSize);
+        }
+        params.set(1.0, ParameterType::Bypass);
+        assert_eq!(BEGIN_EDITS.load(Ordering::Relaxed), 0);
+        params.flush_edits();
+        assert_eq!(BEGIN_EDITS.load(Ordering::Relaxed), 2);
+        params.flush_edits();
+        assert_eq!(BEGIN_EDITS.load(Ordering::Relaxed), 2);
+    }
+
+    /// The S-curve changes the effective wet amount away from the knob's
+    /// midpoint, and every curve leaves the end points alone.
+    #[test]
+    fn mix_curves() {
+        let knob = 0.25;
+        assert!((MixCurve::Linear.apply(knob) - MixCurve::SCurve.apply(knob)).abs() >= 0.01);
+        for &curve in [MixCurve::Linear, MixCurve::SCurve, MixCurve::Exponential].iter() {
+            assert!(curve.apply(0.0).abs() <= 1e-6, "{}", curve);
+            assert!((curve.apply(1.0) - 1.0).abs() <= 1e-6, "{}", curve);
+        }
+    }
+
+    /// A gain renders in both decibels and linear units, and text in either
+    /// unit can be typed in.
+    #[test]
+    fn gain_display() {
+        let params = RawParameters::default(null_host());
+        let index: i32 = ParameterType::OutputGain.into();
+        params.set(0.0, ParameterType::GainDisplay);
+        params.set(0.5, ParameterType::OutputGain);
+        assert_eq!(
+            params.get_strings(ParameterType::OutputGain),
+            ("0.00".to_string(), "dB".to_string())
+        );
+        params.set(1.0, ParameterType::GainDisplay);
+        assert_eq!(
+            params.get_strings(ParameterType::OutputGain),
+            ("1.00".to_string(), "x".to_string())
+        );
+
+        for &text in ["6 dB", "1.995 x"].iter() {
+            params.set(0.5, ParameterType::OutputGain);
+            assert!(
+                params.string_to_parameter(index, text.to_string()),
+                "{}",
+                text
+            );
+            let db = params.snapshot().output_gain.db;
+            assert!((db - 6.0).abs() <= 0.01, "typing {:?} gave {} dB", text, db);
+        }
+    }
+
+    /// The wet/dry knob (a linear ramp) and the drive (a glide) respond
+    /// differently to the same step.
+    #[test]
+    fn smoothing_kinds() {
+        const SAMPLE_RATE: f32 = 44100.0;
+        assert!(matches!(
+            RawParameters::get_smoothing(ParameterType::WetDryLeft),
+            Smoothing::Linear(_)
+        ));
+        assert!(matches!(
+            RawParameters::get_smoothing(ParameterType::Drive),
+            Smoothing::Glide(_)
+        ));
+        let step = |parameter: ParameterType| -> Vec<f32> {
+            let smoothing = RawParameters::get_smoothing(parameter);
+            let mut smoother = Smoother::new(smoothing, 0.0, SAMPLE_RATE);
+            smoother.set_target(1.0);
+            (0..SAMPLE_RATE as usize / 10)
+                .map(|_| smoother.advance())
+                .collect()
+        };
+        let linear = step(ParameterType::WetDryLeft);
+        let glide = step(ParameterType::Drive);
+        let difference = linear
+            .iter()
+            .zip(glide.iter())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(difference >= 0.1, "the ramps only differ by {}", difference);
+        assert!((linear[linear.len() - 1] - 1.0).abs() <= 1e-6);
+    }
+
+    /// The parameter table's indices are exactly `0..COUNT`, and each
+    /// parameter converts to its index and back.
+    #[test]
+    fn parameter_indices() {
+        assert_eq!(ParameterType::check_indices(), Ok(()));
+    }
+
+    /// Automation wiggling a parameter across a digit boundary doesn't change
+    /// its displayed value.
+    #[test]
+    fn display_rounding() {
+        let params = RawParameters::default(null_host());
+        params.set(0.49994, ParameterType::WetDryLeft);
+        let below = params.get_strings(ParameterType::WetDryLeft);
+        params.set(0.49996, ParameterType::WetDryLeft);
+        assert_eq!(params.get_strings(ParameterType::WetDryLeft), below);
+    }
+
+    /// Asking for twice the largest window clamps it to `MAX_WINDOW_SIZE`,
+    /// and the displayed value shows the clamp only when there was one.
+    #[test]
+    fn window_clamp() {
+        let params = RawParameters::default(null_host());
+        params.offline.store(true, Ordering::Relaxed);
+        params.force_odd.set(0.0);
+        params.set(1.0, ParameterType::WindowUnit);
+        params.set(1.0, ParameterType::WindowSize);
+        // The longest window in milliseconds is twice `MAX_WINDOW_SIZE` at
+        // this sample rate.
+        let sample_rate = 2.0 * MAX_WINDOW_SIZE as f32 / (MAX_WINDOW_MS / 1000.0);
+        params.sample_rate.set(sample_rate);
+        assert_eq!(
+            Parameters::from(&params).window_size.samples,
+            MAX_WINDOW_SIZE
+        );
+        let expected = window_strings(MAX_WINDOW_SIZE, true);
+        assert!(expected.1.contains("max"));
+        assert_eq!(params.get_strings(ParameterType::WindowSize), expected);
+
+        // A window which fits, but is made one sample shorter by `ForceOdd`,
+        // wasn't clamped.
+        params.force_odd.set(1.0);
+        params
+            .sample_rate
+            .set(MAX_WINDOW_SIZE as f32 / (MAX_WINDOW_MS / 1000.0));
+        assert_eq!(
+            params.get_strings(ParameterType::WindowSize),
+            window_strings(MAX_WINDOW_SIZE - 1, false)
+        );
+    }
+
+    /// The normalized window size for a frequency in `WindowUnit::Hertz`.
+    fn window_hz_to_normalized(hz: f32) -> f32 {
+        ((hz / MAX_WINDOW_HZ).ln() / (MIN_WINDOW_HZ / MAX_WINDOW_HZ).ln()).clamp(0.0, 1.0)
+    }
+
+    /// A window set in Hz becomes `sample_rate / hz` samples, and is displayed
+    /// in Hz.
+    #[test]
+    fn window_in_hz() {
+        let params = RawParameters::default(null_host());
+        params.offline.store(true, Ordering::Relaxed);
+        params.force_odd.set(0.0);
+        params.sample_rate.set(48000.0);
+        params.set(0.5, ParameterType::WindowUnit);
+        params.set(window_hz_to_normalized(480.0), ParameterType::WindowSize);
+        assert_eq!(Parameters::from(&params).window_size.samples, 100);
+        let (value, unit) = params.get_strings(ParameterType::WindowSize);
+        assert_eq!(unit, " Hz");
+        let hz: f32 = value.parse().unwrap();
+        assert!((hz - 480.0).abs() <= 1.0, "displayed as {} Hz", hz);
+    }
+
+    #[test]
+    fn shelf_gain_typed_in() {
+        let params = RawParameters::default(null_host());
+        assert!(params.string_to_parameter(ParameterType::ShelfGain.into(), "6 dB".to_string()));
+        assert!((params.snapshot().shelf_gain.db - 6.0).abs() <= 0.01);
+    }
+
+    #[test]
+    fn input_gain_typed_in() {
+        let params = RawParameters::default(null_host());
+        assert!(params.string_to_parameter(ParameterType::InputGain.into(), "6 dB".to_string()));
+        assert!((params.snapshot().input_gain.db - 6.0).abs() <= 0.01);
+    }
+
+    /// The parameter descriptors match the table row for row.
+    #[test]
+    fn descriptors() {
+        let descriptors = RawParameters::descriptors();
+        assert_eq!(descriptors.len(), ParameterType::COUNT);
+        for (index, (descriptor, parameter)) in
+            descriptors.iter().zip(ParameterType::all()).enumerate()
+        {
+            assert_eq!(descriptor.index, index);
+            assert_eq!(descriptor.name, parameter.to_string());
+        }
+
+        let row = |parameter: ParameterType| descriptors[i32::from(parameter) as usize].clone();
+        assert_eq!(
+            row(ParameterType::WetDryLeft),
+            ParamDescriptor {
+                name: "Wet/Dry L".to_string(),
+                index: 0,
+                default: 0.5,
+                min: 0.0,
+                max: 1.0,
+                unit: "% Wet".to_string(),
+                kind: Mapping::Linear,
+            }
+        );
+        assert_eq!(
+            row(ParameterType::WindowSize),
+            ParamDescriptor {
+                name: "Window Size".to_string(),
+                index: 1,
+                default: 0.5,
+                min: 0.0,
+                max: 1.0,
+                unit: " Samples".to_string(),
+                kind: Mapping::Linear,
+            }
+        );
+        // The window size's units follow the window unit, but it is always
+        // described in samples, its default unit.
+        let params = RawParameters::default(null_host());
+        params.set(0.5, ParameterType::WindowUnit);
+        assert_eq!(params.get_strings(ParameterType::WindowSize).1, " Hz");
+        assert_eq!(RawParameters::descriptors(), descriptors);
+        assert_eq!(
+            row(ParameterType::ForceOdd),
+            ParamDescriptor {
+                name: "Force Odd".to_string(),
+                index: 9,
+                default: 0.0,
+                min: 0.0,
+                max: 1.0,
+                unit: "".to_string(),
+                kind: Mapping::Stepped(2),
+            }
+        );
+    }
+
+    /// The descriptors report the mappings declared in the table.
+    #[test]
+    fn descriptor_mappings() {
+        let descriptors = RawParameters::descriptors();
+        let kind = |parameter: ParameterType| descriptors[i32::from(parameter) as usize].kind;
+        assert_eq!(kind(ParameterType::WetDryLeft), Mapping::Linear);
+        assert_eq!(kind(ParameterType::WindowSize), Mapping::Linear);
+        assert_eq!(kind(ParameterType::CompensateDry), Mapping::Stepped(2));
+        assert_eq!(kind(ParameterType::Mode), Mapping::Stepped(3));
+        assert_eq!(kind(ParameterType::ForceOdd), Mapping::Stepped(2));
+        assert_eq!(kind(ParameterType::LowCrossover), Mapping::Log);
+        assert_eq!(kind(ParameterType::WindowAttack), Mapping::Log);
+        assert_eq!(kind(ParameterType::WindowRelease), Mapping::Log);
+    }
+}
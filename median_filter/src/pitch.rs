@@ -0,0 +1,66 @@
+/// The shortest period, in samples, that can be detected.
+pub const MIN_PERIOD: usize = 16;
+/// The longest period, in samples, that can be detected. This is about 86 Hz
+/// at 44.1 kHz.
+pub const MAX_PERIOD: usize = 512;
+/// How many samples are compared at each candidate period.
+const ANALYSIS_SAMPLES: usize = 512;
+/// How many samples `detect_period` needs to look at.
+pub const DETECTION_SAMPLES: usize = ANALYSIS_SAMPLES + MAX_PERIOD;
+/// How well the signal must match itself one period later for it to count as
+/// pitched. 1.0 is a perfect match.
+const MIN_CLARITY: f32 = 0.5;
+/// The first peak within this fraction of the best match is taken as the
+/// period. Without this, a multiple of the period is often picked instead,
+/// since it matches just as well.
+const PEAK_THRESHOLD: f32 = 0.9;
+
+/// Detect the fundamental period of `samples`, in samples, using the
+/// normalized square difference function from the McLeod pitch method.
+/// `samples` are ordered from oldest to newest, and only the last
+/// `DETECTION_SAMPLES` are used. Returns None if there are too few samples,
+/// or the signal has no clear pitch (such as silence or noise).
+pub fn detect_period(samples: &[f32]) -> Option<usize> {
+    if samples.len() < DETECTION_SAMPLES {
+        return None;
+    }
+    let samples = &samples[samples.len() - DETECTION_SAMPLES..];
+
+    let mut nsdf = [0.0; MAX_PERIOD + 1];
+    for (lag, value) in nsdf.iter_mut().enumerate() {
+        let mut correlation = 0.0;
+        let mut energy = 0.0;
+        for i in 0..ANALYSIS_SAMPLES {
+            let (x, y) = (samples[i], samples[i + lag]);
+            correlation += x * y;
+            energy += x * x + y * y;
+        }
+        if energy > 0.0 {
+            *value = 2.0 * correlation / energy;
+        }
+    }
+
+    // Skip the peak around a lag of zero, which always matches perfectly.
+    let start = (1..=MAX_PERIOD)
+        .find(|&lag| nsdf[lag] < 0.0)?
+        .max(MIN_PERIOD);
+    let best = nsdf[start..].iter().cloned().fold(0.0, f32::max);
+    if best < MIN_CLARITY {
+        return None;
+    }
+    let lag = (start..MAX_PERIOD).find(|&lag| {
+        nsdf[lag] >= best * PEAK_THRESHOLD
+            && nsdf[lag] >= nsdf[lag - 1]
+            && nsdf[lag] >= nsdf[lag + 1]
+    })?;
+
+    // Fit a parabola through the peak to find the period between samples.
+    let (before, peak, after) = (nsdf[lag - 1], nsdf[lag], nsdf[lag + 1]);
+    let curvature = before - 2.0 * peak + after;
+    let offset = if curvature < 0.0 {
+        0.5 * (before - after) / curvature
+    } else {
+        0.0
+    };
+    Some((lag as f32 + offset).round() as usize)
+}
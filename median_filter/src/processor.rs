@@ -0,0 +1,1985 @@
+use vst::plugin::{Category, Info};
+
+use common::{
+    db_to_gain, plugin::EffectProcessor, processor::ChannelProcessor, smoothing::Smoother,
+};
+
+use crate::{
+    envelope::MedianEnvelope,
+    lfo::Lfo,
+    limiter::LookaheadLimiter,
+    meters,
+    multiband::Multiband,
+    oversample::Oversampler,
+    parameters::{
+        dry_delay, envelope_window_samples, Diagnostic, EdgeMode, GainMode, LimitMode, Mode,
+        ParameterType, RawParameters, StereoMode, Target,
+    },
+    pitch::{self, DETECTION_SAMPLES},
+    shelf::HighShelf,
+    window::SortedWindow,
+    Parameters, BYPASS_RAMP_MS, LOUDNESS_SMOOTHING_MS, LOUDNESS_WINDOW_MS,
+    MAD_TO_STANDARD_DEVIATION, MAKEUP_RELEASE_MS, MAX_BIT_DEPTH, MAX_LOUDNESS_GAIN,
+    MAX_WINDOW_SIZE, NULL_MAKEUP_DB, TRANSIENT_FAST_MS, TRANSIENT_SLOW_MS,
+};
+
+/// The median filter for a single channel.
+pub(crate) struct MedianProcessor {
+    // Which channel this processes: 0 for the left (or mid) channel and 1 for
+    // the right (or side) channel.
+    channel: usize,
+    params: Parameters,
+    pub(crate) filter: SortedWindow,
+    pub(crate) window_size: usize,
+    // The last `MAX_WINDOW_SIZE` input samples, used to delay the dry signal.
+    history: DelayLine,
+    // The last `MAX_WINDOW_SIZE` samples fed into the filter. This differs from
+    // `history` when there are stages before the filter, and is used to refill
+    // the filter when the window size changes.
+    filter_history: DelayLine,
+    // The last `MAX_WINDOW_SIZE` input samples, used to delay the right
+    // channel's filter input by `StereoOffset`.
+    offset_history: DelayLine,
+    // How much of the bypassed signal is in the output.
+    bypass: Ramp,
+    // The mean squared level of the dry and filtered signal.
+    dry_energy: OnePole,
+    wet_energy: OnePole,
+    // The gain applied to the filtered signal when `ConstantLoudness` is on.
+    loudness_gain: OnePole,
+    // The makeup gain `ConstantLoudness` asks for, and the gain actually
+    // applied once `SafeMakeup` has backed it off to stay under the ceiling.
+    makeup_target: f32,
+    makeup_gain: f32,
+    // The peak of the made-up filtered signal, for `SafeMakeup`.
+    makeup_peak: OnePole,
+    // The mean squared level of the input and the processed output, and the
+    // gain they give for the bypassed signal with `BypassGainMatch`. The gain
+    // is held while bypassed.
+    input_energy: OnePole,
+    output_energy: OnePole,
+    bypass_gain: f32,
+    // The level of the input, used to detect transients. The fast envelope
+    // jumps above the slow one at the start of a transient.
+    fast_envelope: OnePole,
+    slow_envelope: OnePole,
+    // Splits the filter's input for `Target`, and holds the part which isn't
+    // filtered, so that it can be added back in line with the filtered part.
+    splitter: TransientSplitter,
+    unfiltered_history: DelayLine,
+    // The window size set by the parameters, which `DynamicWindow` shrinks
+    // from, and the follower which does the shrinking.
+    base_window: usize,
+    window_follower: WindowFollower,
+    // Runs the saturation stage at a higher sample rate, with
+    // `OversampleDrive`.
+    oversampler: Oversampler,
+    // Set when the filter is empty, so that the next block fills it before
+    // processing rather than starting from silence and clicking.
+    needs_warm_up: bool,
+    // The output gain applied to the current sample, and how much it changes
+    // by each sample while moving towards a new `OutputGain`.
+    gain: f32,
+    gain_step: f32,
+    limiter: LookaheadLimiter,
+    // Used instead of `filter` when the signal is split into bands.
+    multiband: Multiband,
+    crusher: Crusher,
+    // The last period detected in the filter's input, for `PitchSync`.
+    period: Option<usize>,
+    // Follows the level of the input for the envelope meter.
+    envelope: MedianEnvelope,
+    pub(crate) sample_rate: f32,
+    // The channel's wet/dry knob and the drive, smoothed as the parameter
+    // table asks.
+    wet_dry: Smoother,
+    drive: Smoother,
+    // Modulates the wet/dry.
+    pub(crate) lfo: Lfo,
+    // Restores the high end the median takes out of the wet signal.
+    shelf: HighShelf,
+    // The gain applied to the input, in decibels.
+    input_gain: Smoother,
+}
+
+impl MedianProcessor {
+    fn channel_window_size(channel: usize, params: &Parameters) -> usize {
+        let (left, right) = params.window_sizes();
+        if channel == 0 {
+            left
+        } else {
+            right
+        }
+    }
+
+    /// Change the window size. The new filter is filled with the most recent
+    /// input, so that the output continues smoothly instead of restarting from
+    /// an empty window.
+    fn set_window_size(&mut self, window_size: usize) {
+        self.filter.set_window_size(window_size);
+        self.window_size = window_size;
+        for delay in (0..window_size.min(self.filter_history.len())).rev() {
+            self.filter.consume(self.filter_history.get(delay));
+        }
+    }
+
+    /// The median absolute value of the input over the envelope window, or
+    /// 0.0 if the envelope meter is off.
+    pub(crate) fn envelope_level(&self) -> f32 {
+        if self.params.envelope {
+            self.envelope.level()
+        } else {
+            0.0
+        }
+    }
+
+    /// For `PitchSync`, a window of `Periods` periods of the input's pitch.
+    /// Until a pitch has been detected, this is None. If the input loses its
+    /// pitch, the last pitch detected is kept.
+    fn pitch_window(&mut self) -> Option<usize> {
+        if self.filter_history.len() >= DETECTION_SAMPLES {
+            let mut samples = [0.0; DETECTION_SAMPLES];
+            for (i, sample) in samples.iter_mut().enumerate() {
+                *sample = self.filter_history.get(DETECTION_SAMPLES - 1 - i);
+            }
+            if let Some(period) = pitch::detect_period(&samples) {
+                self.period = Some(period);
+            }
+        }
+        self.period
+            .map(|period| (period * self.params.periods).min(MAX_WINDOW_SIZE))
+    }
+
+    fn wet_dry_parameter(channel: usize) -> ParameterType {
+        if channel == 0 {
+            ParameterType::WetDryLeft
+        } else {
+            ParameterType::WetDryRight
+        }
+    }
+
+    fn wet_dry_knob(channel: usize, params: &Parameters) -> f32 {
+        if channel == 0 {
+            params.wet_dry_left
+        } else {
+            params.wet_dry_right
+        }
+    }
+
+    /// Advance the wet/dry smoothing and LFO by one sample and return the
+    /// amount of filtered signal in this channel's output. The LFO pulls the
+    /// knob towards its waveform by `LfoDepth`, so at full depth the wet/dry
+    /// follows the waveform alone.
+    fn wet_dry(&mut self) -> f32 {
+        let knob = self.wet_dry.advance();
+        let lfo = self
+            .lfo
+            .advance(self.params.lfo_shape, self.params.lfo_rate);
+        let knob = knob + (lfo - knob) * self.params.lfo_depth;
+        // On a send/return bus, the dry signal is already mixed in elsewhere.
+        if self.params.full_wet {
+            1.0
+        } else {
+            self.params.mix_curve.apply(knob)
+        }
+    }
+
+    /// The stages before and after the filter.
+    fn split_stages(&self) -> ([Stage; 2], usize) {
+        let stages = self.params.stages();
+        let filter_index = stages
+            .iter()
+            .position(|&stage| stage == Stage::Filter)
+            .expect("the filter stage is always present");
+        (stages, filter_index)
+    }
+
+    /// Run `x` through `stages`, like `apply_stages`, but with the saturation
+    /// oversampled if `OversampleDrive` is on.
+    fn run_stages(&mut self, stages: &[Stage], x: f32, drive: f32) -> f32 {
+        if !self.params.oversample_drive {
+            return apply_stages(stages, x, drive);
+        }
+        stages.iter().fold(x, |x, stage| match stage {
+            Stage::Filter => x,
+            Stage::Saturate => self.oversampler.process(x, |x| saturate(x, drive)),
+        })
+    }
+
+    /// Feed a sample into the filter and return the filtered sample.
+    fn filter_sample(&mut self, x: f32) -> f32 {
+        self.filter_history.push(x);
+        self.filter.consume(x);
+        select_sample(
+            &self.filter,
+            &self.filter_history,
+            self.window_size,
+            &self.params,
+        )
+    }
+
+    /// The window size that the wet signal lags behind by half of.
+    fn wet_window(&self) -> usize {
+        if self.params.bands > 1 {
+            self.params.max_band_window()
+        } else {
+            self.window_size
+        }
+    }
+
+    /// Fill an empty filter with `window_size - 1` samples, standing in for the
+    /// input from before processing started. `input` is the first block of
+    /// input the channel will process.
+    fn warm_up(&mut self, input: &[f32]) {
+        let first = match input.first() {
+            Some(&first) => first,
+            None => return,
+        };
+        // The window stands in for input which has been through the input
+        // gain, like the input which follows.
+        let input_gain = db_to_gain(self.input_gain.get());
+        let (stages, filter_index) = self.split_stages();
+        let pre_filter = &stages[..filter_index];
+        let padding = self.window_size - 1;
+        for i in (1..=padding).rev() {
+            let sample = match self.params.edge_mode {
+                EdgeMode::ZeroPad => 0.0,
+                EdgeMode::Replicate => first * input_gain,
+                // Mirror the input around the first sample. If the block is too
+                // short to mirror, repeat the last sample of the block.
+                EdgeMode::Reflect => input[i.min(input.len() - 1)] * input_gain,
+            };
+            self.filter
+                .consume(apply_stages(pre_filter, sample, self.drive.get()));
+        }
+    }
+
+    /// How much `x` is part of a transient, from 0.0 for a steady signal up to
+    /// 1.0 for a sudden jump in level out of silence.
+    fn transient(&mut self, x: f32) -> f32 {
+        let fast = self.fast_envelope.next(x.abs());
+        let slow = self.slow_envelope.next(x.abs());
+        if fast > 1.0e-9 {
+            ((fast - slow) / fast).max(0.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Measure the loudness of the dry and filtered signal, and return the gain
+    /// needed to bring the filtered signal up to the level of the dry signal.
+    /// The median filter removes energy from the signal, so without this the
+    /// output gets quieter as the wet/dry knob is turned up.
+    fn loudness_gain(&mut self, dry: f32, wet: f32) -> f32 {
+        let dry_energy = self.dry_energy.next(dry * dry);
+        let wet_energy = self.wet_energy.next(wet * wet);
+        let target = if wet_energy > 1.0e-9 {
+            (dry_energy / wet_energy).sqrt().min(MAX_LOUDNESS_GAIN)
+        } else {
+            1.0
+        };
+        self.loudness_gain.next(target)
+    }
+}
+
+impl EffectProcessor for MedianProcessor {
+    fn info() -> Info {
+        Info {
+            name: "Median Filter".to_string(),
+            vendor: "a2aaron".to_string(),
+            // Used by hosts to differentiate between plugins. This predates
+            // deriving the id from the name, and is kept so that hosts still
+            // find the plugin in saved projects.
+            unique_id: 612413,
+            version: 1,
+            category: Category::Effect,
+            // Subtract one here due to "error" type
+            parameters: ParameterType::COUNT as i32,
+            // Two audio inputs
+            inputs: 2,
+            // Two channel audio!
+            outputs: 2,
+            // For CC feedback.
+            midi_outputs: 1,
+            // Save presets with `get_preset_data`, so that they are loaded
+            // all at once.
+            preset_chunks: true,
+            // For now, fill in the rest of our fields with `Default` info.
+            ..Default::default()
+        }
+    }
+
+    fn new(channel: usize, params: &Parameters) -> MedianProcessor {
+        let window_size = Self::channel_window_size(channel, params);
+        MedianProcessor {
+            channel,
+            params: params.clone(),
+            // Room for the largest window is allocated up front, so that the
+            // window can be resized on the audio thread.
+            filter: SortedWindow::with_capacity(window_size, MAX_WINDOW_SIZE),
+            window_size,
+            history: DelayLine::new(),
+            filter_history: DelayLine::new(),
+            offset_history: DelayLine::new(),
+            bypass: Ramp::new(0.0, 44100.0, BYPASS_RAMP_MS),
+            dry_energy: OnePole::new(0.0, 44100.0, LOUDNESS_WINDOW_MS),
+            wet_energy: OnePole::new(0.0, 44100.0, LOUDNESS_WINDOW_MS),
+            loudness_gain: OnePole::new(1.0, 44100.0, LOUDNESS_SMOOTHING_MS),
+            makeup_target: 1.0,
+            makeup_gain: 1.0,
+            makeup_peak: OnePole::new(0.0, 44100.0, MAKEUP_RELEASE_MS),
+            input_energy: OnePole::new(0.0, 44100.0, LOUDNESS_WINDOW_MS),
+            output_energy: OnePole::new(0.0, 44100.0, LOUDNESS_WINDOW_MS),
+            bypass_gain: 1.0,
+            fast_envelope: OnePole::new(0.0, 44100.0, TRANSIENT_FAST_MS),
+            slow_envelope: OnePole::new(0.0, 44100.0, TRANSIENT_SLOW_MS),
+            splitter: TransientSplitter::new(44100.0),
+            unfiltered_history: DelayLine::new(),
+            base_window: window_size,
+            window_follower: WindowFollower::new(window_size),
+            oversampler: Oversampler::new(),
+            needs_warm_up: true,
+            gain: params.output_gain.linear(),
+            gain_step: 0.0,
+            limiter: LookaheadLimiter::new(44100.0),
+            multiband: Multiband::new(params),
+            crusher: Crusher::new(channel as u32),
+            period: None,
+            envelope: MedianEnvelope::new(envelope_window_samples(params.envelope_window, 44100.0)),
+            sample_rate: 44100.0,
+            wet_dry: Smoother::new(
+                RawParameters::get_smoothing(Self::wet_dry_parameter(channel)),
+                Self::wet_dry_knob(channel, params),
+                44100.0,
+            ),
+            drive: Smoother::new(
+                RawParameters::get_smoothing(ParameterType::Drive),
+                params.drive.db,
+                44100.0,
+            ),
+            lfo: Lfo::new(44100.0),
+            shelf: HighShelf::new(params.shelf_freq, params.shelf_gain.db, 44100.0),
+            input_gain: Smoother::new(
+                RawParameters::get_smoothing(ParameterType::InputGain),
+                params.input_gain.db,
+                44100.0,
+            ),
+        }
+    }
+}
+
+impl ChannelProcessor for MedianProcessor {
+    type Parameters = Parameters;
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.bypass.set_duration(sample_rate, BYPASS_RAMP_MS);
+        self.dry_energy.set_time(sample_rate, LOUDNESS_WINDOW_MS);
+        self.wet_energy.set_time(sample_rate, LOUDNESS_WINDOW_MS);
+        self.loudness_gain
+            .set_time(sample_rate, LOUDNESS_SMOOTHING_MS);
+        self.makeup_peak.set_time(sample_rate, MAKEUP_RELEASE_MS);
+        self.input_energy.set_time(sample_rate, LOUDNESS_WINDOW_MS);
+        self.output_energy.set_time(sample_rate, LOUDNESS_WINDOW_MS);
+        self.fast_envelope.set_time(sample_rate, TRANSIENT_FAST_MS);
+        self.slow_envelope.set_time(sample_rate, TRANSIENT_SLOW_MS);
+        self.splitter.set_sample_rate(sample_rate);
+        self.limiter.set_sample_rate(sample_rate);
+        self.multiband.set_sample_rate(sample_rate);
+        self.wet_dry.set_sample_rate(sample_rate);
+        self.drive.set_sample_rate(sample_rate);
+        self.input_gain.set_sample_rate(sample_rate);
+        self.shelf.set(
+            self.params.shelf_freq,
+            self.params.shelf_gain.db,
+            sample_rate,
+        );
+        self.sample_rate = sample_rate;
+    }
+
+    fn set_parameters(&mut self, params: &Parameters) {
+        self.params = params.clone();
+        self.wet_dry
+            .set_target(Self::wet_dry_knob(self.channel, params));
+        self.drive.set_target(params.drive.db);
+        self.input_gain.set_target(params.input_gain.db);
+        self.multiband.set_parameters(params);
+        self.shelf
+            .set(params.shelf_freq, params.shelf_gain.db, self.sample_rate);
+        let window_size = if params.pitch_sync {
+            self.pitch_window()
+                .unwrap_or_else(|| Self::channel_window_size(self.channel, params))
+        } else {
+            Self::channel_window_size(self.channel, params)
+        };
+        self.base_window = window_size;
+        // With `DynamicWindow`, the window is set at the start of each block
+        // instead, once the block's input is known.
+        if window_size != self.window_size && params.dynamic_window <= 0.0 {
+            self.set_window_size(window_size);
+        }
+        if params.envelope {
+            self.envelope.set_window_size(envelope_window_samples(
+                params.envelope_window,
+                self.sample_rate,
+            ));
+        }
+        self.bypass
+            .set_target(if params.bypass { 1.0 } else { 0.0 });
+    }
+
+    fn process_block(&mut self, input: &[f32], output: &mut [f32]) {
+        self.begin_block(input);
+        for (x, out) in input.iter().zip(output.iter_mut()) {
+            *out = self.process_sample(*x);
+        }
+        self.end_block();
+    }
+
+    fn process_sample(&mut self, input: f32) -> f32 {
+        let x = self.apply_input_gain(input);
+        let wet = self.filter(x);
+        self.mix(input, x, wet)
+    }
+
+    /// Clear the filter and input history.
+    fn reset(&mut self) {
+        // Everything is cleared in place, since this is called on the audio
+        // thread when the stereo mode or mono input changes.
+        self.filter.clear();
+        self.history.clear();
+        self.filter_history.clear();
+        self.offset_history.clear();
+        self.unfiltered_history.clear();
+        self.window_follower = WindowFollower::new(self.base_window);
+        self.oversampler.reset();
+        self.limiter.reset();
+        self.multiband.reset();
+        self.crusher.reset();
+        self.period = None;
+        self.envelope.reset();
+        self.shelf.reset();
+        self.needs_warm_up = true;
+    }
+}
+
+impl MedianProcessor {
+    /// Get ready to process `input`. Each block must start with a call to this
+    /// and end with a call to `end_block`.
+    pub(crate) fn begin_block(&mut self, input: &[f32]) {
+        if self.params.dynamic_window > 0.0 && !input.is_empty() {
+            let window_size =
+                self.window_follower
+                    .next(input, self.base_window, &self.params, self.sample_rate);
+            if window_size != self.window_size {
+                self.set_window_size(window_size);
+            }
+        }
+        // By now `set_parameters` has been called with the parameters for this
+        // block, so the filter is warmed up at the window size it will use.
+        if self.needs_warm_up && !input.is_empty() {
+            self.warm_up(input);
+            self.needs_warm_up = false;
+        }
+
+        let gain = self.params.output_gain.linear();
+        self.gain_step = match self.params.gain_mode {
+            GainMode::PerSample if !input.is_empty() => (gain - self.gain) / input.len() as f32,
+            _ => {
+                self.gain = gain;
+                0.0
+            }
+        };
+    }
+
+    pub(crate) fn end_block(&mut self) {
+        // Avoid drifting away from the target due to rounding in the ramp.
+        self.gain = self.params.output_gain.linear();
+        self.gain_step = 0.0;
+    }
+
+    /// Run `x` through the filter and the stages around it, returning the wet
+    /// signal.
+    pub(crate) fn filter(&mut self, x: f32) -> f32 {
+        if self.params.envelope {
+            self.envelope.process(x);
+        }
+        let x = self.offset_input(x);
+        let (x, unfiltered) = self.split_target(x);
+        let (stages, filter_index) = self.split_stages();
+        let drive = self.drive.advance();
+        let wet = self.run_stages(&stages[..filter_index], x, drive);
+        let wet = if self.params.bands > 1 {
+            self.multiband.process(wet, &self.params)
+        } else {
+            self.filter_sample(wet)
+        };
+        let wet = wet + unfiltered;
+        let wet = self.run_stages(&stages[filter_index + 1..], wet, drive);
+        self.crusher.process(
+            wet,
+            self.params.bit_crush,
+            self.params.sample_rate_reduce,
+            self.params.dither,
+        )
+    }
+
+    /// In left/right mode, delay the right channel's filter input by
+    /// `StereoOffset`. The median reacts to the delayed input differently
+    /// than the left channel does to the original, giving a subtle,
+    /// Haas-like sense of width. The dry signal isn't delayed.
+    fn offset_input(&mut self, x: f32) -> f32 {
+        if self.channel != 1 || self.params.stereo_mode != StereoMode::LeftRight {
+            return x;
+        }
+        self.offset_history.push(x);
+        self.offset_history.get(self.params.stereo_offset)
+    }
+
+    /// Split `x` according to `Target`, returning the part to filter and the
+    /// part to leave alone. The part left alone is delayed by the center of
+    /// the window, so that it lines up with the filtered part when they are
+    /// added back together.
+    fn split_target(&mut self, x: f32) -> (f32, f32) {
+        let (transient, sustain) = self.splitter.split(x);
+        let (filtered, unfiltered) = match self.params.target {
+            Target::Full => (x, 0.0),
+            Target::SustainOnly => (sustain, transient),
+            Target::TransientOnly => (transient, sustain),
+        };
+        self.unfiltered_history.push(unfiltered);
+        // Only the filtered part goes through saturation before the filter.
+        let delay = if self.params.saturate_before_filter {
+            dry_delay(self.wet_window(), true) + self.params.oversample_delay()
+        } else {
+            dry_delay(self.wet_window(), true)
+        };
+        (filtered, self.unfiltered_history.get(delay))
+    }
+
+    /// How far `offset_input` delays this channel's filter input.
+    fn input_offset(&self) -> usize {
+        if self.channel != 1 || self.params.stereo_mode != StereoMode::LeftRight {
+            0
+        } else {
+            self.params.stereo_offset
+        }
+    }
+
+    /// Apply `InputGain` to a sample of input, advancing its smoothing.
+    /// Everything but the bypassed signal goes through the input gain.
+    pub(crate) fn apply_input_gain(&mut self, input: f32) -> f32 {
+        input * db_to_gain(self.input_gain.advance())
+    }
+
+    /// Mix the wet signal returned by `filter` with `x`, the input after the
+    /// input gain, returning the output sample. `input` is the raw input,
+    /// which is crossfaded to when bypassed.
+    pub(crate) fn mix(&mut self, input: f32, x: f32, wet: f32) -> f32 {
+        let dry_delay = dry_delay(self.wet_window(), self.params.compensates_dry())
+            + self.params.oversample_delay();
+        // Line the dry signal up with everything which delays the wet signal,
+        // so that inverting the wet signal cancels the dry signal exactly.
+        let dry_delay = if self.params.wet_invert {
+            dry_delay + self.input_offset()
+        } else {
+            dry_delay
+        };
+        self.history.push(x);
+        let dry = self.history.get(dry_delay);
+
+        let wet = if self.params.constant_loudness {
+            self.makeup_target = self.loudness_gain(dry, wet);
+            self.makeup_gain = self.makeup_target;
+            wet * self.makeup_target
+        } else {
+            wet
+        };
+        let wet = self.shelf.process(wet);
+        let wet = if self.params.constant_loudness && self.params.safe_makeup {
+            wet * self.makeup_backoff(wet)
+        } else {
+            wet
+        };
+        // Let the attack of transients through by turning the filter down while
+        // they last. The input is ahead of the dry signal when it is delayed,
+        // which gives the filter a head start on getting out of the way.
+        let transient = self.transient(x);
+        let wet_dry = self.wet_dry() * (1.0 - self.params.transient_preserve * transient);
+        let mixed = if self.params.wet_invert {
+            dry - wet * wet_dry
+        } else {
+            dry * (1.0 - wet_dry) + wet * wet_dry
+        };
+        let out = if self.params.limit {
+            let ceiling = self.params.ceiling.linear();
+            match self.params.limit_mode {
+                LimitMode::SoftClip => soft_clip(mixed, ceiling),
+                LimitMode::Lookahead => self.limiter.process(mixed, ceiling),
+            }
+        } else {
+            mixed
+        };
+        self.gain += self.gain_step;
+        let out = match self.params.diagnostic {
+            Diagnostic::Off => out * self.gain + self.params.dc_trim,
+            // Only the change made by the filter is heard, so silence means
+            // the filter is doing nothing. The difference is limited, since
+            // the makeup gain can make it very loud.
+            Diagnostic::Null => soft_clip((dry - mixed) * db_to_gain(NULL_MAKEUP_DB), 1.0),
+        };
+        let bypass = self.bypass.next();
+        let bypass_gain = self.bypass_gain(input, out, bypass);
+        out * (1.0 - bypass) + input * bypass * bypass_gain
+    }
+
+    /// With `SafeMakeup`, return how much to back off the `ConstantLoudness`
+    /// makeup so that the made-up filtered signal `wet` stays under the
+    /// ceiling. The peak of `wet` jumps up at once and falls back slowly, so
+    /// the makeup backs off as soon as a peak would cross the ceiling, and
+    /// comes back smoothly afterwards. Only the makeup is backed off, so a
+    /// filtered signal which is over the ceiling by itself is left alone.
+    fn makeup_backoff(&mut self, wet: f32) -> f32 {
+        let peak = self.makeup_peak.next_peak(wet.abs());
+        let ceiling = self.params.ceiling.linear();
+        let backoff = if peak > ceiling { ceiling / peak } else { 1.0 };
+        let backoff = backoff.max(1.0 / self.makeup_target.max(1.0));
+        self.makeup_gain = self.makeup_target * backoff;
+        backoff
+    }
+
+    /// With `BypassGainMatch`, return the gain which brings the bypassed
+    /// signal `x` to the loudness of the processed output `out`, so that
+    /// toggling bypass compares the sound of the filter rather than its
+    /// level. The loudness is measured like `loudness_gain`, but only while
+    /// the output is fully processed, and the last gain measured is held
+    /// while bypassed.
+    fn bypass_gain(&mut self, x: f32, out: f32, bypass: f32) -> f32 {
+        if !self.params.bypass_gain_match {
+            return 1.0;
+        }
+        #[allow(clippy::float_cmp)]
+        if bypass == 0.0 {
+            let input_energy = self.input_energy.next(x * x);
+            let output_energy = self.output_energy.next(out * out);
+            if input_energy > 1.0e-9 {
+                self.bypass_gain = (output_energy / input_energy)
+                    .sqrt()
+                    .clamp(1.0 / MAX_LOUDNESS_GAIN, MAX_LOUDNESS_GAIN);
+            }
+        }
+        self.bypass_gain
+    }
+}
+
+/// Pick the filtered sample from a window which has just consumed the latest
+/// sample, according to the mode. `history` holds the samples the window
+/// consumed, most recent first.
+pub(crate) fn select_sample(
+    filter: &SortedWindow,
+    history: &DelayLine,
+    window_size: usize,
+    params: &Parameters,
+) -> f32 {
+    let median = if params.smart_window {
+        filter.median_ignoring_silence(params.silence_threshold.linear(), params.tie_break)
+    } else {
+        filter.median(params.tie_break)
+    };
+    match params.mode {
+        Mode::Smooth => median,
+        Mode::DeClick | Mode::Hampel => {
+            // Only replace samples which stray far from the median, since
+            // those are likely to be clicks. The median lags the input by
+            // about half a window, so compare against the sample it lines up
+            // with.
+            let sample = history.get((window_size - 1) / 2);
+            let threshold = if params.mode == Mode::Hampel {
+                let mad = filter.median_absolute_deviation(median, params.tie_break);
+                params.hampel_k * MAD_TO_STANDARD_DEVIATION * mad
+            } else {
+                params.threshold.linear()
+            };
+            if (sample - median).abs() > threshold {
+                median
+            } else {
+                sample
+            }
+        }
+    }
+}
+
+/// A step in the chain that the filtered signal passes through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stage {
+    /// The median filter itself.
+    Filter,
+    /// Soft saturation, controlled by `Drive`.
+    Saturate,
+}
+
+/// Run `x` through each of `stages` in order. The filter stage has state, so it
+/// is run by the `MedianProcessor` itself and is skipped here. `drive` is the
+/// saturation drive, in decibels.
+fn apply_stages(stages: &[Stage], x: f32, drive: f32) -> f32 {
+    stages.iter().fold(x, |x, stage| match stage {
+        Stage::Filter => x,
+        Stage::Saturate => saturate(x, drive),
+    })
+}
+
+/// Saturate `x` with a tanh curve. `drive` is the amount of gain, in decibels,
+/// applied before saturating. The output is scaled back down by the same
+/// amount, so quiet signals keep roughly the same level. A `drive` of zero
+/// disables saturation.
+pub(crate) fn saturate(x: f32, drive: f32) -> f32 {
+    if drive <= 0.0 {
+        x
+    } else {
+        let gain = db_to_gain(drive);
+        (x * gain).tanh() / gain
+    }
+}
+
+/// Softly limit `x` to the range `[-ceiling, ceiling]`. Samples below the knee
+/// are passed through untouched, while samples above it are smoothly compressed
+/// so that they never exceed the ceiling.
+fn soft_clip(x: f32, ceiling: f32) -> f32 {
+    // The fraction of the ceiling at which limiting starts.
+    const KNEE: f32 = 0.9;
+    let knee = ceiling * KNEE;
+    let magnitude = x.abs();
+    if magnitude <= knee {
+        x
+    } else {
+        let range = ceiling - knee;
+        (knee + range * ((magnitude - knee) / range).tanh()).copysign(x)
+    }
+}
+
+/// A value which moves linearly towards a target, taking a fixed amount of time
+/// to move a distance of 1.0.
+pub(crate) struct Ramp {
+    value: f32,
+    target: f32,
+    // How far the value moves each sample.
+    step: f32,
+}
+
+impl Ramp {
+    pub(crate) fn new(value: f32, sample_rate: f32, duration_ms: f32) -> Ramp {
+        let mut ramp = Ramp {
+            value,
+            target: value,
+            step: 0.0,
+        };
+        ramp.set_duration(sample_rate, duration_ms);
+        ramp
+    }
+
+    pub(crate) fn set_duration(&mut self, sample_rate: f32, duration_ms: f32) {
+        let samples = (duration_ms / 1000.0 * sample_rate).max(1.0);
+        self.step = 1.0 / samples;
+    }
+
+    pub(crate) fn set_target(&mut self, target: f32) {
+        self.target = target;
+    }
+
+    // Advance the ramp by one sample and return the new value.
+    pub(crate) fn next(&mut self) -> f32 {
+        let distance = self.target - self.value;
+        if distance.abs() <= self.step {
+            self.value = self.target;
+        } else {
+            self.value += self.step.copysign(distance);
+        }
+        self.value
+    }
+}
+
+/// A one-pole lowpass filter, which smoothly and exponentially approaches its
+/// input. Reaches about 63% of a step change after the time constant passes.
+struct OnePole {
+    value: f32,
+    coefficient: f32,
+}
+
+impl OnePole {
+    fn new(value: f32, sample_rate: f32, time_ms: f32) -> OnePole {
+        let mut one_pole = OnePole {
+            value,
+            coefficient: 0.0,
+        };
+        one_pole.set_time(sample_rate, time_ms);
+        one_pole
+    }
+
+    fn set_time(&mut self, sample_rate: f32, time_ms: f32) {
+        let samples = (time_ms / 1000.0 * sample_rate).max(1.0);
+        self.coefficient = (-1.0 / samples).exp();
+    }
+
+    // Advance the filter by one sample towards `input` and return the new value.
+    fn next(&mut self, input: f32) -> f32 {
+        self.value = input + (self.value - input) * self.coefficient;
+        self.value
+    }
+
+    // Like `next`, but jump straight to `input` if it is above the value, so
+    // that the filter follows the peaks of the input.
+    fn next_peak(&mut self, input: f32) -> f32 {
+        if input > self.value {
+            self.value = input;
+            self.value
+        } else {
+            self.next(input)
+        }
+    }
+}
+
+/// Splits a signal into its transients and the sustain between them, by how
+/// far a fast envelope of the signal jumps above a slow one, like
+/// `MedianProcessor::transient`. The two parts always add up to the signal.
+struct TransientSplitter {
+    fast_envelope: OnePole,
+    slow_envelope: OnePole,
+}
+
+impl TransientSplitter {
+    fn new(sample_rate: f32) -> TransientSplitter {
+        TransientSplitter {
+            fast_envelope: OnePole::new(0.0, sample_rate, TRANSIENT_FAST_MS),
+            slow_envelope: OnePole::new(0.0, sample_rate, TRANSIENT_SLOW_MS),
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.fast_envelope.set_time(sample_rate, TRANSIENT_FAST_MS);
+        self.slow_envelope.set_time(sample_rate, TRANSIENT_SLOW_MS);
+    }
+
+    /// Split `x`, returning its transient and sustain parts.
+    fn split(&mut self, x: f32) -> (f32, f32) {
+        let fast = self.fast_envelope.next(x.abs());
+        let slow = self.slow_envelope.next(x.abs());
+        let amount = if fast > 1.0e-9 {
+            ((fast - slow) / fast).max(0.0)
+        } else {
+            0.0
+        };
+        let transient = x * amount;
+        (transient, x - transient)
+    }
+}
+
+/// Shrinks the window on transients for `DynamicWindow`, so that attacks
+/// are smoothed less than the sustain after them. Once a block, the window
+/// moves towards a target, at `WindowAttack` when the target is smaller and
+/// at `WindowRelease` when it is larger. The target is the window set by the
+/// parameters, shrunk by how far the block's peak jumps above the recent
+/// level of the input.
+struct WindowFollower {
+    window: f32,
+    // The recent level of the input, or None before the first block, so that
+    // the input starting isn't taken for a transient.
+    level: Option<f32>,
+}
+
+impl WindowFollower {
+    fn new(window_size: usize) -> WindowFollower {
+        WindowFollower {
+            window: window_size as f32,
+            level: None,
+        }
+    }
+
+    /// Follow a block of input, returning the window size to use for it.
+    fn next(
+        &mut self,
+        input: &[f32],
+        base_window: usize,
+        params: &Parameters,
+        sample_rate: f32,
+    ) -> usize {
+        let samples = input.len();
+        let peak = meters::peak(input);
+        let level = self.level.unwrap_or(peak);
+        let transient = if peak > 1.0e-9 {
+            ((peak - level) / peak).max(0.0)
+        } else {
+            0.0
+        };
+        self.level = Some(meters::smooth(
+            level,
+            peak,
+            TRANSIENT_SLOW_MS,
+            samples,
+            sample_rate,
+        ));
+        let target = base_window as f32 * (1.0 - params.dynamic_window * transient);
+        let time_ms = if target < self.window {
+            params.window_attack
+        } else {
+            params.window_release
+        };
+        self.window = meters::smooth(self.window, target, time_ms, samples, sample_rate);
+        (self.window.round() as usize).clamp(1, base_window.max(1))
+    }
+}
+
+/// Reduces the bit depth and sample rate of a signal, for a lo-fi sound.
+struct Crusher {
+    // The sample being held while the sample rate is reduced.
+    held: f32,
+    // How many more samples `held` is held for.
+    remaining: usize,
+    // The state of the dither noise generator. The noise is deterministic, so
+    // that renders are repeatable.
+    seed: u32,
+    initial_seed: u32,
+}
+
+impl Crusher {
+    fn new(seed: u32) -> Crusher {
+        // Xorshift gets stuck on zero.
+        let seed = seed.wrapping_mul(0x9E37_79B9) | 1;
+        Crusher {
+            held: 0.0,
+            remaining: 0,
+            seed,
+            initial_seed: seed,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.held = 0.0;
+        self.remaining = 0;
+        self.seed = self.initial_seed;
+    }
+
+    /// Quantize `x` to `bits` bits, and hold each sample for `reduce` samples.
+    /// With `dither`, triangular noise of one step is added before quantizing,
+    /// which trades the distortion of quantizing for a little noise.
+    fn process(&mut self, x: f32, bits: u32, reduce: usize, dither: bool) -> f32 {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            return self.held;
+        }
+        self.remaining = reduce.max(1) - 1;
+        self.held = if bits >= MAX_BIT_DEPTH {
+            x
+        } else {
+            // The number of steps between -1.0 and 1.0.
+            let step = 2.0 / (1u32 << bits) as f32;
+            let noise = if dither {
+                self.random() - self.random()
+            } else {
+                0.0
+            };
+            ((x / step + noise).round() * step).clamp(-1.0, 1.0)
+        };
+        self.held
+    }
+
+    // A random number from 0.0 to 1.0.
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
+
+/// A fixed-size delay line holding the last `MAX_WINDOW_SIZE` samples.
+pub(crate) struct DelayLine {
+    buffer: Box<[f32]>,
+    // The index of the most recently pushed sample.
+    position: usize,
+    // The number of samples pushed, up to `MAX_WINDOW_SIZE`.
+    len: usize,
+}
+
+impl DelayLine {
+    pub(crate) fn new() -> DelayLine {
+        DelayLine {
+            buffer: vec![0.0; MAX_WINDOW_SIZE].into_boxed_slice(),
+            position: 0,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, input: f32) {
+        self.position = (self.position + 1) % MAX_WINDOW_SIZE;
+        self.buffer[self.position] = input;
+        self.len = (self.len + 1).min(MAX_WINDOW_SIZE);
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    // Forget every sample pushed so far, without reallocating.
+    pub(crate) fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.position = 0;
+        self.len = 0;
+    }
+
+    // Return the sample that was pushed `delay` samples ago. A `delay` of zero
+    // returns the most recently pushed sample.
+    pub(crate) fn get(&self, delay: usize) -> f32 {
+        debug_assert!(delay < MAX_WINDOW_SIZE);
+        self.buffer[(self.position + MAX_WINDOW_SIZE - delay) % MAX_WINDOW_SIZE]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vst::api::{TimeInfo, TimeInfoFlags};
+
+    use common::test_util::null_host;
+
+    use super::*;
+    use crate::{
+        lfo::NoteDivision, multiband::MAX_BANDS, parameters::Window, MedianFilter, TieBreak,
+    };
+
+    /// At 50% wet, the dry signal lines up with the filtered signal only when
+    /// `CompensateDry` is on. On a slowly changing signal the median is close
+    /// to the sample in the middle of the window, so the mix should then be
+    /// the input delayed by half the window.
+    #[test]
+    fn compensate_dry_at_half_wet() {
+        const WINDOW_SIZE: usize = 5;
+        let delay = (WINDOW_SIZE - 1) / 2;
+        let input: Vec<f32> = (0..1000)
+            .map(|i| (i as f32 * std::f32::consts::TAU / 200.0).sin())
+            .collect();
+        for &compensate_dry in [true, false].iter() {
+            let mut params = MedianFilter::test_parameters(WINDOW_SIZE);
+            params.wet_dry_left = 0.5;
+            params.compensate_dry = compensate_dry;
+            let mut output = vec![0.0; input.len()];
+            MedianProcessor::new(0, &params).process_block(&input, &mut output);
+
+            let residual = (WINDOW_SIZE..input.len())
+                .map(|i| (output[i] - input[i - delay]).abs())
+                .fold(0.0, f32::max);
+            assert_eq!(
+                residual < 0.005,
+                compensate_dry,
+                "CompensateDry {}: residual {}",
+                compensate_dry,
+                residual
+            );
+        }
+    }
+
+    /// De-Click replaces the clicks on a sine wave with the median, and passes
+    /// the rest of the sine through untouched.
+    #[test]
+    fn declick_removes_clicks() {
+        const WINDOW_SIZE: usize = 7;
+        const CLICK_SPACING: usize = 50;
+        let mut params = MedianFilter::test_parameters(WINDOW_SIZE);
+        params.mode = Mode::DeClick;
+        // Well above how far the sine moves over a window, and well below the
+        // size of the clicks.
+        params.threshold.db = -6.0;
+
+        let len = CLICK_SPACING * 20;
+        let sine: Vec<f32> = (0..len)
+            .map(|i| 0.5 * (i as f32 * std::f32::consts::TAU / 64.0).sin())
+            .collect();
+        let mut input = sine.clone();
+        for i in (CLICK_SPACING / 2..len).step_by(CLICK_SPACING) {
+            input[i] += 0.8;
+        }
+        let mut output = vec![0.0; len];
+        MedianProcessor::new(0, &params).process_block(&input, &mut output);
+
+        // The output lines up with the sample in the middle of the window.
+        let delay = (WINDOW_SIZE - 1) / 2;
+        for (i, &out) in output[delay..].iter().enumerate() {
+            #[allow(clippy::float_cmp)]
+            if input[i] != sine[i] {
+                assert!(
+                    (out - sine[i]).abs() < 0.2,
+                    "click at {} left as {}",
+                    i,
+                    out
+                );
+            } else {
+                assert_eq!(out, input[i], "sample {} was changed", i);
+            }
+        }
+    }
+
+    /// With Limit on, an over-unity signal is held under the ceiling. With it
+    /// off, the signal is passed through as it is.
+    #[test]
+    fn limit_holds_output_under_ceiling() {
+        let input: Vec<f32> = (0..1000).map(|i| 2.0 * (i as f32 * 0.05).sin()).collect();
+        for &limit in [true, false].iter() {
+            // A window of one sample passes the input straight through.
+            let mut params = MedianFilter::test_parameters(1);
+            params.limit = limit;
+            params.ceiling.db = -1.0;
+            let mut output = vec![0.0; input.len()];
+            MedianProcessor::new(0, &params).process_block(&input, &mut output);
+
+            if limit {
+                let peak = output.iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+                assert!(peak <= params.ceiling.linear(), "peak of {}", peak);
+            } else {
+                assert_eq!(output, input);
+            }
+        }
+    }
+
+    /// Toggling bypass crossfades to the input over `BYPASS_RAMP_MS`, rather
+    /// than jumping to it.
+    #[test]
+    fn bypass_crossfades() {
+        // Only the change made by the filter is heard, and a DC input isn't
+        // changed, so the processed output is silent and the input is not.
+        let mut params = MedianFilter::test_parameters(5);
+        params.diagnostic = Diagnostic::Null;
+        let input = vec![0.5; 2000];
+        let mut processor = MedianProcessor::new(0, &params);
+        let mut output = vec![0.0; input.len()];
+        processor.process_block(&input[..1000], &mut output[..1000]);
+        params.bypass = true;
+        processor.set_parameters(&params);
+        processor.process_block(&input[1000..], &mut output[1000..]);
+
+        assert_eq!(output[999], 0.0);
+        assert_eq!(output[1999], 0.5);
+        let ramp_samples = BYPASS_RAMP_MS / 1000.0 * 44100.0;
+        let largest_step = output[999..]
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs())
+            .fold(0.0, f32::max);
+        assert!(
+            largest_step < 2.0 * 0.5 / ramp_samples,
+            "step of {}",
+            largest_step
+        );
+    }
+
+    /// With ForceOdd on, every window is odd, so that even with the Average
+    /// tie-break the output is always one of the samples in the window.
+    #[test]
+    fn force_odd_outputs_input_samples() {
+        let raw = RawParameters::default(null_host());
+        raw.set(1.0, ParameterType::ForceOdd);
+        for i in 0..=100 {
+            raw.set(i as f32 / 100.0, ParameterType::WindowSize);
+            let window = Parameters::from(&raw).window_size.samples;
+            assert_eq!(window % 2, 1, "window of {} samples", window);
+        }
+
+        // A window of 8 samples, which ForceOdd takes down to 7.
+        raw.set(0.085, ParameterType::WindowSize);
+        let mut params = MedianFilter::test_parameters(1);
+        params.window_size = Parameters::from(&raw).window_size;
+        params.tie_break = TieBreak::Average;
+        let window_size = params.window_size.samples;
+        assert_eq!(window_size, 7);
+
+        let input: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.37).sin()).collect();
+        let mut output = vec![0.0; input.len()];
+        MedianProcessor::new(0, &params).process_block(&input, &mut output);
+        for i in window_size..input.len() {
+            let window = &input[i + 1 - window_size..=i];
+            assert!(
+                window.contains(&output[i]),
+                "output {} at sample {} is not in the window",
+                output[i],
+                i
+            );
+        }
+    }
+
+    /// When the window jumps to a new size, it is refilled from the recent
+    /// input, so a steady signal carries on through the change instead of
+    /// starting over with a partly filled window.
+    #[test]
+    fn window_jump_keeps_steady_output() {
+        // A pulse every third sample, which every full window turns into
+        // silence, but which a partly filled window may not.
+        let input: Vec<f32> = (0..1500)
+            .map(|i| if i % 3 == 0 { 1.0 } else { 0.0 })
+            .collect();
+        let mut params = MedianFilter::test_parameters(5);
+        params.tie_break = TieBreak::Upper;
+        let mut output = vec![0.0; input.len()];
+        let mut processor = MedianProcessor::new(0, &params);
+        for (i, &window_size) in [5, 51, 5].iter().enumerate() {
+            params.window_size = Window::new(window_size);
+            processor.set_parameters(&params);
+            let block = i * 500..(i + 1) * 500;
+            processor.process_block(&input[block.clone()], &mut output[block]);
+        }
+        for (i, &out) in output.iter().enumerate().skip(5) {
+            assert_eq!(out, 0.0, "sample {}", i);
+        }
+    }
+
+    /// With ConstantLoudness on, sweeping the mix keeps the output about as
+    /// loud as the input, even though the filter takes the noise out of a
+    /// noisy sine.
+    #[test]
+    fn constant_loudness_keeps_level() {
+        let mut seed: u32 = 1;
+        let input: Vec<f32> = (0..44100)
+            .map(|i| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let noise = (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5;
+                0.5 * (i as f32 * std::f32::consts::TAU / 100.0).sin() + noise
+            })
+            .collect();
+        let rms = |x: &[f32]| (x.iter().map(|x| x * x).sum::<f32>() / x.len() as f32).sqrt();
+        // Leave out the first half second, while the loudness measurement
+        // settles.
+        let settled = input.len() / 2..;
+        let dry_rms = rms(&input[settled.clone()]);
+
+        let level_change_db = |constant_loudness: bool, wet_dry: f32| {
+            let mut params = MedianFilter::test_parameters(9);
+            params.compensate_dry = true;
+            params.constant_loudness = constant_loudness;
+            params.wet_dry_left = wet_dry;
+            let mut output = vec![0.0; input.len()];
+            MedianProcessor::new(0, &params).process_block(&input, &mut output);
+            20.0 * (rms(&output[settled.clone()]) / dry_rms).log10()
+        };
+        for &wet_dry in [0.25, 0.5, 0.75, 1.0].iter() {
+            let change_db = level_change_db(true, wet_dry);
+            assert!(change_db.abs() < 1.0, "{} dB at {} wet", change_db, wet_dry);
+        }
+        // Without it, the filtered signal is noticeably quieter.
+        assert!(level_change_db(false, 1.0) < -1.0);
+    }
+
+    /// Each edge mode fills the window differently before the first sample,
+    /// which shows in the output until the window has filled with input.
+    #[test]
+    fn edge_modes_fill_the_window() {
+        let input = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+        let expected = [
+            (EdgeMode::ZeroPad, [0.0, 0.0, 1.0, 2.0, 3.0]),
+            (EdgeMode::Replicate, [1.0, 1.0, 1.0, 2.0, 3.0]),
+            // The window starts out as 5, 4, 3, 2.
+            (EdgeMode::Reflect, [3.0, 2.0, 2.0, 2.0, 3.0]),
+        ];
+        for &(edge_mode, expected) in expected.iter() {
+            let mut params = MedianFilter::test_parameters(5);
+            params.edge_mode = edge_mode;
+            let mut output = [0.0; 8];
+            MedianProcessor::new(0, &params).process_block(&input, &mut output);
+            assert_eq!(output[..5], expected, "{}", edge_mode);
+            assert_eq!(output[5..], [4.0, 5.0, 6.0], "{}", edge_mode);
+        }
+    }
+
+    /// With FullWet on, the Wet/Dry knob is ignored and the output is the
+    /// filtered signal alone.
+    #[test]
+    fn full_wet_ignores_wet_dry() {
+        let input: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.37).sin()).collect();
+        let process = |params: &Parameters| {
+            let mut output = vec![0.0; input.len()];
+            MedianProcessor::new(0, params).process_block(&input, &mut output);
+            output
+        };
+        let mut params = MedianFilter::test_parameters(5);
+        let filtered = process(&params);
+        params.full_wet = true;
+        for &wet_dry in [0.0, 0.5, 1.0].iter() {
+            params.wet_dry_left = wet_dry;
+            assert_eq!(process(&params), filtered, "{} wet", wet_dry);
+        }
+    }
+
+    /// SaturateBeforeFilter swaps the order of the saturation and the filter.
+    /// The two orders only differ when the median averages two samples, so
+    /// this uses an even window.
+    #[test]
+    fn saturate_before_filter_order() {
+        const WINDOW_SIZE: usize = 4;
+        const DRIVE_DB: f32 = 12.0;
+        let input: Vec<f32> = (0..1000).map(|i| 0.9 * (i as f32 * 0.37).sin()).collect();
+        let median = |window: &[f32]| {
+            let mut window = window.to_vec();
+            window.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            (window[1] + window[2]) / 2.0
+        };
+        let mut outputs = Vec::new();
+        for &saturate_before_filter in [true, false].iter() {
+            let mut params = MedianFilter::test_parameters(WINDOW_SIZE);
+            params.tie_break = TieBreak::Average;
+            params.drive.db = DRIVE_DB;
+            params.saturate_before_filter = saturate_before_filter;
+            let mut output = vec![0.0; input.len()];
+            MedianProcessor::new(0, &params).process_block(&input, &mut output);
+
+            for i in WINDOW_SIZE..input.len() {
+                let window = &input[i + 1 - WINDOW_SIZE..=i];
+                let expected = if saturate_before_filter {
+                    let saturated: Vec<f32> =
+                        window.iter().map(|&x| saturate(x, DRIVE_DB)).collect();
+                    median(&saturated)
+                } else {
+                    saturate(median(window), DRIVE_DB)
+                };
+                assert!(
+                    (output[i] - expected).abs() < 1.0e-6,
+                    "SaturateBeforeFilter {}: expected {} at sample {}, got {}",
+                    saturate_before_filter,
+                    expected,
+                    i,
+                    output[i]
+                );
+            }
+            outputs.push(output);
+        }
+        assert_ne!(outputs[0], outputs[1]);
+    }
+
+    /// Unlinked, each channel follows its own Wet/Dry knob. Linked, both
+    /// follow the left knob.
+    #[test]
+    fn per_channel_wet_dry() {
+        let input: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.37).sin()).collect();
+        let raw = RawParameters::default(null_host());
+        raw.set(0.0, ParameterType::WetDryLeft);
+        raw.set(1.0, ParameterType::WetDryRight);
+        let process = |channel: usize| {
+            let snapshot = Parameters::from(&raw);
+            let mut params = MedianFilter::test_parameters(5);
+            params.wet_dry_left = snapshot.wet_dry_left;
+            params.wet_dry_right = snapshot.wet_dry_right;
+            let mut output = vec![0.0; input.len()];
+            MedianProcessor::new(channel, &params).process_block(&input, &mut output);
+            output
+        };
+        let filtered = {
+            let mut output = vec![0.0; input.len()];
+            MedianProcessor::new(0, &MedianFilter::test_parameters(5))
+                .process_block(&input, &mut output);
+            output
+        };
+
+        raw.set(0.0, ParameterType::LinkWetDry);
+        assert_eq!(process(0), input);
+        assert_eq!(process(1), filtered);
+
+        raw.set(1.0, ParameterType::LinkWetDry);
+        assert_eq!(process(0), input);
+        assert_eq!(process(1), input);
+    }
+
+    /// Hampel mode replaces the spikes on a sine wave, and passes the sine
+    /// through untouched.
+    #[test]
+    fn hampel_removes_spikes() {
+        const WINDOW_SIZE: usize = 7;
+        const SPIKE_SPACING: usize = 50;
+        let mut params = MedianFilter::test_parameters(WINDOW_SIZE);
+        params.mode = Mode::Hampel;
+        params.hampel_k = 3.0;
+
+        let len = SPIKE_SPACING * 20;
+        let sine: Vec<f32> = (0..len)
+            .map(|i| (i as f32 * std::f32::consts::TAU / 64.0).sin())
+            .collect();
+        let mut input = sine.clone();
+        for i in (SPIKE_SPACING / 2..len).step_by(SPIKE_SPACING) {
+            input[i] += 10.0;
+        }
+        let mut output = vec![0.0; len];
+        MedianProcessor::new(0, &params).process_block(&input, &mut output);
+
+        // The output lines up with the sample in the middle of the window.
+        let delay = (WINDOW_SIZE - 1) / 2;
+        for (i, &out) in output[delay..].iter().enumerate() {
+            #[allow(clippy::float_cmp)]
+            if input[i] != sine[i] {
+                assert!(
+                    (out - sine[i]).abs() < 0.2,
+                    "spike at {} left as {}",
+                    i,
+                    out
+                );
+            } else {
+                assert_eq!(out, input[i], "sample {} was changed", i);
+            }
+        }
+    }
+
+    /// TransientPreserve lets the attack of a drum-like hit through the
+    /// filter, so more of its peak survives.
+    #[test]
+    fn transient_preserve_keeps_attacks() {
+        // Silence, then a buzzy hit which dies away quickly.
+        let input: Vec<f32> = (0..4000usize)
+            .map(|i| match i.checked_sub(2000) {
+                Some(t) => (-(t as f32) / 200.0).exp() * (t as f32 * 0.9).sin(),
+                None => 0.0,
+            })
+            .collect();
+        let peak = |transient_preserve: f32| {
+            let mut params = MedianFilter::test_parameters(15);
+            params.transient_preserve = transient_preserve;
+            let mut output = vec![0.0; input.len()];
+            MedianProcessor::new(0, &params).process_block(&input, &mut output);
+            output.iter().fold(0.0f32, |peak, x| peak.max(x.abs()))
+        };
+        let filtered = peak(0.0);
+        let preserved = peak(1.0);
+        assert!(
+            preserved > 2.0 * filtered,
+            "peak of {} with TransientPreserve, {} without",
+            preserved,
+            filtered
+        );
+    }
+
+    /// When the output gain changes, Per Block jumps straight to the new gain,
+    /// while Per Sample ramps to it over the block.
+    #[test]
+    fn gain_modes() {
+        const BLOCK_SIZE: usize = 100;
+        let input = [1.0; BLOCK_SIZE];
+        for &gain_mode in [GainMode::PerBlock, GainMode::PerSample].iter() {
+            // A window of one sample passes the input straight through.
+            let mut params = MedianFilter::test_parameters(1);
+            params.gain_mode = gain_mode;
+            params.output_gain.db = 0.0;
+            let mut processor = MedianProcessor::new(0, &params);
+            let mut output = [0.0; BLOCK_SIZE];
+            processor.process_block(&input, &mut output);
+            params.output_gain.db = -6.0;
+            processor.set_parameters(&params);
+            processor.process_block(&input, &mut output);
+
+            let gain = params.output_gain.linear();
+            match gain_mode {
+                GainMode::PerBlock => assert_eq!(output, [gain; BLOCK_SIZE]),
+                GainMode::PerSample => {
+                    assert!(output[0] < 1.0 && output[0] > 0.99);
+                    assert!(output.windows(2).all(|pair| pair[1] < pair[0]));
+                    assert!((output[BLOCK_SIZE - 1] - gain).abs() < 1.0e-6);
+                }
+            }
+        }
+    }
+
+    /// The null test is silent when the filter is fully dry, and isn't when
+    /// the filter changes the signal.
+    #[test]
+    fn null_test() {
+        let mut params = MedianFilter::test_parameters(5);
+        params.diagnostic = Diagnostic::Null;
+        // A square wave, whose corners are rounded off by the filter.
+        let input: Vec<f32> = (0..256)
+            .map(|i| if (i / 16) % 2 == 0 { 0.5 } else { -0.5 })
+            .collect();
+        for &wet_dry in [0.0, 1.0].iter() {
+            params.wet_dry_left = wet_dry;
+            let mut output = vec![0.0; input.len()];
+            MedianProcessor::new(0, &params).process_block(&input, &mut output);
+            let silent = output.iter().all(|&x| x == 0.0);
+            assert_eq!(silent, wet_dry == 0.0, "{}% wet", wet_dry * 100.0);
+        }
+    }
+
+    /// With every band at 0% wet, the bands sum back to the input, delayed by
+    /// half of the largest band window.
+    #[test]
+    fn multiband_sums_to_input() {
+        let mut params = MedianFilter::test_parameters(5);
+        params.bands = MAX_BANDS;
+        params.low_band_window = Window::new(3);
+        params.mid_band_window = Window::new(9);
+        params.high_band_window = Window::new(5);
+        params.low_band_wet_dry = 0.0;
+        params.mid_band_wet_dry = 0.0;
+        params.high_band_wet_dry = 0.0;
+        let delay = (params.max_band_window() - 1) / 2;
+
+        let input: Vec<f32> = (0..512)
+            .map(|i| {
+                let i = i as f32;
+                (i * 0.01).sin() * 0.5 + (i * 0.7).sin() * 0.3 + (i * 2.1).sin() * 0.2
+            })
+            .collect();
+        let mut output = vec![0.0; input.len()];
+        let mut channel = MedianProcessor::new(0, &params);
+        channel.set_parameters(&params);
+        channel.process_block(&input, &mut output);
+        for (i, &out) in output[delay..].iter().enumerate() {
+            assert!(
+                (out - input[i]).abs() <= 1e-5,
+                "sample {} is {} instead of {}",
+                i,
+                out,
+                input[i]
+            );
+        }
+    }
+
+    /// The full bit depth leaves the signal alone, and a reduced bit depth
+    /// only outputs the expected levels, with or without dither.
+    #[test]
+    fn bit_crush_levels() {
+        const BITS: u32 = 3;
+        let input: Vec<f32> = (0..256)
+            .map(|i| (i as f32 * std::f32::consts::TAU / 64.0).sin() * 0.9)
+            .collect();
+        let mut crusher = Crusher::new(0);
+        for &x in input.iter() {
+            assert_eq!(crusher.process(x, MAX_BIT_DEPTH, 1, false), x);
+        }
+        let step = 2.0 / (1 << BITS) as f32;
+        for &dither in [false, true].iter() {
+            for &x in input.iter() {
+                let level = crusher.process(x, BITS, 1, dither) / step;
+                assert!(
+                    (level - level.round()).abs() <= 1e-6,
+                    "{} steps (dither: {})",
+                    level,
+                    dither
+                );
+            }
+        }
+    }
+
+    /// `PitchSync` locks the window to a multiple of the period of a sine
+    /// wave, resizing the window in place rather than allocating a new one on
+    /// the audio thread.
+    #[test]
+    fn pitch_sync_locks_to_period() {
+        const PERIOD: usize = 100;
+        const PERIODS: usize = 3;
+        let mut params = MedianFilter::test_parameters(5);
+        params.pitch_sync = true;
+        params.periods = PERIODS;
+        let input: Vec<f32> = (0..4096)
+            .map(|i| (i as f32 * std::f32::consts::TAU / PERIOD as f32).sin() * 0.5)
+            .collect();
+        let mut output = vec![0.0; 512];
+        let mut processor = MedianProcessor::new(0, &params);
+        for block in input.chunks(512) {
+            processor.set_parameters(&params);
+            processor.process_block(block, &mut output[..block.len()]);
+        }
+        processor.set_parameters(&params);
+        assert_eq!(processor.window_size, PERIOD * PERIODS);
+        assert_eq!(processor.filter.window_size(), processor.window_size);
+        assert_eq!(processor.filter.capacity(), MAX_WINDOW_SIZE);
+    }
+
+    /// `DcTrim` raises the mean of a zero-mean signal by exactly the trim.
+    #[test]
+    fn dc_trim() {
+        const TRIM: f32 = 0.1;
+        let mut params = MedianFilter::test_parameters(5);
+        params.wet_dry_left = 0.0;
+        params.dc_trim = TRIM;
+        // A whole number of periods, so that the input's mean is zero.
+        let input: Vec<f32> = (0..1000)
+            .map(|i| (i as f32 * std::f32::consts::TAU / 100.0).sin())
+            .collect();
+        let mut output = vec![0.0; input.len()];
+        MedianProcessor::new(0, &params).process_block(&input, &mut output);
+        let mean = |x: &[f32]| x.iter().sum::<f32>() / x.len() as f32;
+        let change = mean(&output) - mean(&input);
+        assert!((change - TRIM).abs() <= 1e-4, "the mean rose by {}", change);
+    }
+
+    /// The LFO leaves the wet/dry alone at zero depth, and at full depth
+    /// sweeps the whole range once per cycle, in sync with the host's song
+    /// position.
+    #[test]
+    fn lfo_sweeps_wet_dry() {
+        const SAMPLE_RATE: f32 = 44100.0;
+        const TEMPO: f64 = 120.0;
+        // A quarter note lasts half a second at 120 BPM.
+        const CYCLE: usize = (SAMPLE_RATE / 2.0) as usize;
+        let mut params = MedianFilter::test_parameters(5);
+        params.wet_dry_left = 0.5;
+        params.lfo_rate = NoteDivision::Quarter;
+        let mut processor = MedianProcessor::new(0, &params);
+        processor.set_sample_rate(SAMPLE_RATE);
+        processor.lfo.set_sample_rate(SAMPLE_RATE);
+        for i in 0..2 * CYCLE {
+            assert_eq!(processor.wet_dry(), 0.5, "sample {}", i);
+        }
+
+        params.lfo_depth = 1.0;
+        processor.set_parameters(&params);
+        // Start a quarter of a beat into the song, so that the first cycle's
+        // peak comes a quarter of a cycle in.
+        processor.lfo.sync(Some(TimeInfo {
+            ppq_pos: 0.25,
+            tempo: TEMPO,
+            flags: (TimeInfoFlags::PPQ_POS_VALID | TimeInfoFlags::TEMPO_VALID).bits(),
+            ..Default::default()
+        }));
+        let sweep: Vec<f32> = (0..CYCLE).map(|_| processor.wet_dry()).collect();
+        let min = sweep.iter().cloned().fold(f32::MAX, f32::min);
+        let max = sweep.iter().cloned().fold(f32::MIN, f32::max);
+        assert!(
+            min <= 1e-4 && max >= 1.0 - 1e-4,
+            "swept from {} to {}",
+            min,
+            max
+        );
+        // The peak and trough land where the song position says they should.
+        assert!(
+            sweep[CYCLE / 4] >= 1.0 - 1e-4,
+            "peak is {}",
+            sweep[CYCLE / 4]
+        );
+        assert!(
+            sweep[3 * CYCLE / 4] <= 1e-4,
+            "trough is {}",
+            sweep[3 * CYCLE / 4]
+        );
+    }
+
+    /// Without a stereo offset both channels filter the same input in time,
+    /// and an offset delays the right channel's filter input by that many
+    /// samples.
+    #[test]
+    fn stereo_offset_delays_right() {
+        let input: Vec<f32> = (0..256).map(|i| (i as f32 * 0.1).sin()).collect();
+        for &offset in [0, 5].iter() {
+            // A window of one sample passes the filter input straight through.
+            let mut params = MedianFilter::test_parameters(1);
+            params.wet_dry_right = 1.0;
+            params.stereo_offset = offset;
+            let mut outputs = [vec![0.0; input.len()], vec![0.0; input.len()]];
+            for (channel, output) in outputs.iter_mut().enumerate() {
+                MedianProcessor::new(channel, &params).process_block(&input, output);
+            }
+            let [left, right] = &outputs;
+            assert_eq!(
+                right[offset..],
+                left[..input.len() - offset],
+                "offset {}",
+                offset
+            );
+        }
+    }
+
+    /// With `BypassGainMatch`, a quiet processed signal and the bypassed
+    /// signal have about the same RMS.
+    #[test]
+    fn bypass_gain_match() {
+        const SAMPLES: usize = 44100;
+        let mut params = MedianFilter::test_parameters(5);
+        params.output_gain.db = -12.0;
+        params.bypass_gain_match = true;
+        let input: Vec<f32> = (0..SAMPLES)
+            .map(|i| (i as f32 * std::f32::consts::TAU / 100.0).sin())
+            .collect();
+        let mut processor = MedianProcessor::new(0, &params);
+        let mut processed = vec![0.0; SAMPLES];
+        processor.process_block(&input, &mut processed);
+        params.bypass = true;
+        processor.set_parameters(&params);
+        let mut bypassed = vec![0.0; SAMPLES];
+        processor.process_block(&input, &mut bypassed);
+
+        // Skip the start of each, while the loudness is measured or the
+        // bypass crossfades.
+        let rms = |x: &[f32]| {
+            let x = &x[SAMPLES / 2..];
+            (x.iter().map(|x| x * x).sum::<f32>() / x.len() as f32).sqrt()
+        };
+        let difference_db = 20.0 * (rms(&bypassed) / rms(&processed)).log10();
+        assert!(
+            difference_db.abs() <= 0.5,
+            "bypassed is {} dB louder",
+            difference_db
+        );
+    }
+
+    /// The wet signal is unchanged by a shelf at 0 dB, and a boost raises the
+    /// level of high frequencies. With a window of one sample, the wet signal
+    /// is the input.
+    #[test]
+    fn shelf_boosts_highs() {
+        const SAMPLES: usize = 4410;
+        let input: Vec<f32> = (0..SAMPLES)
+            .map(|i| (i as f32 * std::f32::consts::TAU * 15000.0 / 44100.0).sin())
+            .collect();
+        let mut params = MedianFilter::test_parameters(1);
+        let mut output = vec![0.0; SAMPLES];
+        MedianProcessor::new(0, &params).process_block(&input, &mut output);
+        assert_eq!(output, input);
+
+        params.shelf_gain.db = 12.0;
+        MedianProcessor::new(0, &params).process_block(&input, &mut output);
+        let rms = |x: &[f32]| {
+            let x = &x[SAMPLES / 2..];
+            (x.iter().map(|x| x * x).sum::<f32>() / x.len() as f32).sqrt()
+        };
+        let boost_db = 20.0 * (rms(&output) / rms(&input)).log10();
+        assert!(boost_db >= 6.0, "15 kHz was only raised by {} dB", boost_db);
+    }
+
+    /// With `wet_invert` at 100% wet, a filter which passes the signal through
+    /// unchanged nulls it completely. A window of one sample doesn't change
+    /// the signal, and neither does a median of a ramp, which only delays it,
+    /// as long as the dry signal is lined up with the wet signal, even when
+    /// Compensate Dry is off or the input is offset.
+    #[test]
+    fn wet_invert_nulls_unchanged_signal() {
+        let input: Vec<f32> = (0..256).map(|i| i as f32 / 256.0).collect();
+        for &(channel, window_size, stereo_offset) in
+            [(0, 1, 0), (0, 5, 0), (1, 1, 3), (1, 5, 3)].iter()
+        {
+            let mut params = MedianFilter::test_parameters(window_size);
+            params.wet_dry_right = 1.0;
+            params.wet_invert = true;
+            params.stereo_offset = stereo_offset;
+            let mut output = vec![0.0; input.len()];
+            MedianProcessor::new(channel, &params).process_block(&input, &mut output);
+            assert_eq!(
+                output,
+                vec![0.0; input.len()],
+                "channel {} with a window of {} and an offset of {}",
+                channel,
+                window_size,
+                stereo_offset
+            );
+        }
+    }
+
+    /// With `Target::SustainOnly`, the attack of a percussive sound keeps its
+    /// peak, while its decay is still smoothed. The sound is a burst of noise
+    /// which dies away. Smoothing the whole sound, as `Target::Full` does,
+    /// flattens the attack's peak as well.
+    #[test]
+    fn sustain_only_target_keeps_attack() {
+        const WINDOW_SIZE: usize = 15;
+        const DELAY: usize = (WINDOW_SIZE - 1) / 2;
+        const ONSET: usize = 256;
+        const ATTACK: usize = 64;
+        const LENGTH: usize = 8192;
+        let mut seed: u32 = 1;
+        let input: Vec<f32> = (0..LENGTH)
+            .map(|i| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let noise = (seed >> 8) as f32 / (1u32 << 23) as f32 - 1.0;
+                match i.checked_sub(ONSET) {
+                    Some(t) => noise * (-(t as f32) / 2000.0).exp(),
+                    None => 0.0,
+                }
+            })
+            .collect();
+        // How rough a stretch of signal is, from the size of its changes.
+        let roughness =
+            |x: &[f32]| -> f32 { x.windows(2).map(|pair| (pair[1] - pair[0]).powi(2)).sum() };
+        let attack_peak = meters::peak(&input[ONSET..ONSET + ATTACK]);
+        let decay_roughness = roughness(&input[LENGTH / 2..]);
+
+        let mut params = MedianFilter::test_parameters(WINDOW_SIZE);
+        let mut peaks = [0.0; 2];
+        for (&target, peak) in [Target::Full, Target::SustainOnly]
+            .iter()
+            .zip(peaks.iter_mut())
+        {
+            params.target = target;
+            let mut output = vec![0.0; input.len()];
+            MedianProcessor::new(0, &params).process_block(&input, &mut output);
+            *peak = meters::peak(&output[ONSET + DELAY..ONSET + DELAY + ATTACK]);
+            let smoothed = roughness(&output[LENGTH / 2 + DELAY..]);
+            assert!(
+                smoothed <= decay_roughness * 0.5,
+                "{}: decay roughness {}",
+                target,
+                smoothed
+            );
+        }
+        let [full, sustain_only] = peaks;
+        assert!(
+            sustain_only >= attack_peak * 0.9,
+            "Sustain Only peak {}",
+            sustain_only
+        );
+        assert!(full <= attack_peak * 0.9, "Full peak {}", full);
+    }
+
+    /// `SafeMakeup` keeps loud peaks under the ceiling when the
+    /// `ConstantLoudness` makeup would push them over it. The signal is quiet
+    /// noise, which the filter takes out, so the makeup is large, along with
+    /// loud pulses, which the filter leaves alone.
+    #[test]
+    fn safe_makeup_stays_under_ceiling() {
+        const WINDOW_SIZE: usize = 9;
+        let mut seed: u32 = 1;
+        let input: Vec<f32> = (0..16384)
+            .map(|i| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let noise = (seed >> 8) as f32 / (1u32 << 23) as f32 - 1.0;
+                let pulse = if i % 4096 > 4000 { 0.5 } else { 0.0 };
+                0.2 * noise + pulse
+            })
+            .collect();
+        let mut params = MedianFilter::test_parameters(WINDOW_SIZE);
+        params.constant_loudness = true;
+        let ceiling = params.ceiling.linear();
+        for &safe_makeup in [false, true].iter() {
+            params.safe_makeup = safe_makeup;
+            let mut processor = MedianProcessor::new(0, &params);
+            let mut output = vec![0.0; input.len()];
+            let mut backed_off = false;
+            for (x, out) in input.chunks(64).zip(output.chunks_mut(64)) {
+                processor.process_block(x, out);
+                backed_off |= processor.makeup_gain < processor.makeup_target;
+            }
+            let peak = meters::peak(&output);
+            assert_eq!(backed_off, safe_makeup);
+            assert_eq!(
+                peak <= ceiling,
+                safe_makeup,
+                "peak {} against {}",
+                peak,
+                ceiling
+            );
+        }
+    }
+
+    /// With `DynamicWindow`, the window shrinks quickly when a loud sound
+    /// starts, at the attack time, and grows back slowly afterwards, at the
+    /// release time. The window is resized in place, without allocating.
+    #[test]
+    fn dynamic_window_follows_level() {
+        const WINDOW_SIZE: usize = 101;
+        const BLOCK_SIZE: usize = 64;
+        const SAMPLE_RATE: f32 = 44100.0;
+        // 200 ms of a loud sound, after some quiet.
+        const LOUD_BLOCKS: usize = (0.2 * SAMPLE_RATE) as usize / BLOCK_SIZE;
+        let quiet = [0.01; BLOCK_SIZE];
+        let loud = [1.0; BLOCK_SIZE];
+        let mut output = [0.0; BLOCK_SIZE];
+        for &(release_ms, grown_back) in [(10.0, true), (1000.0, false)].iter() {
+            let mut params = MedianFilter::test_parameters(WINDOW_SIZE);
+            params.dynamic_window = 1.0;
+            params.window_attack = 1.0;
+            params.window_release = release_ms;
+            let mut processor = MedianProcessor::new(0, &params);
+            for _ in 0..16 {
+                processor.process_block(&quiet, &mut output);
+            }
+            assert_eq!(processor.window_size, WINDOW_SIZE);
+            processor.process_block(&loud, &mut output);
+            assert!(
+                processor.window_size <= WINDOW_SIZE / 2,
+                "only shrank to {}",
+                processor.window_size
+            );
+            assert_eq!(processor.filter.capacity(), MAX_WINDOW_SIZE);
+            assert_eq!(processor.filter.window_size(), processor.window_size);
+            let shrunk = processor.window_follower.window;
+            for _ in 1..LOUD_BLOCKS {
+                processor.process_block(&loud, &mut output);
+            }
+            // How far the window should have grown back, if it only moved at
+            // the release time. The target grows back too, so it moves less.
+            let elapsed_ms = (LOUD_BLOCKS - 1) as f32 * BLOCK_SIZE as f32 / SAMPLE_RATE * 1000.0;
+            let most = WINDOW_SIZE as f32
+                - (WINDOW_SIZE as f32 - shrunk) * (-elapsed_ms / release_ms).exp();
+            let window = processor.window_size as f32;
+            assert!(
+                window <= most + 1.0,
+                "grew back to {} with a {} ms release",
+                window,
+                release_ms
+            );
+            assert_eq!(
+                window > WINDOW_SIZE as f32 * 0.9,
+                grown_back,
+                "{} ms release",
+                release_ms
+            );
+        }
+    }
+
+    /// Without `Stagger`, both channels use the same window, and with it, the
+    /// right channel's window is longer by the stagger.
+    #[test]
+    fn stagger_lengthens_right_window() {
+        const WINDOW_SIZE: usize = 100;
+        for &(stagger, expected) in [(0.0, WINDOW_SIZE), (3.0, 103), (10.0, 110)].iter() {
+            let mut params = MedianFilter::test_parameters(WINDOW_SIZE);
+            params.stagger = stagger;
+            assert_eq!(MedianProcessor::new(0, &params).window_size, WINDOW_SIZE);
+            assert_eq!(
+                MedianProcessor::new(1, &params).window_size,
+                expected,
+                "{}%",
+                stagger
+            );
+        }
+    }
+
+    /// The delay `OversampleDrive` adds is compensated for, so the inverted
+    /// wet signal still cancels the dry signal.
+    #[test]
+    fn oversample_drive_delay_compensated() {
+        const LENGTH: usize = 4096;
+        let input: Vec<f32> = (0..LENGTH)
+            .map(|i| 0.9 * (std::f32::consts::TAU * (23 * i % LENGTH) as f32 / LENGTH as f32).sin())
+            .collect();
+        let mut params = MedianFilter::test_parameters(1);
+        params.wet_invert = true;
+        params.oversample_drive = true;
+        let mut output = vec![0.0; input.len()];
+        MedianProcessor::new(0, &params).process_block(&input, &mut output);
+        let residual = meters::peak(&output[LENGTH / 2..]);
+        assert!(residual <= 1.0e-4, "{} of the dry signal is left", residual);
+    }
+
+    /// After a gap of silence, the filter follows the resuming signal straight
+    /// away with `SmartWindow`, where without it the silence left in the
+    /// window holds the output at zero.
+    #[test]
+    fn smart_window_skips_silence() {
+        const WINDOW_SIZE: usize = 101;
+        const GAP: usize = 300;
+        const LEVEL: f32 = 0.5;
+        let input: Vec<f32> = (0..3 * GAP)
+            .map(|i| {
+                if (GAP..2 * GAP).contains(&i) {
+                    0.0
+                } else {
+                    LEVEL
+                }
+            })
+            .collect();
+        let resumed = &input[2 * GAP..];
+        for &(smart_window, expected) in &[(false, Some(0)), (true, None)] {
+            let mut params = MedianFilter::test_parameters(WINDOW_SIZE);
+            params.smart_window = smart_window;
+            let mut output = vec![0.0; input.len()];
+            MedianProcessor::new(0, &params).process_block(&input, &mut output);
+            let dip = output[2 * GAP..]
+                .iter()
+                .zip(resumed.iter())
+                .position(|(y, x)| (y - x).abs() > 1.0e-6);
+            assert_eq!(dip, expected, "smart window {}", smart_window);
+        }
+    }
+}
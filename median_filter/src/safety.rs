@@ -0,0 +1,183 @@
+use common::db_to_gain;
+
+/// How quickly the level detector falls after a peak. This keeps the level up
+/// between the peaks of a loud waveform, so that a sustained loud signal is
+/// seen as sustained.
+const LEVEL_RELEASE_MS: f32 = 20.0;
+/// How long it takes to fade the output out or back in.
+const FADE_MS: f32 = 10.0;
+
+/// What `SafetyMute` does when the output stays too loud.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SafetyAction {
+    /// Never mute.
+    Off,
+    /// Mute until the level falls back under the threshold.
+    Mute,
+    /// Mute until `SafetyMute::reset` is called.
+    Latch,
+}
+
+/// Settings for `SafetyMute`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SafetySettings {
+    pub action: SafetyAction,
+    /// The output level, in decibels, above which the output is too loud.
+    pub threshold_db: f32,
+    /// How long the output may stay above the threshold before it is muted.
+    pub hold_ms: f32,
+}
+
+impl Default for SafetySettings {
+    fn default() -> SafetySettings {
+        SafetySettings {
+            action: SafetyAction::Mute,
+            threshold_db: 6.0,
+            hold_ms: 200.0,
+        }
+    }
+}
+
+/// Why the output was muted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MuteReason {
+    /// The level, in decibels, which the output stayed above.
+    pub threshold_db: f32,
+    /// How long it stayed there, in milliseconds.
+    pub duration_ms: f32,
+}
+
+impl std::fmt::Display for MuteReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "output stayed above {:.1} dB for {:.0} ms",
+            self.threshold_db, self.duration_ms
+        )
+    }
+}
+
+/// Mutes the output when it stays dangerously loud for too long, protecting
+/// ears and speakers from extreme parameter combinations, such as a lot of
+/// drive and output gain together.
+pub struct SafetyMute {
+    settings: SafetySettings,
+    sample_rate: f32,
+    level: f32,
+    // How many samples in a row the level has been above the threshold.
+    over_samples: usize,
+    gain: f32,
+    muted: bool,
+    reason: Option<MuteReason>,
+}
+
+impl SafetyMute {
+    pub fn new(settings: SafetySettings, sample_rate: f32) -> SafetyMute {
+        SafetyMute {
+            settings,
+            sample_rate,
+            level: 0.0,
+            over_samples: 0,
+            gain: 1.0,
+            muted: false,
+            reason: None,
+        }
+    }
+
+    pub fn settings(&self) -> SafetySettings {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: SafetySettings) {
+        self.settings = settings;
+        if settings.action == SafetyAction::Off {
+            self.muted = false;
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Whether the output is currently muted, or fading out.
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Why the output was last muted, if it ever was.
+    pub fn reason(&self) -> Option<MuteReason> {
+        self.reason
+    }
+
+    /// Unmute, including after `SafetyAction::Latch`, and forget the level
+    /// measured so far.
+    pub fn reset(&mut self) {
+        self.level = 0.0;
+        self.over_samples = 0;
+        self.muted = false;
+    }
+
+    /// Measure one sample of output, where `peak` is the largest absolute
+    /// value across all channels, and return the gain to apply to it.
+    pub fn process(&mut self, peak: f32) -> f32 {
+        if self.settings.action == SafetyAction::Off {
+            self.gain = 1.0;
+            return self.gain;
+        }
+
+        let release = (-1.0 / (LEVEL_RELEASE_MS / 1000.0 * self.sample_rate).max(1.0)).exp();
+        self.level = peak.max(self.level * release);
+        if self.level > db_to_gain(self.settings.threshold_db) {
+            self.over_samples += 1;
+        } else {
+            self.over_samples = 0;
+        }
+
+        let hold_samples = self.settings.hold_ms / 1000.0 * self.sample_rate;
+        if self.over_samples as f32 > hold_samples && !self.muted {
+            self.muted = true;
+            self.reason = Some(MuteReason {
+                threshold_db: self.settings.threshold_db,
+                duration_ms: self.over_samples as f32 / self.sample_rate * 1000.0,
+            });
+        } else if self.over_samples == 0 && self.settings.action == SafetyAction::Mute {
+            self.muted = false;
+        }
+
+        let fade_step = 1.0 / (FADE_MS / 1000.0 * self.sample_rate).max(1.0);
+        self.gain = if self.muted {
+            (self.gain - fade_step).max(0.0)
+        } else {
+            (self.gain + fade_step).min(1.0)
+        };
+        self.gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sustained over-unity signal is muted once the hold time has passed,
+    /// and not before.
+    #[test]
+    fn mutes_after_hold() {
+        const SAMPLE_RATE: f32 = 44100.0;
+        let settings = SafetySettings {
+            action: SafetyAction::Mute,
+            threshold_db: 0.0,
+            hold_ms: 100.0,
+        };
+        let mut safety = SafetyMute::new(settings, SAMPLE_RATE);
+        let hold_samples = (settings.hold_ms / 1000.0 * SAMPLE_RATE) as usize;
+        for i in 0..hold_samples * 2 {
+            let x = 2.0 * (i as f32 * std::f32::consts::TAU / 100.0).sin();
+            let gain = safety.process(x.abs());
+            if i < hold_samples {
+                assert_eq!(gain, 1.0, "muted after {} samples", i);
+            }
+        }
+        assert_eq!(safety.process(2.0), 0.0);
+        assert!(safety.reason().is_some());
+    }
+}
@@ -0,0 +1,109 @@
+use std::sync::atomic::{fence, AtomicU64, AtomicUsize, Ordering};
+
+use vst::util::AtomicFloat;
+
+use common::plugin::MAX_CHANNELS;
+
+/// How many of the most recent input samples `Scope` holds for each channel.
+/// This is enough for a waveform or a coarse spectrum, without making a
+/// reader copy much on every frame.
+pub const SCOPE_SAMPLES: usize = 2048;
+
+/// The most recent input samples of each channel, for a waveform or spectrum
+/// display in a GUI. Like `Meters`, the audio thread writes to it once per
+/// block, and it may be read from any thread. Reading never blocks the audio
+/// thread.
+pub struct Scope {
+    // A ring buffer of samples for each channel. Every channel's newest
+    // sample is at `(written - 1) % SCOPE_SAMPLES`.
+    samples: Vec<Box<[AtomicFloat]>>,
+    // The number of samples written to each channel so far.
+    written: AtomicU64,
+    // The number of channels written by the last block.
+    channels: AtomicUsize,
+    // Incremented before and after every write, so it is odd while a write is
+    // in progress. See `Meters`.
+    generation: AtomicU64,
+}
+
+impl Scope {
+    pub fn new() -> Scope {
+        Scope {
+            samples: (0..MAX_CHANNELS)
+                .map(|_| (0..SCOPE_SAMPLES).map(|_| AtomicFloat::new(0.0)).collect())
+                .collect(),
+            written: AtomicU64::new(0),
+            channels: AtomicUsize::new(0),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Add a block of each channel's input. This should only be called from
+    /// the audio thread. Channels past `MAX_CHANNELS` are left out.
+    pub fn write(&self, inputs: &[&[f32]]) {
+        let samples = inputs.iter().map(|input| input.len()).min().unwrap_or(0);
+        if samples == 0 {
+            return;
+        }
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.generation.store(generation + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        let written = self.written.load(Ordering::Relaxed);
+        // Only the last `SCOPE_SAMPLES` of a long block would survive.
+        let skipped = samples.saturating_sub(SCOPE_SAMPLES);
+        for (ring, input) in self.samples.iter().zip(inputs.iter()) {
+            for (i, &x) in input[skipped..samples].iter().enumerate() {
+                let position = (written + (skipped + i) as u64) % SCOPE_SAMPLES as u64;
+                ring[position as usize].set(x);
+            }
+        }
+        self.written
+            .store(written + samples as u64, Ordering::Relaxed);
+        self.channels
+            .store(inputs.len().min(MAX_CHANNELS), Ordering::Relaxed);
+
+        self.generation.store(generation + 2, Ordering::Release);
+    }
+
+    /// The number of channels written by the last block.
+    pub fn channels(&self) -> usize {
+        self.channels.load(Ordering::Relaxed)
+    }
+
+    /// Copy the most recent samples of `channel`'s input, oldest first. There
+    /// are `SCOPE_SAMPLES` of them once that many have been written, and fewer
+    /// before then. Returns None if there is no such channel. If the audio
+    /// thread is partway through a write, this waits for it to finish, so the
+    /// samples never mix two blocks.
+    pub fn read(&self, channel: usize) -> Option<Vec<f32>> {
+        let mut samples = Vec::with_capacity(SCOPE_SAMPLES);
+        loop {
+            let generation = self.generation.load(Ordering::Acquire);
+            if generation & 1 == 0 {
+                if channel >= self.channels() {
+                    return None;
+                }
+                let ring = &self.samples[channel];
+                let written = self.written.load(Ordering::Relaxed);
+                let len = written.min(SCOPE_SAMPLES as u64);
+                samples.clear();
+                samples.extend((written - len..written).map(|i| {
+                    let position = i % SCOPE_SAMPLES as u64;
+                    ring[position as usize].get()
+                }));
+                fence(Ordering::Acquire);
+                if self.generation.load(Ordering::Relaxed) == generation {
+                    return Some(samples);
+                }
+            }
+            std::hint::spin_loop();
+        }
+    }
+}
+
+impl Default for Scope {
+    fn default() -> Scope {
+        Scope::new()
+    }
+}
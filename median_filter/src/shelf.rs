@@ -0,0 +1,90 @@
+/// A second order high-shelf filter, which boosts or cuts everything above
+/// its frequency by a fixed gain and leaves everything below alone.
+pub struct HighShelf {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    // The last two inputs and outputs.
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+    // The settings the coefficients were last calculated for.
+    frequency: f32,
+    gain_db: f32,
+    sample_rate: f32,
+}
+
+impl HighShelf {
+    pub fn new(frequency: f32, gain_db: f32, sample_rate: f32) -> HighShelf {
+        let mut shelf = HighShelf {
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+            frequency: 0.0,
+            gain_db: 0.0,
+            sample_rate: 0.0,
+        };
+        shelf.set(frequency, gain_db, sample_rate);
+        shelf
+    }
+
+    /// Set the shelf's frequency, in Hz, and its gain, in decibels.
+    pub fn set(&mut self, frequency: f32, gain_db: f32, sample_rate: f32) {
+        #[allow(clippy::float_cmp)]
+        if frequency == self.frequency && gain_db == self.gain_db && sample_rate == self.sample_rate
+        {
+            return;
+        }
+        self.frequency = frequency;
+        self.gain_db = gain_db;
+        self.sample_rate = sample_rate;
+        // The high shelf from the Audio EQ Cookbook, with a slope of 1.
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let frequency = frequency.min(sample_rate * 0.49);
+        let omega = std::f32::consts::TAU * frequency / sample_rate;
+        let cos = omega.cos();
+        let alpha = omega.sin() / std::f32::consts::SQRT_2;
+        let shelf = 2.0 * a.sqrt() * alpha;
+        let a0 = (a + 1.0) - (a - 1.0) * cos + shelf;
+        self.b0 = a * ((a + 1.0) + (a - 1.0) * cos + shelf) / a0;
+        self.b1 = -2.0 * a * ((a - 1.0) + (a + 1.0) * cos) / a0;
+        self.b2 = a * ((a + 1.0) + (a - 1.0) * cos - shelf) / a0;
+        self.a1 = 2.0 * ((a - 1.0) - (a + 1.0) * cos) / a0;
+        self.a2 = ((a + 1.0) - (a - 1.0) * cos - shelf) / a0;
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        // At 0 dB the shelf does nothing, so pass the signal through exactly,
+        // rather than with rounding errors. The history is still kept, so
+        // that turning the shelf up doesn't click.
+        #[allow(clippy::float_cmp)]
+        let y = if self.gain_db == 0.0 {
+            x
+        } else {
+            self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+                - self.a1 * self.y1
+                - self.a2 * self.y2
+        };
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+
+    pub fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.x2 = 0.0;
+        self.y1 = 0.0;
+        self.y2 = 0.0;
+    }
+}
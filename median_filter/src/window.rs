@@ -0,0 +1,440 @@
+use crate::TieBreak;
+
+/// A sliding window over the last `window_size` samples which also keeps
+/// those samples in sorted order, so that the median (or any other rank) can
+/// be read directly.
+///
+/// Inserting and removing samples shifts the sorted buffer, which costs
+/// O(window size) per sample. For the window sizes this plugin allows, this is
+/// a fast `memmove` and beats a pair of heaps in practice.
+///
+/// All memory is allocated up front, for windows of up to `capacity` samples.
+/// The sorted buffer never holds more than `capacity` samples, so neither
+/// `consume` nor `set_window_size` ever reallocates, which keeps them safe to
+/// call on the audio thread.
+///
+/// Every reading, such as `median`, returns an `f32` by value and only borrows
+/// the window for the length of the call, so readings can be freely
+/// interleaved with `consume`.
+pub struct SortedWindow {
+    // The samples in the order they arrived, as a ring buffer. Only the first
+    // `window_size` samples are used.
+    samples: Box<[f32]>,
+    window_size: usize,
+    // The index in `samples` of the oldest sample.
+    oldest: usize,
+    // The samples currently in the window, in ascending order.
+    sorted: Vec<f32>,
+}
+
+impl SortedWindow {
+    pub fn new(window_size: usize) -> SortedWindow {
+        SortedWindow::with_capacity(window_size, window_size)
+    }
+
+    /// Create a window of `window_size` samples which can later be resized to
+    /// any size up to `capacity` samples without allocating.
+    pub fn with_capacity(window_size: usize, capacity: usize) -> SortedWindow {
+        let capacity = capacity.max(1);
+        SortedWindow {
+            samples: vec![0.0; capacity].into_boxed_slice(),
+            window_size: window_size.clamp(1, capacity),
+            oldest: 0,
+            sorted: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Change the window size, clamped to the window's capacity. This clears
+    /// the window, but keeps its memory.
+    pub fn set_window_size(&mut self, window_size: usize) {
+        self.window_size = window_size.clamp(1, self.samples.len());
+        self.clear();
+    }
+
+    /// The most samples the window holds once it has filled.
+    pub fn window_size(&self) -> usize {
+        self.window_size
+    }
+
+    /// Add a sample to the window, dropping the oldest sample if the window is
+    /// full.
+    pub fn consume(&mut self, x: f32) {
+        // NaNs can't be ordered, so they are treated as silence.
+        let x = if x.is_nan() { 0.0 } else { x };
+        if self.sorted.len() == self.window_size {
+            let removed = self.samples[self.oldest];
+            let index = self.position(removed);
+            self.sorted.remove(index);
+            self.samples[self.oldest] = x;
+            self.oldest = (self.oldest + 1) % self.window_size;
+        } else {
+            self.samples[self.sorted.len()] = x;
+        }
+        let index = self.position(x);
+        let capacity = self.sorted.capacity();
+        self.sorted.insert(index, x);
+        debug_assert_eq!(capacity, self.sorted.capacity(), "window reallocated");
+    }
+
+    /// Remove every sample from the window. The window's memory is kept, so
+    /// this is safe to call on the audio thread.
+    pub fn clear(&mut self) {
+        self.sorted.clear();
+        self.oldest = 0;
+    }
+
+    /// The number of samples currently in the window. This is less than the
+    /// window size until the window has filled.
+    pub fn len(&self) -> usize {
+        self.sorted.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sorted.is_empty()
+    }
+
+    /// The largest window size the window can be set to without allocating.
+    /// This is fixed when the window is created.
+    pub fn capacity(&self) -> usize {
+        self.sorted.capacity()
+    }
+
+    /// The median of the samples in the window, or 0.0 if the window is empty.
+    /// When the window holds an even number of samples, there are two middle
+    /// samples and `tie_break` picks between them.
+    pub fn median(&self, tie_break: TieBreak) -> f32 {
+        let len = self.sorted.len();
+        if len == 0 {
+            return 0.0;
+        }
+        if len % 2 == 1 {
+            return self.sorted[len / 2];
+        }
+        let lower = self.sorted[len / 2 - 1];
+        let upper = self.sorted[len / 2];
+        match tie_break {
+            TieBreak::Average => (lower + upper) / 2.0,
+            TieBreak::Lower => lower,
+            TieBreak::Upper => upper,
+        }
+    }
+
+    /// The weighted median of the samples in the window, where samples within
+    /// `threshold` of zero have a weight of zero and every other sample a
+    /// weight of one. In other words, the median of the window with its
+    /// silent samples left out. If every sample is silent, this is the same
+    /// as `median`.
+    pub fn median_ignoring_silence(&self, threshold: f32, tie_break: TieBreak) -> f32 {
+        // The silent samples are all next to each other in sorted order.
+        let silent_start = self.sorted.partition_point(|&y| y < -threshold);
+        let silent_end = self.sorted.partition_point(|&y| y <= threshold);
+        let silent = silent_end - silent_start;
+        let len = self.sorted.len() - silent;
+        if len == 0 {
+            return self.median(tie_break);
+        }
+        let kept = |i: usize| {
+            if i < silent_start {
+                self.sorted[i]
+            } else {
+                self.sorted[i + silent]
+            }
+        };
+        let lower = kept((len - 1) / 2);
+        if len % 2 == 1 {
+            return lower;
+        }
+        let upper = kept(len / 2);
+        match tie_break {
+            TieBreak::Average => (lower + upper) / 2.0,
+            TieBreak::Lower => lower,
+            TieBreak::Upper => upper,
+        }
+    }
+
+    /// The median absolute deviation of the samples in the window from
+    /// `median`, which should be the window's median. This is a measure of
+    /// how spread out the window is which, unlike the standard deviation, is
+    /// barely affected by a few outliers. Returns 0.0 if the window is empty.
+    pub fn median_absolute_deviation(&self, median: f32, tie_break: TieBreak) -> f32 {
+        let len = self.sorted.len();
+        if len == 0 {
+            return 0.0;
+        }
+        let mut deviations = Deviations::new(&self.sorted, median);
+        // Skip to the middle of the deviations, which come out in ascending
+        // order.
+        for _ in 0..(len - 1) / 2 {
+            deviations.next();
+        }
+        let lower = deviations.next().unwrap_or(0.0);
+        if len % 2 == 1 {
+            return lower;
+        }
+        let upper = deviations.next().unwrap_or(lower);
+        match tie_break {
+            TieBreak::Average => (lower + upper) / 2.0,
+            TieBreak::Lower => lower,
+            TieBreak::Upper => upper,
+        }
+    }
+
+    /// Count the samples in the window into `counts.len()` equally sized
+    /// buckets spanning `min` to `max`. Samples outside of that range are
+    /// counted in the first or last bucket.
+    #[cfg(feature = "analysis")]
+    pub fn histogram(&self, min: f32, max: f32, counts: &mut [usize]) {
+        for count in counts.iter_mut() {
+            *count = 0;
+        }
+        if counts.is_empty() {
+            return;
+        }
+        let buckets = counts.len();
+        for &x in self.sorted.iter() {
+            let position = (x - min) / (max - min) * buckets as f32;
+            let bucket = (position.max(0.0) as usize).min(buckets - 1);
+            counts[bucket] += 1;
+        }
+    }
+
+    /// Check that the sorted buffer is in ascending order and holds exactly
+    /// the samples in the window, returning an error describing the first
+    /// problem found. Equal samples are interchangeable, so removing any one
+    /// of them when the oldest leaves keeps the window correct, however long
+    /// a run of equal samples is. This sorts a copy of the window, so it is
+    /// too slow to call while processing.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(i) = (1..self.sorted.len()).find(|&i| self.sorted[i - 1] > self.sorted[i]) {
+            return Err(format!(
+                "sorted samples {} and {} are out of order",
+                self.sorted[i - 1],
+                self.sorted[i]
+            ));
+        }
+        let mut samples = self.samples[..self.sorted.len()].to_vec();
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        if samples != self.sorted {
+            return Err("the sorted samples differ from the samples in the window".to_string());
+        }
+        Ok(())
+    }
+
+    /// Swap the lowest and highest samples in the sorted buffer, so that tests
+    /// can check that a broken window is caught.
+    #[cfg(test)]
+    pub(crate) fn corrupt(&mut self) {
+        let last = self.sorted.len() - 1;
+        self.sorted.swap(0, last);
+    }
+
+    // The index in `sorted` at which `x` is, or should be inserted.
+    fn position(&self, x: f32) -> usize {
+        self.sorted.partition_point(|&y| y < x)
+    }
+}
+
+/// Iterates over the absolute deviations of sorted samples from a center
+/// value, in ascending order. Samples below the center get further away going
+/// down the list, and samples above it get further away going up, so the two
+/// sides are merged outwards from the center.
+struct Deviations<'a> {
+    sorted: &'a [f32],
+    center: f32,
+    // The next sample below the center, plus one, or 0 if there are none left.
+    below: usize,
+    // The next sample at or above the center.
+    above: usize,
+}
+
+impl<'a> Deviations<'a> {
+    fn new(sorted: &'a [f32], center: f32) -> Deviations<'a> {
+        let split = sorted.partition_point(|&x| x < center);
+        Deviations {
+            sorted,
+            center,
+            below: split,
+            above: split,
+        }
+    }
+}
+
+impl<'a> Iterator for Deviations<'a> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let below = if self.below > 0 {
+            Some(self.center - self.sorted[self.below - 1])
+        } else {
+            None
+        };
+        let above = self.sorted.get(self.above).map(|&x| x - self.center);
+        match (below, above) {
+            (Some(below), Some(above)) if below < above => {
+                self.below -= 1;
+                Some(below)
+            }
+            (_, Some(above)) => {
+                self.above += 1;
+                Some(above)
+            }
+            (Some(below), None) => {
+                self.below -= 1;
+                Some(below)
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_catches_a_broken_window() {
+        let mut window = SortedWindow::new(5);
+        for &x in [0.3, -0.2, 0.9, 0.1].iter() {
+            window.consume(x);
+        }
+        assert_eq!(window.validate(), Ok(()));
+
+        // Out of order.
+        window.sorted.swap(0, 3);
+        assert!(window.validate().is_err());
+
+        // In order, but not the samples in the window.
+        window.sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        window.sorted[0] = -0.5;
+        assert!(window.validate().is_err());
+    }
+
+    /// Each tie-break picks its own median from an even window.
+    #[test]
+    fn tie_breaks() {
+        let mut window = SortedWindow::new(4);
+        for &x in [4.0, 1.0, 3.0, 2.0].iter() {
+            window.consume(x);
+        }
+        assert_eq!(window.median(TieBreak::Average), 2.5);
+        assert_eq!(window.median(TieBreak::Lower), 2.0);
+        assert_eq!(window.median(TieBreak::Upper), 3.0);
+    }
+
+    /// A window doesn't grow while processing a long stream, since the audio
+    /// thread must not allocate.
+    #[test]
+    fn bounded_memory() {
+        let mut window = SortedWindow::new(64);
+        let capacity = window.capacity();
+        for i in 0..100_000 {
+            window.consume((i as f32 * 0.37).sin());
+            assert_eq!(window.capacity(), capacity, "grew after {} samples", i + 1);
+        }
+    }
+
+    /// The histogram of a known window counts each sample in the right bucket.
+    #[cfg(feature = "analysis")]
+    #[test]
+    fn histogram() {
+        let mut window = SortedWindow::new(8);
+        for &x in [-0.9, -0.6, -0.1, 0.1, 0.2, 0.3, 0.7, 2.0].iter() {
+            window.consume(x);
+        }
+        let mut counts = [0; 4];
+        window.histogram(-1.0, 1.0, &mut counts);
+        // The sample at 2.0 is out of range, so it falls in the last bucket.
+        assert_eq!(counts, [2, 1, 3, 2]);
+    }
+
+    /// After millions of identical samples, including denormal ones, the
+    /// window is still consistent and the median follows a change as soon as
+    /// it fills half the window.
+    #[test]
+    fn long_runs() {
+        const WINDOW_SIZE: usize = 101;
+        const CHANGE: f32 = -0.5;
+        for &value in &[0.25, 1.0e-40] {
+            let mut window = SortedWindow::new(WINDOW_SIZE);
+            for _ in 0..2_000_000 {
+                window.consume(value);
+            }
+            assert_eq!(window.validate(), Ok(()));
+            for i in 1..=WINDOW_SIZE / 2 + 1 {
+                window.consume(CHANGE);
+                let expected = if i <= WINDOW_SIZE / 2 { value } else { CHANGE };
+                assert_eq!(
+                    window.median(TieBreak::Average),
+                    expected,
+                    "{} after a run of {}",
+                    i,
+                    value
+                );
+            }
+        }
+    }
+
+    /// Reading the median between each sample added matches sorting the last
+    /// `window_size` samples, for each tie-break, both with and without the
+    /// samples near zero. The input is quantized so that the window often
+    /// holds equal samples.
+    #[test]
+    fn reads_match_sorting() {
+        const SILENCE: f32 = 0.3;
+        let tie_breaks = [TieBreak::Average, TieBreak::Lower, TieBreak::Upper];
+        let mut state = 0x2545_f491u32;
+        let input: Vec<f32> = (0..500)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state % 9) as f32 * 0.25 - 1.0
+            })
+            .collect();
+        for window_size in 1..=8 {
+            let mut window = SortedWindow::new(window_size);
+            for (i, &x) in input.iter().enumerate() {
+                window.consume(x);
+                assert_eq!(window.validate(), Ok(()));
+                let start = (i + 1).saturating_sub(window_size);
+                let mut sorted = input[start..=i].to_vec();
+                sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let mut loud: Vec<f32> = sorted
+                    .iter()
+                    .copied()
+                    .filter(|x| x.abs() > SILENCE)
+                    .collect();
+                if loud.is_empty() {
+                    loud = sorted.clone();
+                }
+                for &tie_break in tie_breaks.iter() {
+                    let median_of = |sorted: &[f32]| {
+                        let len = sorted.len();
+                        let (lower, upper) = (sorted[(len - 1) / 2], sorted[len / 2]);
+                        match tie_break {
+                            TieBreak::Average => (lower + upper) / 2.0,
+                            TieBreak::Lower => lower,
+                            TieBreak::Upper => upper,
+                        }
+                    };
+                    assert_eq!(
+                        window.median(tie_break),
+                        median_of(&sorted),
+                        "median of a window of {} after sample {} with {} tie-break",
+                        window_size,
+                        i,
+                        tie_break
+                    );
+                    assert_eq!(
+                        window.median_ignoring_silence(SILENCE, tie_break),
+                        median_of(&loud),
+                        "median ignoring silence of a window of {} after sample {} with {} tie-break",
+                        window_size,
+                        i,
+                        tie_break
+                    );
+                }
+            }
+        }
+    }
+}
@@ -11,7 +11,7 @@ use vst::{
     util::AtomicFloat,
 };
 
-use common::{ease_in_expo, make_strings};
+use common::{ease_in_expo, make_strings, mapping::Mapping, smoothing::Smoothing};
 
 const MAX_BUFFER_SIZE: usize = 32768; // 2^16
 
@@ -62,7 +62,7 @@ impl Plugin for Stutter {
 
     // Output audio given the current state of the VST
     fn process(&mut self, buffer: &mut AudioBuffer<f32>) {
-        let params = Parameters::from(self.params.as_ref());
+        let params = self.params.snapshot();
         let wet_dry = params.wet_dry;
         let num_samples = buffer.samples();
 
@@ -101,6 +101,8 @@ impl Plugin for Stutter {
         }
 
         self.last_trigger_state = params.trigger;
+
+        self.params.flush_edits();
     }
 
     // The raw parameters exposed to the host
@@ -161,7 +163,7 @@ impl RingBuffer {
     }
 }
 
-struct Parameters {
+pub struct Parameters {
     trigger: bool,
     buffer_size: usize,
     wet_dry: f32,
@@ -184,10 +186,10 @@ macro_rules! table {
         $macro! {
         //  RawParameter identifier, ParameterType identifier
             RawParameters,          ParameterType;
-        //  variant      field_name    name             idx   default    strings
-            WetDry,      wet_dry,      "Wet/Dry",       0,    1.0,       |x: f32| make_strings(x * 100.0, "%");
-            Trigger,     trigger,      "Trigger",       1,    0.0,       |x: bool| if x {("ON".to_string(), "".to_string())} else {("OFF".to_string(), "".to_string())};
-            BufferSize,  buffer_size,  "Buffer Size",   2,    0.5,       |x: usize| (format!("{}", x), "Samples".to_string());
+        //  variant     field_name   name           idx  default  smoothing        mapping              usable_min  usable_max  strings
+            WetDry,     wet_dry,     "Wet/Dry",     0,   1.0,     Smoothing::None, Mapping::Linear,     0.0,        1.0,        |x: f32| make_strings(x * 100.0, "%");
+            Trigger,    trigger,     "Trigger",     1,   0.0,     Smoothing::None, Mapping::Stepped(2), 0.0,        1.0,        |x: bool| if x {("ON".to_string(), "".to_string())} else {("OFF".to_string(), "".to_string())};
+            BufferSize, buffer_size, "Buffer Size", 2,   0.5,     Smoothing::None, Mapping::Log,        0.0,        1.0,        |x: usize| (format!("{}", x), "Samples".to_string());
         }
     };
 }
@@ -198,5 +200,12 @@ impl ParameterType {
 
 impl_all! {RawParameters, ParameterType, table}
 
+impl RawParameters {
+    // None of the parameters can be typed in.
+    fn parse_text(&self, _parameter: ParameterType, _text: &str) -> Option<f32> {
+        None
+    }
+}
+
 // Export symbols for main
 vst::plugin_main!(Stutter);